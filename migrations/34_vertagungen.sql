@@ -0,0 +1,7 @@
+create table vertagungen (
+    id uuid primary key default gen_random_uuid(),
+    top_id uuid not null references tops(id) on delete cascade,
+    von_sitzung_id uuid references sitzungen(id) on delete set null,
+    nach_sitzung_id uuid not null references sitzungen(id) on delete cascade,
+    vertagt_am timestamptz not null default current_timestamp
+);
@@ -0,0 +1,7 @@
+create table sitzung_serien (
+    id uuid primary key default gen_random_uuid(),
+    name text not null
+);
+
+alter table sitzungen
+    add column serie_id uuid references sitzung_serien(id) on delete set null;
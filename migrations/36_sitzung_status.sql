@@ -0,0 +1,19 @@
+create type sitzungstatus as ENUM (
+    'geplant',
+    'abgesagt',
+    'verschoben',
+    'abgeschlossen'
+);
+
+alter table sitzungen
+    add column status sitzungstatus not null default 'geplant',
+    add column status_grund text;
+
+create table verschiebungen (
+    id uuid primary key default gen_random_uuid(),
+    sitzung_id uuid not null references sitzungen(id) on delete cascade,
+    alte_datetime timestamptz not null,
+    neue_datetime timestamptz not null,
+    grund text,
+    verschoben_am timestamptz not null default current_timestamp
+);
@@ -0,0 +1,20 @@
+alter table legislatur_perioden
+    add column start date,
+    add column ende date,
+    add constraint legislatur_perioden_start_vor_ende check (start <= ende),
+    add constraint legislatur_perioden_keine_ueberschneidung
+        exclude using gist (daterange(start, ende, '[]') with &&)
+        where (start is not null and ende is not null);
+
+create type mitgliedsfunktion as ENUM (
+    'gewaehlt',
+    'kooptiert',
+    'beratend'
+);
+
+create table legislatur_perioden_mitglieder (
+    legislatur_periode_id uuid not null references legislatur_perioden(id) on delete cascade,
+    person_id uuid not null references person(id) on delete cascade,
+    funktion mitgliedsfunktion not null,
+    primary key (legislatur_periode_id, person_id)
+);
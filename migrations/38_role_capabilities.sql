@@ -0,0 +1,5 @@
+create table role_capabilities (
+    rolle text not null references roles(name) on delete cascade,
+    capability text not null,
+    primary key (rolle, capability)
+);
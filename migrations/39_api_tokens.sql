@@ -0,0 +1,10 @@
+create table api_tokens (
+    id uuid primary key default gen_random_uuid(),
+    person_id uuid not null references person(id) on delete cascade,
+    name text not null,
+    token_hash text not null unique,
+    capabilities text[] not null,
+    erstellt_am timestamptz not null default current_timestamp,
+    ablaufdatum timestamptz not null,
+    widerrufen_am timestamptz
+);
@@ -0,0 +1,4 @@
+create table person_identities (
+    user_name text primary key,
+    person_id uuid not null references person(id) on delete cascade
+);
@@ -0,0 +1,13 @@
+create table sessions (
+    id uuid primary key default gen_random_uuid(),
+    person_id uuid not null references person(id) on delete cascade,
+    oauth_provider text,
+    user_info jsonb not null,
+    access_token text not null,
+    refresh_token text,
+    erstellt_am timestamptz not null default current_timestamp,
+    zuletzt_aktiv timestamptz not null default current_timestamp,
+    ablaufdatum timestamptz not null
+);
+
+create index sessions_person_id on sessions(person_id);
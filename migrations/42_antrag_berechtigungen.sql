@@ -0,0 +1,18 @@
+create type antragsrolle as enum ('editor', 'viewer', 'reviewer');
+
+create table antrag_berechtigungen (
+    antrags_id uuid not null references antraege(id) on delete cascade,
+    person_id uuid not null references person(id) on delete cascade,
+    rolle antragsrolle not null,
+    primary key (antrags_id, person_id)
+);
+
+create table antrag_kommentare (
+    id uuid primary key default gen_random_uuid(),
+    antrags_id uuid not null references antraege(id) on delete cascade,
+    person_id uuid not null references person(id) on delete cascade,
+    text text not null,
+    erstellt_am timestamptz not null default current_timestamp
+);
+
+create index antrag_kommentare_antrags_id on antrag_kommentare (antrags_id);
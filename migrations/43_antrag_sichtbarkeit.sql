@@ -0,0 +1,3 @@
+create type antragsichtbarkeit as enum ('entwurf', 'intern', 'oeffentlich');
+
+alter table antraege add column sichtbarkeit antragsichtbarkeit not null default 'oeffentlich';
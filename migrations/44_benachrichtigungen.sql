@@ -0,0 +1,6 @@
+create table benachrichtigungs_einstellungen (
+    person_id uuid primary key references person(id) on delete cascade,
+    sitzungen boolean not null default true,
+    antraege boolean not null default true,
+    kommentare boolean not null default true
+);
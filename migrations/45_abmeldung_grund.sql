@@ -0,0 +1,3 @@
+alter table abmeldungen
+    add column grund text,
+    add column privat boolean not null default true;
@@ -0,0 +1,4 @@
+alter table attachments
+    add column content_type text not null default 'application/octet-stream',
+    add column size bigint,
+    add column sha256 text;
@@ -0,0 +1,7 @@
+alter table attachments
+    add column erstellt_am timestamptz not null default current_timestamp,
+    add column bestaetigt boolean not null default true;
+
+-- new attachments are staged until the request uploading them commits
+alter table attachments
+    alter column bestaetigt set default false;
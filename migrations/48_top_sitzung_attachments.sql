@@ -0,0 +1,11 @@
+create table top_attachment_mapping (
+    attachment_id uuid references attachments(id) on delete cascade,
+    top_id uuid references tops(id) on delete cascade,
+    primary key (attachment_id, top_id)
+);
+
+create table sitzung_attachment_mapping (
+    attachment_id uuid references attachments(id) on delete cascade,
+    sitzung_id uuid references sitzungen(id) on delete cascade,
+    primary key (attachment_id, sitzung_id)
+);
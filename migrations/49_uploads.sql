@@ -0,0 +1,8 @@
+-- chunked uploads in progress, their content is kept in the data dir until they are complete
+create table uploads (
+    id uuid primary key default gen_random_uuid(),
+    person_id uuid not null references person(id) on delete cascade,
+    filename text not null,
+    size bigint not null,
+    erstellt_am timestamptz not null default current_timestamp
+);
@@ -0,0 +1,34 @@
+-- full-text search over anträge, tops and the text of attachments. the search vectors of
+-- anträge and tops are only indexed, queries have to use the same functions to hit the index
+create function antrag_suchvektor(titel text, antragstext text, begruendung text)
+returns tsvector
+language sql immutable
+as $$
+    select
+        setweight(to_tsvector('german', titel), 'A') ||
+        setweight(to_tsvector('german', antragstext), 'B') ||
+        setweight(to_tsvector('german', begruendung), 'C')
+$$;
+
+create index antraege_suche on antraege
+    using gin (antrag_suchvektor(titel, antragstext, begruendung));
+
+create function top_suchvektor(name text, inhalt text)
+returns tsvector
+language sql immutable
+as $$
+    select
+        setweight(to_tsvector('german', name), 'A') ||
+        setweight(to_tsvector('german', inhalt), 'B')
+$$;
+
+create index tops_suche on tops using gin (top_suchvektor(name, inhalt));
+
+-- text extracted from pdf and plain text attachments, empty if there was none
+create table attachment_texts (
+    attachment_id uuid primary key references attachments(id) on delete cascade,
+    inhalt text not null,
+    suchvektor tsvector generated always as (to_tsvector('german', inhalt)) stored
+);
+
+create index attachment_texts_suche on attachment_texts using gin (suchvektor);
@@ -0,0 +1,3 @@
+-- staged attachments may only be attached by whoever uploaded them
+alter table attachments
+    add column hochgeladen_von uuid references person(id) on delete set null;
@@ -0,0 +1,33 @@
+-- the capabilities split off of ManageSitzungen and ManagePersons are granted to the roles that
+-- held those, so nobody loses access
+insert into role_capabilities (rolle, capability)
+    select rolle, neu.capability
+    from role_capabilities
+    join (values
+        ('ManageSitzungen', 'ManageLegislaturPerioden'),
+        ('ManageSitzungen', 'ManageCalendars'),
+        ('ManageSitzungen', 'ManageTemplates'),
+        ('ManagePersons', 'ManageLegislaturPerioden'),
+        ('ManagePersons', 'ManageRoles'),
+        ('ManagePersons', 'ViewAudit')
+    ) as neu (alt, capability) on role_capabilities.capability = neu.alt
+    on conflict do nothing;
+
+-- api tokens keep working for what they were scoped to
+update api_tokens
+    set capabilities = array(
+        select distinct unnest(capabilities || array(
+            select neu.capability
+            from (values
+                ('ManageSitzungen', 'ManageLegislaturPerioden'),
+                ('ManageSitzungen', 'ManageCalendars'),
+                ('ManageSitzungen', 'ManageTemplates'),
+                ('ManagePersons', 'ManageLegislaturPerioden'),
+                ('ManagePersons', 'ManageRoles'),
+        ('ManagePersons', 'ViewAudit')
+            ) as neu (alt, capability)
+            where neu.alt = any(api_tokens.capabilities)
+        ))
+    )
+    where capabilities && array['ManageSitzungen', 'ManagePersons'];
+
@@ -0,0 +1,49 @@
+-- who may see an antrag or attachment, like domain::can_view_antrag. the betrachter is given by
+-- whether they may see every antrag, whether they may see interne anträge and their person
+create function antrag_sichtbar(
+    antrags_id uuid,
+    sichtbarkeit antragsichtbarkeit,
+    alle boolean,
+    intern boolean,
+    person uuid
+)
+returns boolean
+language sql stable
+as $$
+    select
+        alle or
+        sichtbarkeit = 'oeffentlich' or
+        (intern and sichtbarkeit = 'intern') or
+        exists (
+            select * from antragsstellende
+            where antragsstellende.antrags_id = antrag_sichtbar.antrags_id
+                and person_id = person
+        ) or
+        exists (
+            select * from antrag_berechtigungen
+            where antrag_berechtigungen.antrags_id = antrag_sichtbar.antrags_id
+                and person_id = person
+        )
+$$;
+
+-- attachments of sitzungen and tops are public, those of anträge are visible with the antrag
+create function anhang_sichtbar(attachment_id uuid, alle boolean, intern boolean, person uuid)
+returns boolean
+language sql stable
+as $$
+    select
+        exists (
+            select * from top_attachment_mapping
+            where top_attachment_mapping.attachment_id = anhang_sichtbar.attachment_id
+        ) or
+        exists (
+            select * from sitzung_attachment_mapping
+            where sitzung_attachment_mapping.attachment_id = anhang_sichtbar.attachment_id
+        ) or
+        exists (
+            select * from attachment_mapping
+                join antraege on antraege.id = attachment_mapping.antrags_id
+            where attachment_mapping.attachment_id = anhang_sichtbar.attachment_id
+                and antrag_sichtbar(antraege.id, antraege.sichtbarkeit, alle, intern, person)
+        )
+$$;
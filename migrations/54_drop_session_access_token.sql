@@ -0,0 +1,2 @@
+-- access tokens are only needed while logging in or refreshing a session, so they are not kept
+alter table sessions drop column access_token;
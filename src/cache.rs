@@ -95,6 +95,11 @@ impl<T: Sync> TimedCache<T> {
             ReadWrapper(result)
         }
     }
+
+    /// Drop the Value of the `TimedCache`, so the next `get` executes the generator again.
+    pub(crate) async fn invalidate(&self) {
+        *self.data_last_updated.write().await = None;
+    }
 }
 
 impl<T: Sync, E: Sync> TimedCache<Result<T, E>> {
@@ -146,6 +151,31 @@ mod test {
         assert_eq!(*cache.get().await, 0);
     }
 
+    #[tokio::test]
+    async fn invalidate() {
+        let x = Arc::new(Mutex::new(0));
+        let a = x.clone();
+
+        let cache = TimedCache::with_generator(
+            move || {
+                let counter = a.clone();
+                Box::pin(async move {
+                    let mut counter = counter.lock().unwrap();
+                    *counter += 1;
+                    *counter
+                })
+            },
+            std::time::Duration::from_secs(60),
+        );
+
+        assert_eq!(*cache.get().await, 1);
+        assert_eq!(*cache.get().await, 1);
+
+        cache.invalidate().await;
+
+        assert_eq!(*cache.get().await, 2);
+    }
+
     #[tokio::test]
     async fn retry() {
         let x = Arc::new(Mutex::new(0));
@@ -153,11 +183,11 @@ mod test {
 
         let cache = TimedCache::with_generator(
             move || {
-                let x = a.clone();
+                let counter = a.clone();
                 Box::pin(async move {
-                    let mut x = x.lock().unwrap();
-                    *x += 1;
-                    if *x == 1 {
+                    let mut counter = counter.lock().unwrap();
+                    *counter += 1;
+                    if *counter == 1 {
                         Err(1)
                     } else {
                         Ok(2)
@@ -3,7 +3,11 @@ use sqlx::{PgConnection, QueryBuilder};
 use uuid::Uuid;
 
 use crate::domain::{
-    antrag::{Antrag, AntragData, AntragRepo},
+    antrag::{
+        Antrag, AntragBerechtigung, AntragBetrachter, AntragData, AntragFilter, AntragKommentar,
+        AntragRepo, AntragsRolle, Sichtbarkeit,
+    },
+    pagination::{Page, Pagination},
     Result,
 };
 
@@ -70,18 +74,26 @@ impl AntragRepo for PgConnection {
         begruendung: &str,
         antragstext: &str,
         erstellt_am: DateTime<Utc>,
+        sichtbarkeit: Sichtbarkeit,
     ) -> Result<Antrag> {
         let antrag = sqlx::query_as!(
             AntragData,
             r#"
-                INSERT INTO antraege (titel, antragstext, begruendung, erstellt_am) 
-                VALUES ($1, $2, $3, $4) 
-                RETURNING *
+                INSERT INTO antraege (titel, antragstext, begruendung, erstellt_am, sichtbarkeit) 
+                VALUES ($1, $2, $3, $4, $5) 
+                RETURNING
+                    id,
+                    titel,
+                    antragstext,
+                    begruendung,
+                    erstellt_am,
+                    sichtbarkeit AS "sichtbarkeit!: Sichtbarkeit"
             "#,
             title,
             antragstext,
             begruendung,
-            erstellt_am
+            erstellt_am,
+            sichtbarkeit as Sichtbarkeit
         )
         .fetch_one(&mut *self)
         .await?;
@@ -97,7 +109,42 @@ impl AntragRepo for PgConnection {
         Ok(result)
     }
 
-    async fn antraege(&mut self) -> Result<Vec<Antrag>> {
+    async fn antraege(
+        &mut self,
+        betrachter: &AntragBetrachter,
+        filter: &AntragFilter,
+        pagination: Pagination,
+    ) -> Result<Page<Antrag>> {
+        let person_id = betrachter.person.as_ref().map(|person| person.id);
+
+        // the sichtbarkeit is checked in the query, so pages stay full
+        let total = sqlx::query_scalar!(
+            r#"
+                SELECT count(*) AS "total!" FROM antraege
+                WHERE
+                    antrag_sichtbar(id, sichtbarkeit, $1, $2, $3) AND
+                    (
+                        $4::uuid IS NULL OR
+                        EXISTS (
+                            SELECT * FROM antragsstellende
+                            WHERE antrags_id = antraege.id AND person_id = $4
+                        )
+                    ) AND
+                    ($5::timestamptz IS NULL OR erstellt_am >= $5) AND
+                    ($6::timestamptz IS NULL OR erstellt_am <= $6) AND
+                    ($7::antragsichtbarkeit IS NULL OR sichtbarkeit = $7)
+            "#,
+            betrachter.alle,
+            betrachter.intern,
+            person_id,
+            filter.ersteller,
+            filter.erstellt_ab,
+            filter.erstellt_bis,
+            filter.sichtbarkeit as Option<Sichtbarkeit>,
+        )
+        .fetch_one(&mut *self)
+        .await?;
+
         let anträge = sqlx::query_as!(
             AntragData,
             r#"
@@ -106,9 +153,100 @@ impl AntragRepo for PgConnection {
                     titel,
                     antragstext,
                     begruendung,
-                    erstellt_am 
+                    erstellt_am,
+                    sichtbarkeit AS "sichtbarkeit!: Sichtbarkeit"
+                FROM antraege
+                WHERE
+                    antrag_sichtbar(id, sichtbarkeit, $1, $2, $3) AND
+                    (
+                        $4::uuid IS NULL OR
+                        EXISTS (
+                            SELECT * FROM antragsstellende
+                            WHERE antrags_id = antraege.id AND person_id = $4
+                        )
+                    ) AND
+                    ($5::timestamptz IS NULL OR erstellt_am >= $5) AND
+                    ($6::timestamptz IS NULL OR erstellt_am <= $6) AND
+                    ($7::antragsichtbarkeit IS NULL OR sichtbarkeit = $7)
+                ORDER BY
+                    CASE WHEN $8 = 'erstellt_am' AND NOT $9 THEN erstellt_am END ASC,
+                    CASE WHEN $8 = 'erstellt_am' AND $9 THEN erstellt_am END DESC,
+                    CASE WHEN $8 = 'titel' AND NOT $9 THEN titel END ASC,
+                    CASE WHEN $8 = 'titel' AND $9 THEN titel END DESC,
+                    id
+                LIMIT $10 OFFSET $11
+            "#,
+            betrachter.alle,
+            betrachter.intern,
+            person_id,
+            filter.ersteller,
+            filter.erstellt_ab,
+            filter.erstellt_bis,
+            filter.sichtbarkeit as Option<Sichtbarkeit>,
+            filter.sort.as_ref(),
+            filter.order.is_desc(),
+            pagination.limit,
+            pagination.offset,
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        let mut items = Vec::new();
+
+        for data in anträge {
+            let creators = query_antragsstellende(&mut *self, data.id).await?;
+            let attachments = query_attachments(&mut *self, data.id).await?;
+
+            items.push(Antrag {
+                data,
+                ersteller: creators,
+                anhaenge: attachments,
+            })
+        }
+
+        Ok(Page { items, total })
+    }
+
+    async fn visible_antrag_ids(
+        &mut self,
+        betrachter: &AntragBetrachter,
+        ids: &[Uuid],
+    ) -> Result<Vec<Uuid>> {
+        let person_id = betrachter.person.as_ref().map(|person| person.id);
+
+        let result = sqlx::query_scalar!(
+            r#"
+                SELECT id FROM antraege
+                WHERE id = ANY($1) AND antrag_sichtbar(id, sichtbarkeit, $2, $3, $4)
+            "#,
+            ids,
+            betrachter.alle,
+            betrachter.intern,
+            person_id as Option<Uuid>,
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn antraege_by_ersteller(&mut self, person_id: Uuid) -> Result<Vec<Antrag>> {
+        let anträge = sqlx::query_as!(
+            AntragData,
+            r#"
+                SELECT
+                    antraege.id,
+                    antraege.titel,
+                    antraege.antragstext,
+                    antraege.begruendung,
+                    antraege.erstellt_am,
+                    antraege.sichtbarkeit AS "sichtbarkeit!: Sichtbarkeit"
                 FROM antraege
-            "#
+                JOIN antragsstellende
+                ON antraege.id = antragsstellende.antrags_id
+                WHERE antragsstellende.person_id = $1
+            "#,
+            person_id
         )
         .fetch_all(&mut *self)
         .await?;
@@ -120,7 +258,7 @@ impl AntragRepo for PgConnection {
             let attachments = query_attachments(&mut *self, data.id).await?;
 
             result.push(Antrag {
-                data: data.clone(),
+                data,
                 ersteller: creators,
                 anhaenge: attachments,
             })
@@ -138,7 +276,8 @@ impl AntragRepo for PgConnection {
                     antraege.titel,
                     antraege.antragstext,
                     antraege.begruendung,
-                    antraege.erstellt_am
+                    antraege.erstellt_am,
+                    antraege.sichtbarkeit AS "sichtbarkeit!: Sichtbarkeit"
                 FROM antraege
                 WHERE id = $1
             "#,
@@ -181,7 +320,13 @@ impl AntragRepo for PgConnection {
                     antragstext = COALESCE($3, antragstext),
                     erstellt_am = $4
                 WHERE id = $5
-                RETURNING *
+                RETURNING
+                    id,
+                    titel,
+                    antragstext,
+                    begruendung,
+                    erstellt_am,
+                    sichtbarkeit AS "sichtbarkeit!: Sichtbarkeit"
             "#,
             title,
             reason,
@@ -224,13 +369,47 @@ impl AntragRepo for PgConnection {
         Ok(Some(result))
     }
 
+    async fn set_antrag_sichtbarkeit(
+        &mut self,
+        id: Uuid,
+        sichtbarkeit: Sichtbarkeit,
+    ) -> Result<Option<AntragData>> {
+        let result = sqlx::query_as!(
+            AntragData,
+            r#"
+                UPDATE antraege
+                SET sichtbarkeit = $2
+                WHERE id = $1
+                RETURNING
+                    id,
+                    titel,
+                    antragstext,
+                    begruendung,
+                    erstellt_am,
+                    sichtbarkeit AS "sichtbarkeit!: Sichtbarkeit"
+            "#,
+            id,
+            sichtbarkeit as Sichtbarkeit
+        )
+        .fetch_optional(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
     async fn delete_antrag(&mut self, id: Uuid) -> Result<Option<AntragData>> {
         let result = sqlx::query_as!(
             AntragData,
             r#"
                 DELETE FROM antraege 
                 WHERE id = $1
-                RETURNING *
+                RETURNING
+                    id,
+                    titel,
+                    antragstext,
+                    begruendung,
+                    erstellt_am,
+                    sichtbarkeit AS "sichtbarkeit!: Sichtbarkeit"
             "#,
             id
         )
@@ -273,6 +452,131 @@ impl AntragRepo for PgConnection {
 
         Ok(())
     }
+
+    async fn berechtigungen_by_antrag(
+        &mut self,
+        antrag_id: Uuid,
+    ) -> Result<Vec<AntragBerechtigung>> {
+        let result = sqlx::query_as!(
+            AntragBerechtigung,
+            r#"
+                SELECT antrags_id AS antrag_id, person_id, rolle AS "rolle!: AntragsRolle"
+                FROM antrag_berechtigungen
+                WHERE antrags_id = $1
+            "#,
+            antrag_id
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn berechtigung(
+        &mut self,
+        antrag_id: Uuid,
+        person_id: Uuid,
+    ) -> Result<Option<AntragBerechtigung>> {
+        let result = sqlx::query_as!(
+            AntragBerechtigung,
+            r#"
+                SELECT antrags_id AS antrag_id, person_id, rolle AS "rolle!: AntragsRolle"
+                FROM antrag_berechtigungen
+                WHERE antrags_id = $1 AND person_id = $2
+            "#,
+            antrag_id,
+            person_id
+        )
+        .fetch_optional(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn set_berechtigung(
+        &mut self,
+        antrag_id: Uuid,
+        person_id: Uuid,
+        rolle: AntragsRolle,
+    ) -> Result<AntragBerechtigung> {
+        let result = sqlx::query_as!(
+            AntragBerechtigung,
+            r#"
+                INSERT INTO antrag_berechtigungen (antrags_id, person_id, rolle)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (antrags_id, person_id) DO UPDATE SET rolle = $3
+                RETURNING antrags_id AS antrag_id, person_id, rolle AS "rolle!: AntragsRolle"
+            "#,
+            antrag_id,
+            person_id,
+            rolle as AntragsRolle
+        )
+        .fetch_one(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_berechtigung(
+        &mut self,
+        antrag_id: Uuid,
+        person_id: Uuid,
+    ) -> Result<Option<AntragBerechtigung>> {
+        let result = sqlx::query_as!(
+            AntragBerechtigung,
+            r#"
+                DELETE FROM antrag_berechtigungen
+                WHERE antrags_id = $1 AND person_id = $2
+                RETURNING antrags_id AS antrag_id, person_id, rolle AS "rolle!: AntragsRolle"
+            "#,
+            antrag_id,
+            person_id
+        )
+        .fetch_optional(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn create_kommentar(
+        &mut self,
+        antrag_id: Uuid,
+        person_id: Uuid,
+        text: &str,
+    ) -> Result<AntragKommentar> {
+        let result = sqlx::query_as!(
+            AntragKommentar,
+            r#"
+                INSERT INTO antrag_kommentare (antrags_id, person_id, text)
+                VALUES ($1, $2, $3)
+                RETURNING id, antrags_id AS antrag_id, person_id, text, erstellt_am
+            "#,
+            antrag_id,
+            person_id,
+            text
+        )
+        .fetch_one(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn kommentare_by_antrag(&mut self, antrag_id: Uuid) -> Result<Vec<AntragKommentar>> {
+        let result = sqlx::query_as!(
+            AntragKommentar,
+            r#"
+                SELECT id, antrags_id AS antrag_id, person_id, text, erstellt_am
+                FROM antrag_kommentare
+                WHERE antrags_id = $1
+                ORDER BY erstellt_am
+            "#,
+            antrag_id
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
 }
 
 #[cfg(test)]
@@ -282,7 +586,12 @@ mod test {
     use sqlx::PgPool;
     use uuid::Uuid;
 
-    use crate::domain::antrag::{Antrag, AntragData, AntragRepo};
+    use crate::domain::antrag::{
+        Antrag, AntragBetrachter, AntragData, AntragFilter, AntragRepo, AntragSort, AntragsRolle,
+        Sichtbarkeit,
+    };
+    use crate::domain::pagination::{Pagination, SortOrder};
+    use crate::domain::persons::PersonRepo;
 
     #[sqlx::test(fixtures("gimme_persons"))]
     async fn create_antrag(pool: PgPool) -> Result<()> {
@@ -305,6 +614,7 @@ mod test {
                 begruendung,
                 antragstext,
                 erstellt_am,
+                Sichtbarkeit::Oeffentlich,
             )
             .await?;
 
@@ -331,12 +641,20 @@ mod test {
         let erstellt_am = DateTime::UNIX_EPOCH;
 
         let antrag = conn
-            .create_antrag(&[], title, begruendung, antragstext, erstellt_am)
+            .create_antrag(
+                &[],
+                title,
+                begruendung,
+                antragstext,
+                erstellt_am,
+                Sichtbarkeit::Entwurf,
+            )
             .await?;
 
         assert_eq!(antrag.data.titel, title);
         assert_eq!(antrag.data.antragstext, antragstext);
         assert_eq!(antrag.data.begruendung, begruendung);
+        assert_eq!(antrag.data.sichtbarkeit, Sichtbarkeit::Entwurf);
 
         assert!(antrag.ersteller.is_empty());
 
@@ -360,9 +678,15 @@ mod test {
     async fn anträge_empty_creators(pool: PgPool) -> Result<()> {
         let mut conn = pool.acquire().await?;
 
-        let antrag = conn.antraege().await?;
+        let antrag = conn
+            .antraege(
+                &AntragBetrachter::default(),
+                &AntragFilter::default(),
+                Pagination::alle(),
+            )
+            .await?;
 
-        assert!(!antrag.is_empty());
+        assert!(!antrag.items.is_empty());
 
         Ok(())
     }
@@ -371,7 +695,14 @@ mod test {
     async fn anträge(pool: PgPool) -> Result<()> {
         let mut conn = pool.acquire().await?;
 
-        let anträge = conn.antraege().await?;
+        let anträge = conn
+            .antraege(
+                &AntragBetrachter::default(),
+                &AntragFilter::default(),
+                Pagination::alle(),
+            )
+            .await?
+            .items;
 
         let creators1 = vec![
             Uuid::parse_str("5a5a134d-9345-4c36-a466-1c3bb806b240").unwrap(),
@@ -391,6 +722,7 @@ mod test {
                 antragstext: antragstext1.to_string(),
                 begruendung: begruendung1.to_string(),
                 erstellt_am: created_at1.parse().unwrap(),
+                sichtbarkeit: Sichtbarkeit::Oeffentlich,
             },
             ersteller: creators1,
             anhaenge: vec![],
@@ -411,6 +743,7 @@ mod test {
                 antragstext: antragstext2.to_string(),
                 begruendung: begruendung2.to_string(),
                 erstellt_am: created_at2.parse().unwrap(),
+                sichtbarkeit: Sichtbarkeit::Oeffentlich,
             },
             ersteller: creators2,
             anhaenge: vec![],
@@ -424,6 +757,122 @@ mod test {
         Ok(())
     }
 
+    #[sqlx::test(fixtures("gimme_persons", "gimme_antraege"))]
+    async fn antraege_paginated(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let anonym = AntragBetrachter::default();
+        let volthahn = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let blub = Uuid::parse_str("5c51d5c0-3943-4695-844d-4c47da854fac").unwrap();
+
+        let neueste = conn
+            .antraege(
+                &anonym,
+                &AntragFilter {
+                    order: SortOrder::Desc,
+                    ..Default::default()
+                },
+                Pagination {
+                    limit: Some(1),
+                    offset: 0,
+                },
+            )
+            .await?;
+
+        assert_eq!(neueste.total, 2);
+        assert_eq!(neueste.items.len(), 1);
+        assert_eq!(neueste.items[0].data.id, blub);
+
+        // how titles compare depends on the collation of the database
+        let nach_titel = conn
+            .antraege(
+                &anonym,
+                &AntragFilter {
+                    sort: AntragSort::Titel,
+                    ..Default::default()
+                },
+                Pagination::alle(),
+            )
+            .await?;
+        let zweite_seite = conn
+            .antraege(
+                &anonym,
+                &AntragFilter {
+                    sort: AntragSort::Titel,
+                    order: SortOrder::Desc,
+                    ..Default::default()
+                },
+                Pagination {
+                    limit: Some(1),
+                    offset: 1,
+                },
+            )
+            .await?;
+
+        assert_eq!(zweite_seite.total, 2);
+        assert_eq!(zweite_seite.items, nach_titel.items[..1]);
+
+        let ersteller_id = Uuid::parse_str("0f3107ac-745d-4077-8bbf-f9734cd66297").unwrap();
+        let vom_ersteller = conn
+            .antraege(
+                &anonym,
+                &AntragFilter {
+                    ersteller: Some(ersteller_id),
+                    erstellt_ab: Some("2021-08-02T00:00:00Z".parse()?),
+                    ..Default::default()
+                },
+                Pagination::alle(),
+            )
+            .await?;
+
+        assert_eq!(vom_ersteller.total, 1);
+        assert_eq!(vom_ersteller.items[0].data.id, blub);
+
+        conn.set_antrag_sichtbarkeit(blub, Sichtbarkeit::Entwurf)
+            .await?;
+
+        let oeffentlich = conn
+            .antraege(&anonym, &AntragFilter::default(), Pagination::alle())
+            .await?;
+        assert_eq!(oeffentlich.total, 1);
+        assert_eq!(oeffentlich.items[0].data.id, volthahn);
+
+        let ersteller = AntragBetrachter {
+            person: conn.person_by_id(ersteller_id).await?,
+            ..Default::default()
+        };
+        let eigene = conn
+            .antraege(&ersteller, &AntragFilter::default(), Pagination::alle())
+            .await?;
+        assert_eq!(eigene.total, 2);
+
+        assert_eq!(
+            conn.visible_antrag_ids(&anonym, &[volthahn, blub]).await?,
+            vec![volthahn]
+        );
+        assert_eq!(
+            conn.visible_antrag_ids(&ersteller, &[blub]).await?,
+            vec![blub]
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("gimme_persons", "gimme_antraege"))]
+    async fn antraege_by_ersteller(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let person_id = Uuid::parse_str("0f3107ac-745d-4077-8bbf-f9734cd66297").unwrap();
+
+        let anträge = conn.antraege_by_ersteller(person_id).await?;
+
+        assert_eq!(anträge.len(), 1);
+        assert_eq!(anträge[0].data.titel, "blub");
+        assert_eq!(anträge[0].ersteller, vec![person_id]);
+
+        Ok(())
+    }
+
     #[sqlx::test(fixtures("gimme_persons", "gimme_antraege"))]
     async fn antrag_by_id(pool: PgPool) -> Result<()> {
         let mut conn = pool.acquire().await?;
@@ -543,4 +992,47 @@ mod test {
 
         Ok(())
     }
+
+    #[sqlx::test(fixtures("gimme_persons", "gimme_antraege"))]
+    async fn berechtigungen(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let person_id = Uuid::parse_str("78be7f57-8340-43e0-bba2-074da360ddf4").unwrap();
+
+        conn.set_berechtigung(antrag_id, person_id, AntragsRolle::Viewer)
+            .await?;
+        let berechtigung = conn
+            .set_berechtigung(antrag_id, person_id, AntragsRolle::Reviewer)
+            .await?;
+
+        assert_eq!(berechtigung.rolle, AntragsRolle::Reviewer);
+        assert_eq!(
+            conn.berechtigungen_by_antrag(antrag_id).await?,
+            vec![berechtigung]
+        );
+
+        conn.delete_berechtigung(antrag_id, person_id).await?;
+
+        assert!(conn.berechtigung(antrag_id, person_id).await?.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("gimme_persons", "gimme_antraege"))]
+    async fn kommentare(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let person_id = Uuid::parse_str("78be7f57-8340-43e0-bba2-074da360ddf4").unwrap();
+
+        let kommentar = conn
+            .create_kommentar(antrag_id, person_id, "Mehr Volt!")
+            .await?;
+
+        assert_eq!(kommentar.text, "Mehr Volt!");
+        assert_eq!(conn.kommentare_by_antrag(antrag_id).await?, vec![kommentar]);
+
+        Ok(())
+    }
 }
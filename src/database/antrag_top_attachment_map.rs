@@ -2,7 +2,7 @@ use sqlx::PgConnection;
 use uuid::Uuid;
 
 use crate::domain::{
-    antrag::{Antrag, AntragData},
+    antrag::{Antrag, AntragData, Sichtbarkeit},
     antrag_top_attachment_map::{AntragTopAttachmentMap, AntragTopMapping},
     sitzung::{Top, TopTyp},
     Result,
@@ -40,7 +40,8 @@ impl AntragTopAttachmentMap for PgConnection {
                     antraege.antragstext,
                     antraege.begruendung,
                     antraege.titel,
-                    antraege.erstellt_am
+                    antraege.erstellt_am,
+                    antraege.sichtbarkeit AS "sichtbarkeit!: Sichtbarkeit"
                 FROM antraege
                 JOIN antragstop
                 ON antraege.id = antragstop.antrag_id
@@ -102,7 +103,8 @@ impl AntragTopAttachmentMap for PgConnection {
                     antragstext,
                     begruendung,
                     titel,
-                    erstellt_am
+                    erstellt_am,
+                    sichtbarkeit AS "sichtbarkeit!: Sichtbarkeit"
                 FROM antraege
                 LEFT JOIN antragstop
                 ON antraege.id = antragstop.antrag_id
@@ -0,0 +1,201 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::domain::{
+    api_token::{ApiToken, ApiTokenRepo},
+    Capability, Result,
+};
+
+use super::parse_capabilities;
+
+impl ApiTokenRepo for PgConnection {
+    async fn create_api_token(
+        &mut self,
+        person_id: Uuid,
+        name: &str,
+        token_hash: &str,
+        capabilities: &[Capability],
+        ablaufdatum: DateTime<Utc>,
+    ) -> Result<ApiToken> {
+        let capabilities = capabilities
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        let r = sqlx::query!(
+            r#"
+                INSERT INTO api_tokens (person_id, name, token_hash, capabilities, ablaufdatum)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING id, person_id, name, capabilities, erstellt_am, ablaufdatum, widerrufen_am
+            "#,
+            person_id,
+            name,
+            token_hash,
+            &capabilities,
+            ablaufdatum
+        )
+        .fetch_one(self)
+        .await?;
+
+        Ok(ApiToken {
+            id: r.id,
+            person_id: r.person_id,
+            name: r.name,
+            capabilities: parse_capabilities(r.capabilities),
+            erstellt_am: r.erstellt_am,
+            ablaufdatum: r.ablaufdatum,
+            widerrufen_am: r.widerrufen_am,
+        })
+    }
+
+    async fn api_token_by_hash(&mut self, token_hash: &str) -> Result<Option<ApiToken>> {
+        let record = sqlx::query!(
+            r#"
+                SELECT id, person_id, name, capabilities, erstellt_am, ablaufdatum, widerrufen_am
+                FROM api_tokens
+                WHERE token_hash = $1
+            "#,
+            token_hash
+        )
+        .fetch_optional(self)
+        .await?;
+
+        let result = record.map(|r| ApiToken {
+            id: r.id,
+            person_id: r.person_id,
+            name: r.name,
+            capabilities: parse_capabilities(r.capabilities),
+            erstellt_am: r.erstellt_am,
+            ablaufdatum: r.ablaufdatum,
+            widerrufen_am: r.widerrufen_am,
+        });
+
+        Ok(result)
+    }
+
+    async fn api_tokens_by_person(&mut self, person_id: Uuid) -> Result<Vec<ApiToken>> {
+        let records = sqlx::query!(
+            r#"
+                SELECT id, person_id, name, capabilities, erstellt_am, ablaufdatum, widerrufen_am
+                FROM api_tokens
+                WHERE person_id = $1
+                ORDER BY erstellt_am ASC
+            "#,
+            person_id
+        )
+        .fetch_all(self)
+        .await?;
+
+        let result = records
+            .into_iter()
+            .map(|r| ApiToken {
+                id: r.id,
+                person_id: r.person_id,
+                name: r.name,
+                capabilities: parse_capabilities(r.capabilities),
+                erstellt_am: r.erstellt_am,
+                ablaufdatum: r.ablaufdatum,
+                widerrufen_am: r.widerrufen_am,
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    async fn revoke_api_token(
+        &mut self,
+        id: Uuid,
+        person_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ApiToken>> {
+        let record = sqlx::query!(
+            r#"
+                UPDATE api_tokens
+                SET widerrufen_am = COALESCE(widerrufen_am, $3)
+                WHERE id = $1 AND person_id = $2
+                RETURNING id, person_id, name, capabilities, erstellt_am, ablaufdatum, widerrufen_am
+            "#,
+            id,
+            person_id,
+            now
+        )
+        .fetch_optional(self)
+        .await?;
+
+        let result = record.map(|r| ApiToken {
+            id: r.id,
+            person_id: r.person_id,
+            name: r.name,
+            capabilities: parse_capabilities(r.capabilities),
+            erstellt_am: r.erstellt_am,
+            ablaufdatum: r.ablaufdatum,
+            widerrufen_am: r.widerrufen_am,
+        });
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use chrono::{DateTime, TimeDelta};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::domain::{
+        api_token::{self, ApiTokenRepo},
+        Capability,
+    };
+
+    #[sqlx::test(fixtures("gimme_persons"))]
+    async fn create_and_revoke_api_token(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let person_id = Uuid::parse_str("0f3107ac-745d-4077-8bbf-f9734cd66297").unwrap();
+        let now = DateTime::parse_from_rfc3339("2024-09-01T00:00:00+02:00")
+            .unwrap()
+            .into();
+
+        let created = api_token::create_api_token(
+            &mut *conn,
+            person_id,
+            "matrix bot",
+            &[Capability::CreateAntrag],
+            now + TimeDelta::days(30),
+        )
+        .await?;
+
+        assert!(created.secret.starts_with(api_token::API_TOKEN_PREFIX));
+
+        let token = api_token::valid_api_token(&mut *conn, &created.secret, now)
+            .await?
+            .unwrap();
+
+        assert_eq!(token.id, created.token.id);
+        assert_eq!(token.capabilities, vec![Capability::CreateAntrag]);
+
+        let expired =
+            api_token::valid_api_token(&mut *conn, &created.secret, now + TimeDelta::days(31))
+                .await?;
+
+        assert!(expired.is_none());
+
+        let other_person = Uuid::parse_str("5a5a134d-9345-4c36-a466-1c3bb806b240").unwrap();
+
+        assert!(conn
+            .revoke_api_token(token.id, other_person, now)
+            .await?
+            .is_none());
+
+        conn.revoke_api_token(token.id, person_id, now).await?;
+
+        let revoked = api_token::valid_api_token(&mut *conn, &created.secret, now).await?;
+
+        assert!(revoked.is_none());
+        assert_eq!(conn.api_tokens_by_person(person_id).await?.len(), 1);
+
+        Ok(())
+    }
+}
@@ -1,18 +1,31 @@
-use sqlx::PgConnection;
+use chrono::{DateTime, Utc};
+use sqlx::{Connection, PgConnection};
+use uuid::Uuid;
 
 use crate::domain::anhang::{Anhang, AnhangRepo};
 use crate::domain::Result;
 
 impl AnhangRepo for PgConnection {
-    async fn create_anhang(&mut self, filename: String) -> Result<Anhang> {
+    async fn create_anhang(
+        &mut self,
+        filename: &str,
+        content_type: &str,
+        size: i64,
+        sha256: &str,
+        hochgeladen_von: Uuid,
+    ) -> Result<Anhang> {
         let result = sqlx::query_as!(
             Anhang,
             r#"
-                INSERT INTO attachments (filename)
-                VALUES ($1)
+                INSERT INTO attachments (filename, content_type, size, sha256, hochgeladen_von)
+                VALUES ($1, $2, $3, $4, $5)
                 RETURNING *
             "#,
-            filename
+            filename,
+            content_type,
+            size,
+            sha256,
+            hochgeladen_von
         )
         .fetch_one(self)
         .await?;
@@ -20,23 +33,184 @@ impl AnhangRepo for PgConnection {
         Ok(result)
     }
 
-    async fn delete_anhang(&mut self, id: uuid::Uuid) -> Result<Option<Anhang>> {
+    async fn confirm_anhang(&mut self, id: Uuid, hochgeladen_von: Uuid) -> Result<Option<Anhang>> {
         let result = sqlx::query_as!(
             Anhang,
             r#"
-                DELETE FROM attachments
+                UPDATE attachments
+                SET bestaetigt = true
+                WHERE id = $1 AND NOT bestaetigt AND hochgeladen_von = $2
+                RETURNING *
+            "#,
+            id,
+            hochgeladen_von
+        )
+        .fetch_optional(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_unused_anhang(
+        &mut self,
+        id: Uuid,
+        staged_before: Option<DateTime<Utc>>,
+    ) -> Result<Option<Anhang>> {
+        let mut transaction = self.begin().await?;
+
+        // waits for transactions confirming or attaching the Anhang and keeps new ones from
+        // starting, so the check below sees their mappings
+        sqlx::query!(
+            r#"
+                SELECT id FROM attachments
                 WHERE id = $1
+                FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        let result = sqlx::query_as!(
+            Anhang,
+            r#"
+                DELETE FROM attachments
+                WHERE
+                    id = $1 AND
+                    ((bestaetigt AND
+                        NOT EXISTS (
+                            SELECT * FROM attachment_mapping
+                            WHERE attachment_id = attachments.id
+                        ) AND
+                        NOT EXISTS (
+                            SELECT * FROM top_attachment_mapping
+                            WHERE attachment_id = attachments.id
+                        ) AND
+                        NOT EXISTS (
+                            SELECT * FROM sitzung_attachment_mapping
+                            WHERE attachment_id = attachments.id
+                        )) OR
+                    (NOT bestaetigt AND erstellt_am < $2))
                 RETURNING *
             "#,
+            id,
+            staged_before
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(result)
+    }
+
+    async fn is_anhang_of_sitzung(&mut self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query_scalar!(
+            r#"
+                SELECT
+                    EXISTS (SELECT * FROM top_attachment_mapping WHERE attachment_id = $1) OR
+                    EXISTS (SELECT * FROM sitzung_attachment_mapping WHERE attachment_id = $1)
+                    AS "attached!"
+            "#,
             id
         )
-        .fetch_optional(&mut *self)
+        .fetch_one(&mut *self)
         .await?;
 
         Ok(result)
     }
 
-    async fn anhang_by_id(&mut self, id: uuid::Uuid) -> Result<Option<Anhang>> {
+    async fn antraege_by_anhang(&mut self, id: Uuid) -> Result<Vec<Uuid>> {
+        let result = sqlx::query_scalar!(
+            r#"
+                SELECT antrags_id FROM attachment_mapping WHERE attachment_id = $1
+            "#,
+            id
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn set_anhang_text(&mut self, id: Uuid, text: &str) -> Result<()> {
+        sqlx::query!(
+            r#"
+                INSERT INTO attachment_texts (attachment_id, inhalt)
+                VALUES ($1, $2)
+                ON CONFLICT (attachment_id) DO UPDATE SET inhalt = $2
+            "#,
+            id,
+            text
+        )
+        .execute(&mut *self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn anhaenge_without_text(&mut self, content_types: &[&str]) -> Result<Vec<Anhang>> {
+        let result = sqlx::query_as!(
+            Anhang,
+            r#"
+                SELECT * FROM attachments
+                WHERE
+                    content_type = ANY($1) AND
+                    NOT EXISTS (
+                        SELECT * FROM attachment_texts WHERE attachment_id = attachments.id
+                    )
+            "#,
+            content_types as &[&str]
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn unused_anhaenge(&mut self, staged_before: DateTime<Utc>) -> Result<Vec<Anhang>> {
+        let result = sqlx::query_as!(
+            Anhang,
+            r#"
+                SELECT * FROM attachments
+                WHERE
+                    (bestaetigt AND
+                        NOT EXISTS (
+                            SELECT * FROM attachment_mapping
+                            WHERE attachment_id = attachments.id
+                        ) AND
+                        NOT EXISTS (
+                            SELECT * FROM top_attachment_mapping
+                            WHERE attachment_id = attachments.id
+                        ) AND
+                        NOT EXISTS (
+                            SELECT * FROM sitzung_attachment_mapping
+                            WHERE attachment_id = attachments.id
+                        )) OR
+                    (NOT bestaetigt AND erstellt_am < $1)
+            "#,
+            staged_before
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn anhaenge(&mut self) -> Result<Vec<Anhang>> {
+        let result = sqlx::query_as!(
+            Anhang,
+            r#"
+                SELECT * FROM attachments
+            "#
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn anhang_by_id(&mut self, id: Uuid) -> Result<Option<Anhang>> {
         let result = sqlx::query_as!(
             Anhang,
             r#"
@@ -50,24 +224,144 @@ impl AnhangRepo for PgConnection {
 
         Ok(result)
     }
+
+    async fn anhang_by_antrag(&mut self, antrag_id: Uuid, id: Uuid) -> Result<Option<Anhang>> {
+        let result = sqlx::query_as!(
+            Anhang,
+            r#"
+                SELECT attachments.* FROM attachments
+                JOIN attachment_mapping ON attachment_mapping.attachment_id = attachments.id
+                WHERE attachment_mapping.antrags_id = $1 AND attachments.id = $2
+            "#,
+            antrag_id,
+            id
+        )
+        .fetch_optional(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn anhang_by_top(
+        &mut self,
+        sitzung_id: Uuid,
+        top_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<Anhang>> {
+        let result = sqlx::query_as!(
+            Anhang,
+            r#"
+                SELECT attachments.* FROM attachments
+                JOIN top_attachment_mapping ON top_attachment_mapping.attachment_id = attachments.id
+                JOIN tops ON tops.id = top_attachment_mapping.top_id
+                WHERE tops.sitzung_id = $1 AND tops.id = $2 AND attachments.id = $3
+            "#,
+            sitzung_id,
+            top_id,
+            id
+        )
+        .fetch_optional(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn anhang_by_sitzung(&mut self, sitzung_id: Uuid, id: Uuid) -> Result<Option<Anhang>> {
+        let result = sqlx::query_as!(
+            Anhang,
+            r#"
+                SELECT attachments.* FROM attachments
+                JOIN sitzung_attachment_mapping
+                    ON sitzung_attachment_mapping.attachment_id = attachments.id
+                WHERE sitzung_attachment_mapping.sitzung_id = $1 AND attachments.id = $2
+            "#,
+            sitzung_id,
+            id
+        )
+        .fetch_optional(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn anhaenge_by_antrag(&mut self, antrag_id: Uuid) -> Result<Vec<Anhang>> {
+        let result = sqlx::query_as!(
+            Anhang,
+            r#"
+                SELECT attachments.* FROM attachments
+                JOIN attachment_mapping ON attachment_mapping.attachment_id = attachments.id
+                WHERE attachment_mapping.antrags_id = $1
+                ORDER BY attachments.erstellt_am
+            "#,
+            antrag_id
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn anhaenge_by_top(&mut self, top_id: Uuid) -> Result<Vec<Anhang>> {
+        let result = sqlx::query_as!(
+            Anhang,
+            r#"
+                SELECT attachments.* FROM attachments
+                JOIN top_attachment_mapping ON top_attachment_mapping.attachment_id = attachments.id
+                WHERE top_attachment_mapping.top_id = $1
+                ORDER BY attachments.erstellt_am
+            "#,
+            top_id
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn anhaenge_by_sitzung(&mut self, sitzung_id: Uuid) -> Result<Vec<Anhang>> {
+        let result = sqlx::query_as!(
+            Anhang,
+            r#"
+                SELECT attachments.* FROM attachments
+                JOIN sitzung_attachment_mapping
+                    ON sitzung_attachment_mapping.attachment_id = attachments.id
+                WHERE sitzung_attachment_mapping.sitzung_id = $1
+                ORDER BY attachments.erstellt_am
+            "#,
+            sitzung_id
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(result)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use anyhow::Result;
+    use chrono::Utc;
     use sqlx::PgPool;
     use uuid::Uuid;
 
-    use crate::domain::anhang::AnhangRepo;
-    #[sqlx::test()]
+    use crate::domain::anhang::{AnhangRepo, STAGING_TIMEOUT};
+    use crate::domain::antrag::AntragRepo;
+    use crate::domain::sitzung::SitzungRepo;
+    #[sqlx::test(fixtures("gimme_persons"))]
     async fn create_attachment(pool: PgPool) -> Result<()> {
         let mut conn = pool.acquire().await?;
 
         let filename = "Tolles Excel Sheet";
+        let person_id = Uuid::parse_str("5a5a134d-9345-4c36-a466-1c3bb806b240").unwrap();
 
-        let attachment = conn.create_anhang(filename.to_string()).await?;
+        let attachment = conn
+            .create_anhang(filename, "application/pdf", 42, "abc", person_id)
+            .await?;
 
         assert_eq!(attachment.filename, filename);
+        assert_eq!(attachment.content_type, "application/pdf");
+        assert_eq!(attachment.size, Some(42));
+        assert_eq!(attachment.hochgeladen_von, Some(person_id));
 
         Ok(())
     }
@@ -77,7 +371,10 @@ mod test {
         let mut conn = pool.acquire().await?;
 
         let attachment_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
-        conn.delete_anhang(attachment_id).await?;
+        assert!(conn
+            .delete_unused_anhang(attachment_id, None)
+            .await?
+            .is_some());
 
         let please_dont_be_an_attachment = conn.anhang_by_id(attachment_id).await?;
 
@@ -85,4 +382,140 @@ mod test {
 
         Ok(())
     }
+
+    #[sqlx::test(fixtures("gimme_antraege", "gimme_attachments", "gimme_attachment_mappings"))]
+    async fn anhang_by_antrag(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let attachment_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+
+        assert!(conn
+            .anhang_by_antrag(antrag_id, attachment_id)
+            .await?
+            .is_some());
+        assert!(conn
+            .anhang_by_antrag(Uuid::new_v4(), attachment_id)
+            .await?
+            .is_none());
+
+        let mut antraege = conn.antraege_by_anhang(attachment_id).await?;
+        antraege.sort();
+        assert_eq!(
+            antraege,
+            vec![
+                antrag_id,
+                Uuid::parse_str("5c51d5c0-3943-4695-844d-4c47da854fac").unwrap()
+            ]
+        );
+        assert!(!conn.is_anhang_of_sitzung(attachment_id).await?);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        "gimme_persons",
+        "gimme_antraege",
+        "gimme_attachments",
+        "gimme_attachment_mappings"
+    ))]
+    async fn unused_anhaenge(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let attachment_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
+        let person_id = Uuid::parse_str("5a5a134d-9345-4c36-a466-1c3bb806b240").unwrap();
+        let now = Utc::now();
+
+        let staged = conn
+            .create_anhang("Entwurf.pdf", "application/pdf", 42, "abc", person_id)
+            .await?;
+
+        assert!(!staged.bestaetigt);
+        assert!(conn
+            .unused_anhaenge(now - STAGING_TIMEOUT)
+            .await?
+            .is_empty());
+
+        let abgelaufen = conn
+            .unused_anhaenge(now + STAGING_TIMEOUT)
+            .await?
+            .into_iter()
+            .map(|anhang| anhang.id)
+            .collect::<Vec<_>>();
+        assert_eq!(abgelaufen, vec![staged.id]);
+
+        // only the person who uploaded it can confirm it
+        let someone_else = Uuid::parse_str("0f3107ac-745d-4077-8bbf-f9734cd66297").unwrap();
+        assert!(conn
+            .confirm_anhang(staged.id, someone_else)
+            .await?
+            .is_none());
+
+        assert!(conn.confirm_anhang(staged.id, person_id).await?.is_some());
+        // attached Anhänge are kept
+        assert!(conn
+            .delete_unused_anhang(attachment_id, None)
+            .await?
+            .is_none());
+
+        for antrag_id in [
+            "46148231-87b0-4486-8043-c55038178518",
+            "5c51d5c0-3943-4695-844d-4c47da854fac",
+        ] {
+            conn.delete_anhang_from_antrag(Uuid::parse_str(antrag_id).unwrap(), attachment_id)
+                .await?;
+        }
+
+        let unused = conn.unused_anhaenge(now).await?;
+        assert_eq!(unused.len(), 2);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("gimme_sitzungen", "gimme_tops", "gimme_attachments"))]
+    async fn anhaenge_of_tops_and_sitzungen(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let attachment_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
+        let sitzung_id = Uuid::parse_str("dfe75b8c-8c24-4a2b-84e5-d0573a8e6f00").unwrap();
+        let other_sitzung_id = Uuid::parse_str("177b861d-0447-45ce-bc56-9eb68991cbda").unwrap();
+        let top_id = conn.tops_by_sitzung(sitzung_id).await?[0].id;
+
+        conn.add_anhang_to_top(top_id, attachment_id).await?;
+        conn.add_anhang_to_sitzung(sitzung_id, attachment_id)
+            .await?;
+
+        assert_eq!(conn.anhaenge_by_top(top_id).await?.len(), 1);
+        assert_eq!(conn.anhaenge_by_sitzung(sitzung_id).await?.len(), 1);
+        assert!(conn
+            .anhang_by_top(sitzung_id, top_id, attachment_id)
+            .await?
+            .is_some());
+        assert!(conn
+            .anhang_by_top(other_sitzung_id, top_id, attachment_id)
+            .await?
+            .is_none());
+        assert!(conn
+            .anhang_by_sitzung(other_sitzung_id, attachment_id)
+            .await?
+            .is_none());
+
+        conn.delete_anhang_from_sitzung(sitzung_id, attachment_id)
+            .await?;
+        assert!(conn
+            .delete_unused_anhang(attachment_id, None)
+            .await?
+            .is_none());
+
+        assert!(conn.is_anhang_of_sitzung(attachment_id).await?);
+
+        conn.delete_anhang_from_top(top_id, attachment_id).await?;
+        assert!(!conn.is_anhang_of_sitzung(attachment_id).await?);
+        assert!(conn
+            .delete_unused_anhang(attachment_id, None)
+            .await?
+            .is_some());
+
+        Ok(())
+    }
 }
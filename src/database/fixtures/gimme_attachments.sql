@@ -1,3 +1,3 @@
-INSERT INTO attachments (id, filename)
+INSERT INTO attachments (id, filename, bestaetigt)
     VALUES
-        ('9b5104a9-6a7d-468e-bbf2-f72a9086a3dc', 'Tolles Excel Sheet');
+        ('9b5104a9-6a7d-468e-bbf2-f72a9086a3dc', 'Tolles Excel Sheet', true);
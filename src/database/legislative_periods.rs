@@ -1,22 +1,35 @@
+use chrono::NaiveDate;
 use sqlx::PgConnection;
 use uuid::Uuid;
 
 use crate::domain::{
-    legislatur_periode::{LegislaturPeriode, LegislaturPeriodeRepo},
-    sitzung::{Sitzung, SitzungTyp},
+    legislatur_periode::{
+        LegislaturPeriode, LegislaturPeriodeFilter, LegislaturPeriodeRepo, Mitglied,
+        MitgliedsFunktion, Mitgliedschaft,
+    },
+    pagination::{Page, Pagination},
+    persons::Person,
+    sitzung::{Sitzung, SitzungStatus, SitzungTyp},
     Result,
 };
 
 impl LegislaturPeriodeRepo for PgConnection {
-    async fn create_legislatur_periode(&mut self, name: String) -> Result<LegislaturPeriode> {
+    async fn create_legislatur_periode(
+        &mut self,
+        name: String,
+        start: Option<NaiveDate>,
+        ende: Option<NaiveDate>,
+    ) -> Result<LegislaturPeriode> {
         let result = sqlx::query_as!(
             LegislaturPeriode,
             r#"
-                INSERT INTO legislatur_perioden (name)
-                VALUES ($1)
+                INSERT INTO legislatur_perioden (name, start, ende)
+                VALUES ($1, $2, $3)
                 RETURNING *
             "#,
             name,
+            start,
+            ende,
         )
         .fetch_one(self)
         .await?;
@@ -38,19 +51,65 @@ impl LegislaturPeriodeRepo for PgConnection {
         Ok(result)
     }
 
-    async fn legislatur_perioden(&mut self) -> Result<Vec<LegislaturPeriode>> {
+    async fn legislatur_periode_by_date(
+        &mut self,
+        date: NaiveDate,
+    ) -> Result<Option<LegislaturPeriode>> {
         let result = sqlx::query_as!(
             LegislaturPeriode,
             r#"
                 SELECT * FROM legislatur_perioden
+                WHERE $1 BETWEEN start AND ende
             "#,
+            date
         )
-        .fetch_all(self)
+        .fetch_optional(self)
         .await?;
 
         Ok(result)
     }
 
+    async fn legislatur_perioden(
+        &mut self,
+        filter: &LegislaturPeriodeFilter,
+        pagination: Pagination,
+    ) -> Result<Page<LegislaturPeriode>> {
+        let total = sqlx::query_scalar!(
+            r#"
+                SELECT count(*) AS "total!" FROM legislatur_perioden
+                WHERE $1::date IS NULL OR $1 BETWEEN start AND ende
+            "#,
+            filter.am,
+        )
+        .fetch_one(&mut *self)
+        .await?;
+
+        // Legislaturperioden without dates come last when sorting by start
+        let items = sqlx::query_as!(
+            LegislaturPeriode,
+            r#"
+                SELECT * FROM legislatur_perioden
+                WHERE $1::date IS NULL OR $1 BETWEEN start AND ende
+                ORDER BY
+                    CASE WHEN $2 = 'start' AND NOT $3 THEN start END ASC,
+                    CASE WHEN $2 = 'start' AND $3 THEN start END DESC NULLS LAST,
+                    CASE WHEN $2 = 'name' AND NOT $3 THEN name END ASC,
+                    CASE WHEN $2 = 'name' AND $3 THEN name END DESC,
+                    id
+                LIMIT $4 OFFSET $5
+            "#,
+            filter.am,
+            filter.sort.as_ref(),
+            filter.order.is_desc(),
+            pagination.limit,
+            pagination.offset,
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        Ok(Page { items, total })
+    }
+
     async fn sitzungen_by_legislatur_periode(&mut self, id: Uuid) -> Result<Vec<Sitzung>> {
         let records = sqlx::query!(
             r#"
@@ -60,8 +119,13 @@ impl LegislaturPeriodeRepo for PgConnection {
                     ort, 
                     typ AS "typ!: SitzungTyp", 
                     antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
                     legislatur_perioden.id AS legislative_id,
-                    legislatur_perioden.name AS legislative_name
+                    legislatur_perioden.name AS legislative_name,
+                    legislatur_perioden.start AS legislative_start,
+                    legislatur_perioden.ende AS legislative_ende
                 FROM sitzungen
                 JOIN legislatur_perioden
                 ON sitzungen.legislatur_periode_id = legislatur_perioden.id
@@ -81,31 +145,110 @@ impl LegislaturPeriodeRepo for PgConnection {
                 ort: r.ort,
                 typ: r.typ,
                 antragsfrist: r.antragsfrist,
+                serie_id: r.serie_id,
+                status: r.status,
+                status_grund: r.status_grund,
                 legislatur_periode: LegislaturPeriode {
                     id: r.legislative_id,
                     name: r.legislative_name,
+                    start: r.legislative_start,
+                    ende: r.legislative_ende,
+                },
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    async fn mitglieder_by_legislatur_periode(
+        &mut self,
+        legislatur_periode_id: Uuid,
+    ) -> Result<Vec<Mitglied>> {
+        let records = sqlx::query!(
+            r#"
+                SELECT 
+                    person.id,
+                    person.name,
+                    person.user_name,
+                    person.matrix_id,
+                    funktion AS "funktion!: MitgliedsFunktion"
+                FROM legislatur_perioden_mitglieder
+                JOIN person
+                ON legislatur_perioden_mitglieder.person_id = person.id
+                WHERE legislatur_periode_id = $1
+                ORDER BY person.name ASC
+            "#,
+            legislatur_periode_id
+        )
+        .fetch_all(self)
+        .await?;
+
+        let result = records
+            .into_iter()
+            .map(|r| Mitglied {
+                person: Person {
+                    id: r.id,
+                    name: r.name,
+                    user_name: r.user_name,
+                    matrix_id: r.matrix_id,
                 },
+                funktion: r.funktion,
             })
             .collect();
 
         Ok(result)
     }
 
+    async fn set_mitglied(
+        &mut self,
+        legislatur_periode_id: Uuid,
+        person_id: Uuid,
+        funktion: MitgliedsFunktion,
+    ) -> Result<Mitgliedschaft> {
+        let result = sqlx::query_as!(
+            Mitgliedschaft,
+            r#"
+                INSERT INTO legislatur_perioden_mitglieder (legislatur_periode_id, person_id, funktion)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (legislatur_periode_id, person_id) DO UPDATE
+                SET funktion = EXCLUDED.funktion
+                RETURNING 
+                    legislatur_periode_id, 
+                    person_id, 
+                    funktion AS "funktion!: MitgliedsFunktion"
+            "#,
+            legislatur_periode_id,
+            person_id,
+            funktion as MitgliedsFunktion
+        )
+        .fetch_one(self)
+        .await?;
+
+        Ok(result)
+    }
+
     async fn update_legislatur_periode(
         &mut self,
         id: uuid::Uuid,
         name: String,
+        start: Option<NaiveDate>,
+        ende: Option<NaiveDate>,
     ) -> Result<Option<LegislaturPeriode>> {
         let result = sqlx::query_as!(
             LegislaturPeriode,
             r#"
                 UPDATE legislatur_perioden
-                SET name = $2
+                SET 
+                    name = $2,
+                    start = COALESCE($3, start),
+                    ende = COALESCE($4, ende)
                 WHERE id = $1
                 RETURNING *
             "#,
             id,
-            name
+            name,
+            start,
+            ende
         )
         .fetch_optional(self)
         .await?;
@@ -131,14 +274,42 @@ impl LegislaturPeriodeRepo for PgConnection {
 
         Ok(result)
     }
+
+    async fn delete_mitglied(
+        &mut self,
+        legislatur_periode_id: Uuid,
+        person_id: Uuid,
+    ) -> Result<Option<Mitgliedschaft>> {
+        let result = sqlx::query_as!(
+            Mitgliedschaft,
+            r#"
+                DELETE FROM legislatur_perioden_mitglieder
+                WHERE legislatur_periode_id = $1 AND person_id = $2
+                RETURNING 
+                    legislatur_periode_id, 
+                    person_id, 
+                    funktion AS "funktion!: MitgliedsFunktion"
+            "#,
+            legislatur_periode_id,
+            person_id
+        )
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use anyhow::Result;
+    use chrono::NaiveDate;
     use sqlx::PgPool;
 
-    use crate::domain::legislatur_periode::LegislaturPeriodeRepo;
+    use crate::domain::legislatur_periode::{
+        LegislaturPeriodeFilter, LegislaturPeriodeRepo, LegislaturPeriodeSort, MitgliedsFunktion,
+    };
+    use crate::domain::pagination::{Pagination, SortOrder};
 
     #[sqlx::test]
     async fn create_legislative(pool: PgPool) -> Result<()> {
@@ -146,13 +317,83 @@ mod test {
 
         let name = "Test".to_string();
 
-        let legislative_period = conn.create_legislatur_periode(name.clone()).await?;
+        let legislative_period = conn
+            .create_legislatur_periode(name.clone(), None, None)
+            .await?;
 
         assert_eq!(legislative_period.name, name);
 
         Ok(())
     }
 
+    #[sqlx::test]
+    async fn legislatur_perioden(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+
+        conn.create_legislatur_periode("Alt".to_string(), date(2023, 4, 1), date(2024, 3, 31))
+            .await?;
+        conn.create_legislatur_periode("Neu".to_string(), date(2024, 4, 1), date(2025, 3, 31))
+            .await?;
+        conn.create_legislatur_periode("Ohne Daten".to_string(), None, None)
+            .await?;
+
+        let neueste = conn
+            .legislatur_perioden(
+                &LegislaturPeriodeFilter {
+                    order: SortOrder::Desc,
+                    ..Default::default()
+                },
+                Pagination {
+                    limit: Some(2),
+                    offset: 0,
+                },
+            )
+            .await?;
+
+        assert_eq!(neueste.total, 3);
+        assert_eq!(
+            neueste
+                .items
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Neu", "Alt"]
+        );
+
+        let nach_name = conn
+            .legislatur_perioden(
+                &LegislaturPeriodeFilter {
+                    sort: LegislaturPeriodeSort::Name,
+                    ..Default::default()
+                },
+                Pagination {
+                    limit: None,
+                    offset: 2,
+                },
+            )
+            .await?;
+
+        assert_eq!(nach_name.items.len(), 1);
+        assert_eq!(nach_name.items[0].name, "Ohne Daten");
+
+        let aktuell = conn
+            .legislatur_perioden(
+                &LegislaturPeriodeFilter {
+                    am: date(2024, 10, 1),
+                    ..Default::default()
+                },
+                Pagination::alle(),
+            )
+            .await?;
+
+        assert_eq!(aktuell.total, 1);
+        assert_eq!(aktuell.items[0].name, "Neu");
+
+        Ok(())
+    }
+
     #[sqlx::test(fixtures("gimme_legislative_period"))]
     async fn legislative_period_by_id(pool: PgPool) -> Result<()> {
         let mut conn = pool.acquire().await?;
@@ -189,7 +430,7 @@ mod test {
         let name = "Test new".to_string();
 
         let legislative_period = conn
-            .update_legislatur_periode(id, name.clone())
+            .update_legislatur_periode(id, name.clone(), None, None)
             .await?
             .unwrap();
 
@@ -210,4 +451,70 @@ mod test {
 
         Ok(())
     }
+
+    #[sqlx::test(fixtures("gimme_legislative_period"))]
+    async fn legislative_period_by_date(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let start = NaiveDate::from_ymd_opt(2024, 10, 1).unwrap();
+        let ende = NaiveDate::from_ymd_opt(2025, 9, 30).unwrap();
+
+        let period = conn
+            .create_legislatur_periode("FSR 24/25".to_string(), Some(start), Some(ende))
+            .await?;
+
+        let found = conn
+            .legislatur_periode_by_date(NaiveDate::from_ymd_opt(2025, 9, 30).unwrap())
+            .await?
+            .unwrap();
+
+        assert_eq!(found.id, period.id);
+
+        let not_found = conn
+            .legislatur_periode_by_date(NaiveDate::from_ymd_opt(2025, 10, 1).unwrap())
+            .await?;
+
+        assert!(not_found.is_none());
+
+        let overlapping = conn
+            .create_legislatur_periode(
+                "FSR 25/26".to_string(),
+                Some(ende),
+                NaiveDate::from_ymd_opt(2026, 9, 30),
+            )
+            .await;
+
+        assert!(overlapping.is_err());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("gimme_legislative_period", "gimme_persons"))]
+    async fn mitglieder(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let id = uuid::Uuid::parse_str("f2b2b2b2-2b2b-2b2b-2b2b-2b2b2b2b2b2b").unwrap();
+        let person_id = uuid::Uuid::parse_str("0f3107ac-745d-4077-8bbf-f9734cd66297").unwrap();
+
+        conn.set_mitglied(id, person_id, MitgliedsFunktion::Kooptiert)
+            .await?;
+        let mitgliedschaft = conn
+            .set_mitglied(id, person_id, MitgliedsFunktion::Gewaehlt)
+            .await?;
+
+        assert_eq!(mitgliedschaft.funktion, MitgliedsFunktion::Gewaehlt);
+
+        let mitglieder = conn.mitglieder_by_legislatur_periode(id).await?;
+
+        assert_eq!(mitglieder.len(), 1);
+        assert_eq!(mitglieder[0].person.id, person_id);
+        assert_eq!(mitglieder[0].funktion, MitgliedsFunktion::Gewaehlt);
+
+        let deleted = conn.delete_mitglied(id, person_id).await?;
+
+        assert!(deleted.is_some());
+        assert!(conn.mitglieder_by_legislatur_periode(id).await?.is_empty());
+
+        Ok(())
+    }
 }
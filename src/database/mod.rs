@@ -1,17 +1,21 @@
-use std::future::Future;
 use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
 
-use crate::domain::Result;
+use crate::domain::{Capability, Result};
 use sqlx::pool::PoolConnection;
 use sqlx::{postgres::PgPoolOptions, PgConnection, PgPool, Postgres, Transaction};
 
 pub mod antrag;
 pub mod antrag_top_attachment_map;
+pub mod api_tokens;
 pub mod attachment;
 pub mod legislative_periods;
 pub mod persons;
+pub mod search;
+pub mod sessions;
 pub mod sitzungen;
 pub mod templates;
+pub mod uploads;
 
 #[derive(Clone)]
 pub struct DatabasePool {
@@ -56,17 +60,11 @@ impl Deref for DatabaseTransaction<'_> {
     }
 }
 
-#[allow(dead_code)]
 impl DatabaseTransaction<'_> {
     pub async fn commit(self) -> Result<()> {
         self.transaction.commit().await?;
         Ok(())
     }
-
-    pub async fn rollback(self) -> Result<()> {
-        self.transaction.rollback().await?;
-        Ok(())
-    }
 }
 
 impl DatabasePool {
@@ -76,6 +74,11 @@ impl DatabasePool {
         Ok(DatabasePool { pool })
     }
 
+    #[cfg(test)]
+    pub fn from_pool(pool: PgPool) -> Self {
+        DatabasePool { pool }
+    }
+
     pub fn pool(&self) -> &PgPool {
         &self.pool
     }
@@ -91,20 +94,12 @@ impl DatabasePool {
             transaction: self.pool.begin().await?,
         })
     }
+}
 
-    #[allow(dead_code)]
-    pub async fn transaction<
-        'a,
-        T: 'static,
-        Fut: Future<Output = Result<(T, DatabaseTransaction<'a>)>>,
-        F: Fn(DatabaseTransaction<'a>) -> Fut + 'static,
-    >(
-        &self,
-        fun: F,
-    ) -> Result<T> {
-        let transaction = self.start_transaction().await?;
-        let (result, transaction) = fun(transaction).await?;
-        transaction.commit().await?;
-        Ok(result)
-    }
+/// Capabilities that are no longer known are skipped
+fn parse_capabilities(capabilities: Vec<String>) -> Vec<Capability> {
+    capabilities
+        .iter()
+        .filter_map(|capability| Capability::from_str(capability).ok())
+        .collect()
 }
@@ -3,10 +3,16 @@ use sqlx::PgConnection;
 use uuid::Uuid;
 
 use crate::domain::{
-    persons::{Abmeldung, Person, PersonRepo, PersonRoleMapping},
-    Result,
+    pagination::{Page, Pagination},
+    persons::{
+        Abmeldung, BenachrichtigungsEinstellungen, Person, PersonFilter, PersonRepo,
+        PersonRoleMapping,
+    },
+    Capability, Result,
 };
 
+use super::parse_capabilities;
+
 impl PersonRepo for PgConnection {
     async fn create_person(
         &mut self,
@@ -54,6 +60,8 @@ impl PersonRepo for PgConnection {
         person_id: Uuid,
         start: NaiveDate,
         end: NaiveDate,
+        grund: Option<&str>,
+        privat: bool,
     ) -> Result<Abmeldung> {
         let result = sqlx::query_as!(
             Abmeldung,
@@ -66,17 +74,21 @@ impl PersonRepo for PgConnection {
                         ablaufdatum >= $2
                     RETURNING *
                 )
-                INSERT INTO abmeldungen 
+                INSERT INTO abmeldungen (person_id, anfangsdatum, ablaufdatum, grund, privat)
                 SELECT 
                     $1,
                     LEAST($2::date, MIN(anfangsdatum)) AS anfangsdatum, 
-                    GREATEST($3::date, MAX(ablaufdatum)) AS ablaufdatum
+                    GREATEST($3::date, MAX(ablaufdatum)) AS ablaufdatum,
+                    NULLIF(CONCAT_WS('; ', STRING_AGG(grund, '; '), $4::text), '') AS grund,
+                    $5::boolean OR COALESCE(BOOL_OR(privat), false) AS privat
                 FROM overlap
-                RETURNING *
+                RETURNING person_id, anfangsdatum, ablaufdatum, grund, privat
             "#,
             person_id,
             start,
             end,
+            grund,
+            privat,
         )
         .fetch_one(self)
         .await?;
@@ -84,17 +96,44 @@ impl PersonRepo for PgConnection {
         Ok(result)
     }
 
-    async fn persons(&mut self) -> Result<Vec<Person>> {
-        let result = sqlx::query_as!(
+    async fn persons(
+        &mut self,
+        filter: &PersonFilter,
+        pagination: Pagination,
+    ) -> Result<Page<Person>> {
+        let total = sqlx::query_scalar!(
+            r#"
+                SELECT count(*) AS "total!" FROM person
+                WHERE $1::text IS NULL OR strpos(lower(name), lower($1)) > 0
+            "#,
+            filter.name,
+        )
+        .fetch_one(&mut *self)
+        .await?;
+
+        let items = sqlx::query_as!(
             Person,
             r#"
                 SELECT * FROM person
-            "#
+                WHERE $1::text IS NULL OR strpos(lower(name), lower($1)) > 0
+                ORDER BY
+                    CASE WHEN $2 = 'name' AND NOT $3 THEN name END ASC,
+                    CASE WHEN $2 = 'name' AND $3 THEN name END DESC,
+                    CASE WHEN $2 = 'user_name' AND NOT $3 THEN user_name END ASC,
+                    CASE WHEN $2 = 'user_name' AND $3 THEN user_name END DESC,
+                    id
+                LIMIT $4 OFFSET $5
+            "#,
+            filter.name,
+            filter.sort.as_ref(),
+            filter.order.is_desc(),
+            pagination.limit,
+            pagination.offset,
         )
-        .fetch_all(self)
+        .fetch_all(&mut *self)
         .await?;
 
-        Ok(result)
+        Ok(Page { items, total })
     }
 
     async fn roles(&mut self) -> Result<Vec<String>> {
@@ -109,6 +148,36 @@ impl PersonRepo for PgConnection {
         Ok(result)
     }
 
+    async fn capabilities_by_role(&mut self, role: &str) -> Result<Vec<Capability>> {
+        let result = sqlx::query_scalar!(
+            r#"
+                SELECT capability FROM role_capabilities
+                WHERE rolle = $1
+            "#,
+            role
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(parse_capabilities(result))
+    }
+
+    async fn capabilities_by_person(&mut self, id: Uuid) -> Result<Vec<Capability>> {
+        let result = sqlx::query_scalar!(
+            r#"
+                SELECT DISTINCT capability FROM role_capabilities
+                JOIN rolemapping
+                ON role_capabilities.rolle = rolemapping.rolle
+                WHERE rolemapping.person_id = $1
+            "#,
+            id
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(parse_capabilities(result))
+    }
+
     async fn roles_by_person(&mut self, id: Uuid) -> Result<Vec<String>> {
         let result = sqlx::query_scalar!(
             r#"
@@ -158,7 +227,10 @@ impl PersonRepo for PgConnection {
             Person,
             r#"
                 SELECT * FROM person
-                WHERE user_name = $1
+                WHERE user_name = $1 OR id IN (
+                    SELECT person_id FROM person_identities
+                    WHERE user_name = $1
+                )
             "#,
             user_name
         )
@@ -186,6 +258,20 @@ impl PersonRepo for PgConnection {
         Ok(result)
     }
 
+    async fn identities_by_person(&mut self, person_id: Uuid) -> Result<Vec<String>> {
+        let result = sqlx::query_scalar!(
+            r#"
+                SELECT user_name FROM person_identities
+                WHERE person_id = $1
+            "#,
+            person_id
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(result)
+    }
+
     async fn abmeldungen_by_person(&mut self, person_id: Uuid) -> Result<Vec<Abmeldung>> {
         let result = sqlx::query_as!(
             Abmeldung,
@@ -218,6 +304,50 @@ impl PersonRepo for PgConnection {
         Ok(result)
     }
 
+    async fn benachrichtigungen_by_person(
+        &mut self,
+        person_id: Uuid,
+    ) -> Result<BenachrichtigungsEinstellungen> {
+        let result = sqlx::query_as!(
+            BenachrichtigungsEinstellungen,
+            r#"
+                SELECT sitzungen, antraege, kommentare
+                FROM benachrichtigungs_einstellungen
+                WHERE person_id = $1
+            "#,
+            person_id
+        )
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result.unwrap_or_default())
+    }
+
+    async fn set_benachrichtigungen(
+        &mut self,
+        person_id: Uuid,
+        einstellungen: BenachrichtigungsEinstellungen,
+    ) -> Result<BenachrichtigungsEinstellungen> {
+        let result = sqlx::query_as!(
+            BenachrichtigungsEinstellungen,
+            r#"
+                INSERT INTO benachrichtigungs_einstellungen (person_id, sitzungen, antraege, kommentare)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (person_id) DO UPDATE
+                SET sitzungen = $2, antraege = $3, kommentare = $4
+                RETURNING sitzungen, antraege, kommentare
+            "#,
+            person_id,
+            einstellungen.sitzungen,
+            einstellungen.antraege,
+            einstellungen.kommentare
+        )
+        .fetch_one(self)
+        .await?;
+
+        Ok(result)
+    }
+
     async fn assign_role_to_person(&mut self, person_id: Uuid, role: &str) -> Result<()> {
         sqlx::query_as!(
             PersonRoleMapping,
@@ -254,6 +384,78 @@ impl PersonRepo for PgConnection {
         Ok(())
     }
 
+    async fn link_identity(&mut self, person_id: Uuid, user_name: &str) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+                INSERT INTO person_identities (user_name, person_id)
+                VALUES ($1, $2)
+                ON CONFLICT (user_name) DO NOTHING
+            "#,
+            user_name,
+            person_id
+        )
+        .execute(self)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn unlink_identity(
+        &mut self,
+        person_id: Uuid,
+        user_name: &str,
+    ) -> Result<Option<String>> {
+        let result = sqlx::query_scalar!(
+            r#"
+                DELETE FROM person_identities
+                WHERE person_id = $1 AND user_name = $2
+                RETURNING user_name
+            "#,
+            person_id,
+            user_name
+        )
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn grant_capability_to_role(&mut self, role: &str, capability: Capability) -> Result<()> {
+        sqlx::query!(
+            r#"
+                INSERT INTO role_capabilities (rolle, capability)
+                VALUES ($1, $2)
+                ON CONFLICT
+                DO NOTHING
+            "#,
+            role,
+            capability.to_string()
+        )
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke_capability_from_role(
+        &mut self,
+        role: &str,
+        capability: Capability,
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"
+                DELETE FROM role_capabilities
+                WHERE rolle = $1 AND capability = $2
+            "#,
+            role,
+            capability.to_string()
+        )
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
     async fn revoke_abmeldung_from_person(
         &mut self,
         person_id: Uuid,
@@ -271,10 +473,15 @@ impl PersonRepo for PgConnection {
                         ablaufdatum >= $2
                     RETURNING *
                 )
-                INSERT INTO abmeldungen (person_id, anfangsdatum, ablaufdatum)
+                INSERT INTO abmeldungen (person_id, anfangsdatum, ablaufdatum, grund, privat)
                 SELECT * FROM (VALUES
-                  ($1, (SELECT MIN(overlap.anfangsdatum) FROM overlap), $2),
-                  ($1, $3, (SELECT MAX(overlap.ablaufdatum) FROM overlap))) AS bounds (person_id, anfangsdatum, ablaufdatum)
+                  ($1, (SELECT MIN(overlap.anfangsdatum) FROM overlap), $2,
+                    (SELECT grund FROM overlap ORDER BY anfangsdatum LIMIT 1),
+                    COALESCE((SELECT privat FROM overlap ORDER BY anfangsdatum LIMIT 1), true)),
+                  ($1, $3, (SELECT MAX(overlap.ablaufdatum) FROM overlap),
+                    (SELECT grund FROM overlap ORDER BY ablaufdatum DESC LIMIT 1),
+                    COALESCE((SELECT privat FROM overlap ORDER BY ablaufdatum DESC LIMIT 1), true)))
+                  AS bounds (person_id, anfangsdatum, ablaufdatum, grund, privat)
                 WHERE
                     bounds.anfangsdatum < $2 OR
                     bounds.ablaufdatum > $3
@@ -290,7 +497,7 @@ impl PersonRepo for PgConnection {
         Ok(())
     }
 
-    async fn update_person<'a>(
+    async fn update_person(
         &mut self,
         id: Uuid,
         name: Option<&str>,
@@ -358,7 +565,11 @@ mod test {
     use sqlx::PgPool;
     use uuid::Uuid;
 
-    use crate::domain::persons::PersonRepo;
+    use crate::domain::{
+        pagination::{Pagination, SortOrder},
+        persons::{BenachrichtigungsEinstellungen, PersonFilter, PersonRepo, PersonSort},
+        Capability,
+    };
 
     #[sqlx::test]
     async fn create_person(pool: PgPool) -> Result<()> {
@@ -402,7 +613,9 @@ mod test {
         let start = NaiveDate::from_ymd_opt(2024, 2, 5).unwrap();
         let end = NaiveDate::from_ymd_opt(2024, 2, 7).unwrap();
 
-        let abmeldung = conn.create_abmeldung(person_id, start, end).await?;
+        let abmeldung = conn
+            .create_abmeldung(person_id, start, end, None, true)
+            .await?;
 
         assert_eq!(abmeldung.person_id, person_id);
         assert_eq!(abmeldung.anfangsdatum, start);
@@ -424,7 +637,9 @@ mod test {
 
         let new_end = NaiveDate::from_ymd_opt(2024, 9, 7).unwrap();
 
-        let abmeldung = conn.create_abmeldung(person_id, start, end).await?;
+        let abmeldung = conn
+            .create_abmeldung(person_id, start, end, None, true)
+            .await?;
 
         assert_eq!(abmeldung.person_id, person_id);
         assert_eq!(abmeldung.anfangsdatum, start);
@@ -446,7 +661,9 @@ mod test {
 
         let new_start = NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
 
-        let abmeldung = conn.create_abmeldung(person_id, start, end).await?;
+        let abmeldung = conn
+            .create_abmeldung(person_id, start, end, None, true)
+            .await?;
 
         assert_eq!(abmeldung.person_id, person_id);
         assert_eq!(abmeldung.anfangsdatum, new_start);
@@ -466,7 +683,9 @@ mod test {
         let start = NaiveDate::from_ymd_opt(2024, 12, 3).unwrap();
         let end = NaiveDate::from_ymd_opt(2024, 12, 24).unwrap();
 
-        let abmeldung = conn.create_abmeldung(person_id, start, end).await?;
+        let abmeldung = conn
+            .create_abmeldung(person_id, start, end, None, true)
+            .await?;
 
         assert_eq!(abmeldung.person_id, person_id);
         assert_eq!(abmeldung.anfangsdatum, start);
@@ -486,7 +705,9 @@ mod test {
         let start = NaiveDate::from_ymd_opt(2024, 12, 6).unwrap();
         let end = NaiveDate::from_ymd_opt(2024, 12, 24).unwrap();
 
-        let abmeldung = conn.create_abmeldung(person_id, start, end).await?;
+        let abmeldung = conn
+            .create_abmeldung(person_id, start, end, None, true)
+            .await?;
 
         assert_eq!(abmeldung.person_id, person_id);
         assert_eq!(abmeldung.anfangsdatum, start);
@@ -498,6 +719,49 @@ mod test {
         Ok(())
     }
 
+    #[sqlx::test(fixtures("gimme_persons", "gimme_abmeldungen"))]
+    async fn create_abmeldung_merges_grund(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let person_id = Uuid::parse_str("78be7f57-8340-43e0-bba2-074da360ddf4").unwrap();
+
+        let urlaub = conn
+            .create_abmeldung(
+                person_id,
+                NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 3, 10).unwrap(),
+                Some("Urlaub"),
+                false,
+            )
+            .await?;
+
+        assert_eq!(urlaub.grund.as_deref(), Some("Urlaub"));
+        assert!(!urlaub.privat);
+
+        let abmeldung = conn
+            .create_abmeldung(
+                person_id,
+                NaiveDate::from_ymd_opt(2025, 3, 8).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 3, 14).unwrap(),
+                Some("Krank"),
+                true,
+            )
+            .await?;
+
+        assert_eq!(
+            abmeldung.anfangsdatum,
+            NaiveDate::from_ymd_opt(2025, 3, 1).unwrap()
+        );
+        assert_eq!(
+            abmeldung.ablaufdatum,
+            NaiveDate::from_ymd_opt(2025, 3, 14).unwrap()
+        );
+        assert_eq!(abmeldung.grund.as_deref(), Some("Urlaub; Krank"));
+        assert!(abmeldung.privat);
+
+        Ok(())
+    }
+
     #[sqlx::test(fixtures("gimme_persons", "gimme_rollen"))]
     async fn roles_by_person(pool: PgPool) -> Result<()> {
         let mut conn = pool.acquire().await?;
@@ -554,6 +818,57 @@ mod test {
         Ok(())
     }
 
+    #[sqlx::test(fixtures("gimme_persons"))]
+    async fn persons(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let erste = conn
+            .persons(
+                &PersonFilter {
+                    name: Some("mutter".to_string()),
+                    ..Default::default()
+                },
+                Pagination {
+                    limit: Some(2),
+                    offset: 0,
+                },
+            )
+            .await?;
+
+        assert_eq!(erste.total, 3);
+        assert_eq!(
+            erste
+                .items
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["deine mutter", "ihre mutter"]
+        );
+
+        let mütter = conn
+            .persons(
+                &PersonFilter {
+                    name: Some("MUTTER".to_string()),
+                    sort: PersonSort::UserName,
+                    order: SortOrder::Desc,
+                },
+                Pagination::alle(),
+            )
+            .await?;
+
+        assert_eq!(mütter.total, 3);
+        assert_eq!(
+            mütter
+                .items
+                .iter()
+                .map(|p| p.user_name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["xXSkywarsDestr0yerXxHD", "xXBedwarsProXx", "steve"]
+        );
+
+        Ok(())
+    }
+
     #[sqlx::test(fixtures("gimme_persons", "gimme_rollen"))]
     async fn persons_with_role(pool: PgPool) -> Result<()> {
         let mut conn = pool.acquire().await?;
@@ -795,4 +1110,93 @@ mod test {
 
         Ok(())
     }
+
+    #[sqlx::test(fixtures("gimme_persons", "gimme_rollen"))]
+    async fn role_capabilities(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let person_id = Uuid::parse_str("51288f16-4442-4d7c-9606-3dce198b0601").unwrap();
+
+        conn.grant_capability_to_role("Rat", Capability::ManageSitzungen)
+            .await?;
+        conn.grant_capability_to_role("Rat", Capability::ManageSitzungen)
+            .await?;
+        conn.grant_capability_to_role("Kooptiert", Capability::ManageSitzungen)
+            .await?;
+        conn.grant_capability_to_role("Kooptiert", Capability::CreateAntrag)
+            .await?;
+
+        assert_eq!(
+            conn.capabilities_by_role("Rat").await?,
+            vec![Capability::ManageSitzungen]
+        );
+
+        let mut capabilities = conn.capabilities_by_person(person_id).await?;
+        capabilities.sort_by_key(ToString::to_string);
+
+        assert_eq!(
+            capabilities,
+            vec![Capability::CreateAntrag, Capability::ManageSitzungen]
+        );
+
+        conn.revoke_capability_from_role("Kooptiert", Capability::CreateAntrag)
+            .await?;
+
+        assert_eq!(
+            conn.capabilities_by_person(person_id).await?,
+            vec![Capability::ManageSitzungen]
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("gimme_persons"))]
+    async fn link_identity(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let id = Uuid::parse_str("0f3107ac-745d-4077-8bbf-f9734cd66297").unwrap();
+        let user_name = "github-1234";
+
+        assert!(conn.person_by_user_name(user_name).await?.is_none());
+
+        assert!(conn.link_identity(id, user_name).await?);
+
+        // an identity belongs to one person only
+        let someone_else = Uuid::parse_str("5a5a134d-9345-4c36-a466-1c3bb806b240").unwrap();
+        assert!(!conn.link_identity(someone_else, user_name).await?);
+
+        let person = conn.person_by_user_name(user_name).await?.unwrap();
+
+        assert_eq!(person.id, id);
+        assert_eq!(conn.identities_by_person(id).await?, vec![user_name]);
+
+        conn.unlink_identity(id, user_name).await?;
+
+        assert!(conn.person_by_user_name(user_name).await?.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("gimme_persons"))]
+    async fn benachrichtigungen(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let id = Uuid::parse_str("0f3107ac-745d-4077-8bbf-f9734cd66297").unwrap();
+
+        assert_eq!(
+            conn.benachrichtigungen_by_person(id).await?,
+            BenachrichtigungsEinstellungen::default()
+        );
+
+        let einstellungen = BenachrichtigungsEinstellungen {
+            kommentare: false,
+            ..Default::default()
+        };
+
+        conn.set_benachrichtigungen(id, einstellungen).await?;
+
+        assert_eq!(conn.benachrichtigungen_by_person(id).await?, einstellungen);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,190 @@
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::domain::antrag::AntragBetrachter;
+use crate::domain::search::{Fundstelle, SearchRepo, TopTreffer};
+use crate::domain::Result;
+
+/// Options for `ts_headline`, which marks the matches like `**this**`
+const AUSZUG_OPTIONEN: &str = "StartSel=**, StopSel=**, MaxWords=30, MinWords=10, MaxFragments=2";
+
+impl SearchRepo for PgConnection {
+    async fn search_antraege(
+        &mut self,
+        betrachter: &AntragBetrachter,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<Fundstelle>> {
+        let person_id = betrachter.person.as_ref().map(|person| person.id);
+
+        let result = sqlx::query_as!(
+            Fundstelle,
+            r#"
+                SELECT
+                    antraege.id,
+                    ts_rank(
+                        antrag_suchvektor(titel, antragstext, begruendung),
+                        query
+                    ) AS "rang!",
+                    ts_headline(
+                        'german',
+                        titel || E'\n' || antragstext || E'\n' || begruendung,
+                        query,
+                        $3
+                    ) AS "auszug!"
+                FROM antraege, websearch_to_tsquery('german', $1) query
+                WHERE
+                    antrag_suchvektor(titel, antragstext, begruendung) @@ query AND
+                    antrag_sichtbar(id, sichtbarkeit, $4, $5, $6)
+                ORDER BY "rang!" DESC, erstellt_am DESC
+                LIMIT $2
+            "#,
+            query,
+            limit,
+            AUSZUG_OPTIONEN,
+            betrachter.alle,
+            betrachter.intern,
+            person_id as Option<Uuid>,
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn search_tops(&mut self, query: &str, limit: i64) -> Result<Vec<TopTreffer>> {
+        let result = sqlx::query_as!(
+            TopTreffer,
+            r#"
+                SELECT
+                    tops.id,
+                    tops.sitzung_id,
+                    tops.name,
+                    ts_rank(top_suchvektor(name, inhalt), query) AS "rang!",
+                    ts_headline('german', name || E'\n' || inhalt, query, $3) AS "auszug!"
+                FROM tops
+                    JOIN sitzungen ON sitzungen.id = tops.sitzung_id,
+                    websearch_to_tsquery('german', $1) query
+                WHERE top_suchvektor(name, inhalt) @@ query
+                ORDER BY "rang!" DESC, sitzungen.datetime DESC
+                LIMIT $2
+            "#,
+            query,
+            limit,
+            AUSZUG_OPTIONEN
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn search_anhaenge(
+        &mut self,
+        betrachter: &AntragBetrachter,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<Fundstelle>> {
+        let person_id = betrachter.person.as_ref().map(|person| person.id);
+
+        let result = sqlx::query_as!(
+            Fundstelle,
+            r#"
+                SELECT
+                    attachments.id,
+                    ts_rank(vektor.suchvektor, query) AS "rang!",
+                    ts_headline(
+                        'german',
+                        filename || E'\n' || coalesce(attachment_texts.inhalt, ''),
+                        query,
+                        $3
+                    ) AS "auszug!"
+                FROM attachments
+                    LEFT JOIN attachment_texts ON attachment_texts.attachment_id = attachments.id,
+                    websearch_to_tsquery('german', $1) query,
+                    LATERAL (
+                        SELECT
+                            setweight(to_tsvector('german', filename), 'A') ||
+                            coalesce(attachment_texts.suchvektor, ''::tsvector) AS suchvektor
+                    ) vektor
+                WHERE
+                    bestaetigt AND
+                    vektor.suchvektor @@ query AND
+                    anhang_sichtbar(attachments.id, $4, $5, $6)
+                ORDER BY "rang!" DESC, erstellt_am DESC
+                LIMIT $2
+            "#,
+            query,
+            limit,
+            AUSZUG_OPTIONEN,
+            betrachter.alle,
+            betrachter.intern,
+            person_id as Option<Uuid>,
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::domain::anhang::AnhangRepo;
+    use crate::domain::antrag::AntragBetrachter;
+    use crate::domain::search::SearchRepo;
+
+    #[sqlx::test(fixtures(
+        "gimme_sitzungen",
+        "gimme_tops",
+        "gimme_antraege",
+        "gimme_attachments",
+        "gimme_attachment_mappings"
+    ))]
+    async fn search(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let alle = AntragBetrachter {
+            alle: true,
+            ..Default::default()
+        };
+
+        let antraege = conn.search_antraege(&alle, "volt", 10).await?;
+        assert_eq!(antraege.len(), 1);
+        assert_eq!(
+            antraege[0].id,
+            Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap()
+        );
+        assert!(antraege[0].auszug.contains("**Volt**"));
+
+        // "toller" and "toll" share their stem
+        let tops = conn.search_tops("toll", 10).await?;
+        assert_eq!(tops.len(), 2);
+
+        assert!(conn
+            .search_antraege(&alle, "volt -gut", 10)
+            .await?
+            .is_empty());
+
+        let attachment_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
+
+        assert_eq!(conn.search_anhaenge(&alle, "excel", 10).await?.len(), 1);
+        assert!(conn
+            .search_anhaenge(&alle, "haushalt", 10)
+            .await?
+            .is_empty());
+
+        conn.set_anhang_text(attachment_id, "Der Haushaltsplan für 2024")
+            .await?;
+
+        let anhaenge = conn.search_anhaenge(&alle, "haushaltsplan", 10).await?;
+        assert_eq!(anhaenge.len(), 1);
+        assert_eq!(anhaenge[0].id, attachment_id);
+
+        Ok(())
+    }
+}
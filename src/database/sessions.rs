@@ -0,0 +1,259 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::domain::{
+    session::{Session, SessionRepo},
+    Result,
+};
+
+impl SessionRepo for PgConnection {
+    async fn create_session(
+        &mut self,
+        person_id: Uuid,
+        oauth_provider: Option<&str>,
+        user_info: &serde_json::Value,
+        refresh_token: Option<&str>,
+        ablaufdatum: DateTime<Utc>,
+    ) -> Result<Session> {
+        let result = sqlx::query_as!(
+            Session,
+            r#"
+                INSERT INTO sessions (person_id, oauth_provider, user_info, refresh_token, ablaufdatum)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING
+                    id, person_id, oauth_provider, user_info, refresh_token, erstellt_am,
+                    zuletzt_aktiv, ablaufdatum
+            "#,
+            person_id,
+            oauth_provider,
+            user_info,
+            refresh_token,
+            ablaufdatum
+        )
+        .fetch_one(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn session_by_id(&mut self, id: Uuid) -> Result<Option<Session>> {
+        let result = sqlx::query_as!(
+            Session,
+            r#"
+                SELECT
+                    id, person_id, oauth_provider, user_info, refresh_token, erstellt_am,
+                    zuletzt_aktiv, ablaufdatum
+                FROM sessions
+                WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn sessions_by_person(&mut self, person_id: Uuid) -> Result<Vec<Session>> {
+        let result = sqlx::query_as!(
+            Session,
+            r#"
+                SELECT
+                    id, person_id, oauth_provider, user_info, refresh_token, erstellt_am,
+                    zuletzt_aktiv, ablaufdatum
+                FROM sessions
+                WHERE person_id = $1
+                ORDER BY zuletzt_aktiv DESC
+            "#,
+            person_id
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn touch_session(&mut self, id: Uuid, now: DateTime<Utc>) -> Result<()> {
+        sqlx::query!(
+            r#"
+                UPDATE sessions
+                SET zuletzt_aktiv = $2
+                WHERE id = $1
+            "#,
+            id,
+            now
+        )
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn update_session_tokens(
+        &mut self,
+        id: Uuid,
+        user_info: &serde_json::Value,
+        refresh_token: Option<&str>,
+    ) -> Result<Option<Session>> {
+        let result = sqlx::query_as!(
+            Session,
+            r#"
+                UPDATE sessions
+                SET 
+                    user_info = $2,
+                    refresh_token = COALESCE($3, refresh_token)
+                WHERE id = $1
+                RETURNING
+                    id, person_id, oauth_provider, user_info, refresh_token, erstellt_am,
+                    zuletzt_aktiv, ablaufdatum
+            "#,
+            id,
+            user_info,
+            refresh_token
+        )
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_session(&mut self, id: Uuid) -> Result<Option<Session>> {
+        let result = sqlx::query_as!(
+            Session,
+            r#"
+                DELETE FROM sessions
+                WHERE id = $1
+                RETURNING
+                    id, person_id, oauth_provider, user_info, refresh_token, erstellt_am,
+                    zuletzt_aktiv, ablaufdatum
+            "#,
+            id
+        )
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_sessions_by_person(&mut self, person_id: Uuid) -> Result<Vec<Session>> {
+        let result = sqlx::query_as!(
+            Session,
+            r#"
+                DELETE FROM sessions
+                WHERE person_id = $1
+                RETURNING
+                    id, person_id, oauth_provider, user_info, refresh_token, erstellt_am,
+                    zuletzt_aktiv, ablaufdatum
+            "#,
+            person_id
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_inactive_sessions(
+        &mut self,
+        now: DateTime<Utc>,
+        idle_timeout: TimeDelta,
+    ) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+                DELETE FROM sessions
+                WHERE ablaufdatum <= $1 OR zuletzt_aktiv <= $2
+            "#,
+            now,
+            now - idle_timeout
+        )
+        .execute(self)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use chrono::{DateTime, TimeDelta, Utc};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::domain::session::{self, SessionRepo};
+
+    #[sqlx::test(fixtures("gimme_persons"))]
+    async fn session_timeouts(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let person_id = Uuid::parse_str("0f3107ac-745d-4077-8bbf-f9734cd66297").unwrap();
+        let now: DateTime<Utc> = DateTime::parse_from_rfc3339("2024-09-01T12:00:00+02:00")
+            .unwrap()
+            .into();
+        let idle_timeout = TimeDelta::hours(1);
+
+        let created = conn
+            .create_session(
+                person_id,
+                Some("authentik"),
+                &json!({ "sub": "1234" }),
+                Some("refresh"),
+                now + TimeDelta::days(30),
+            )
+            .await?;
+
+        let active = session::active_session(&mut *conn, created.id, now, idle_timeout).await?;
+
+        assert!(active.is_some());
+
+        let later = now + TimeDelta::minutes(30);
+
+        session::active_session(&mut *conn, created.id, later, idle_timeout)
+            .await?
+            .unwrap();
+
+        let updated = conn
+            .update_session_tokens(created.id, &json!({ "sub": "5678" }), None)
+            .await?
+            .unwrap();
+
+        assert_eq!(updated.user_info, json!({ "sub": "5678" }));
+        assert_eq!(updated.refresh_token.as_deref(), Some("refresh"));
+        assert_eq!(updated.zuletzt_aktiv, later);
+
+        let idle = later + TimeDelta::hours(2);
+
+        assert!(
+            session::active_session(&mut *conn, created.id, idle, idle_timeout)
+                .await?
+                .is_none()
+        );
+        assert!(conn.session_by_id(created.id).await?.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("gimme_persons"))]
+    async fn delete_sessions_by_person(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let person_id = Uuid::parse_str("0f3107ac-745d-4077-8bbf-f9734cd66297").unwrap();
+        let expires = Utc::now() + TimeDelta::days(30);
+
+        for _ in 0..2 {
+            conn.create_session(person_id, None, &json!({}), None, expires)
+                .await?;
+        }
+
+        assert_eq!(conn.sessions_by_person(person_id).await?.len(), 2);
+
+        let deleted = conn.delete_sessions_by_person(person_id).await?;
+
+        assert_eq!(deleted.len(), 2);
+        assert!(conn.sessions_by_person(person_id).await?.is_empty());
+
+        Ok(())
+    }
+}
@@ -4,7 +4,11 @@ use uuid::Uuid;
 
 use crate::domain::{
     legislatur_periode::LegislaturPeriode,
-    sitzung::{Sitzung, SitzungRepo, SitzungTyp, Top, TopTyp},
+    pagination::{Page, Pagination},
+    sitzung::{
+        Sitzung, SitzungFilter, SitzungRepo, SitzungSerie, SitzungStatus, SitzungTyp, Top, TopTyp,
+        Verschiebung, Vertagung,
+    },
     Result,
 };
 
@@ -16,12 +20,13 @@ impl SitzungRepo for PgConnection {
         typ: SitzungTyp,
         antragsfrist: DateTime<Utc>,
         legislatur_periode_id: Uuid,
+        serie_id: Option<Uuid>,
     ) -> Result<Sitzung> {
         let record = sqlx::query!(
             r#"
                 WITH inserted as (
-                    INSERT INTO sitzungen (datetime, ort, typ, antragsfrist, legislatur_periode_id)
-                    VALUES ($1, $2, $3, $4, $5) 
+                    INSERT INTO sitzungen (datetime, ort, typ, antragsfrist, legislatur_periode_id, serie_id)
+                    VALUES ($1, $2, $3, $4, $5, $6) 
                     RETURNING *
                 ) SELECT 
                     inserted.id, 
@@ -29,8 +34,13 @@ impl SitzungRepo for PgConnection {
                     ort, 
                     typ AS "typ!: SitzungTyp", 
                     antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
                     legislatur_perioden.id as legislative_id,
-                    legislatur_perioden.name as legislative_name
+                    legislatur_perioden.name as legislative_name,
+                    legislatur_perioden.start as legislative_start,
+                    legislatur_perioden.ende as legislative_ende
                 FROM inserted 
                 JOIN legislatur_perioden
                 on inserted.legislatur_periode_id = legislatur_perioden.id
@@ -39,7 +49,8 @@ impl SitzungRepo for PgConnection {
             ort,
             typ as SitzungTyp,
             antragsfrist,
-            legislatur_periode_id
+            legislatur_periode_id,
+            serie_id
         )
         .fetch_one(self)
         .await?;
@@ -50,9 +61,14 @@ impl SitzungRepo for PgConnection {
             ort: record.ort,
             typ: record.typ,
             antragsfrist: record.antragsfrist,
+            serie_id: record.serie_id,
+            status: record.status,
+            status_grund: record.status_grund,
             legislatur_periode: LegislaturPeriode {
                 id: record.legislative_id,
                 name: record.legislative_name,
+                start: record.legislative_start,
+                ende: record.legislative_ende,
             },
         };
 
@@ -98,7 +114,94 @@ impl SitzungRepo for PgConnection {
         Ok(result)
     }
 
-    async fn sitzungen(&mut self) -> Result<Vec<Sitzung>> {
+    async fn create_vertagung(
+        &mut self,
+        top_id: Uuid,
+        von_sitzung_id: Uuid,
+        nach_sitzung_id: Uuid,
+    ) -> Result<Vertagung> {
+        let result = sqlx::query_as!(
+            Vertagung,
+            r#"
+                INSERT INTO vertagungen (top_id, von_sitzung_id, nach_sitzung_id)
+                VALUES ($1, $2, $3)
+                RETURNING *
+            "#,
+            top_id,
+            von_sitzung_id,
+            nach_sitzung_id,
+        )
+        .fetch_one(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn create_sitzung_serie(&mut self, name: &str) -> Result<SitzungSerie> {
+        let result = sqlx::query_as!(
+            SitzungSerie,
+            r#"
+                INSERT INTO sitzung_serien (name)
+                VALUES ($1)
+                RETURNING *
+            "#,
+            name
+        )
+        .fetch_one(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn create_verschiebung(
+        &mut self,
+        sitzung_id: Uuid,
+        alte_datetime: DateTime<Utc>,
+        neue_datetime: DateTime<Utc>,
+        grund: Option<&str>,
+    ) -> Result<Verschiebung> {
+        let result = sqlx::query_as!(
+            Verschiebung,
+            r#"
+                INSERT INTO verschiebungen (sitzung_id, alte_datetime, neue_datetime, grund)
+                VALUES ($1, $2, $3, $4)
+                RETURNING *
+            "#,
+            sitzung_id,
+            alte_datetime,
+            neue_datetime,
+            grund
+        )
+        .fetch_one(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn sitzungen(
+        &mut self,
+        filter: &SitzungFilter,
+        pagination: Pagination,
+    ) -> Result<Page<Sitzung>> {
+        let total = sqlx::query_scalar!(
+            r#"
+                SELECT count(*) AS "total!" FROM sitzungen
+                WHERE
+                    ($1::sitzungkind IS NULL OR typ = $1) AND
+                    ($2::sitzungstatus IS NULL OR status = $2) AND
+                    ($3::uuid IS NULL OR legislatur_periode_id = $3) AND
+                    ($4::timestamptz IS NULL OR datetime >= $4) AND
+                    ($5::timestamptz IS NULL OR datetime <= $5)
+            "#,
+            filter.typ as Option<SitzungTyp>,
+            filter.status as Option<SitzungStatus>,
+            filter.legislatur_periode_id,
+            filter.ab,
+            filter.bis,
+        )
+        .fetch_one(&mut *self)
+        .await?;
+
         let records = sqlx::query!(
             r#"
                 SELECT 
@@ -107,17 +210,44 @@ impl SitzungRepo for PgConnection {
                     ort, 
                     typ AS "typ!: SitzungTyp", 
                     antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
                     legislatur_perioden.id AS legislative_id, 
-                    legislatur_perioden.name AS legislative_name
+                    legislatur_perioden.name AS legislative_name,
+                    legislatur_perioden.start AS legislative_start,
+                    legislatur_perioden.ende AS legislative_ende
                 FROM sitzungen
                 JOIN legislatur_perioden 
                 ON sitzungen.legislatur_periode_id = legislatur_perioden.id
-            "#
+                WHERE
+                    ($1::sitzungkind IS NULL OR typ = $1) AND
+                    ($2::sitzungstatus IS NULL OR status = $2) AND
+                    ($3::uuid IS NULL OR legislatur_periode_id = $3) AND
+                    ($4::timestamptz IS NULL OR datetime >= $4) AND
+                    ($5::timestamptz IS NULL OR datetime <= $5)
+                ORDER BY
+                    CASE WHEN $6 = 'datetime' AND NOT $7 THEN datetime END ASC,
+                    CASE WHEN $6 = 'datetime' AND $7 THEN datetime END DESC,
+                    CASE WHEN $6 = 'antragsfrist' AND NOT $7 THEN antragsfrist END ASC,
+                    CASE WHEN $6 = 'antragsfrist' AND $7 THEN antragsfrist END DESC,
+                    sitzungen.id
+                LIMIT $8 OFFSET $9
+            "#,
+            filter.typ as Option<SitzungTyp>,
+            filter.status as Option<SitzungStatus>,
+            filter.legislatur_periode_id,
+            filter.ab,
+            filter.bis,
+            filter.sort.as_ref(),
+            filter.order.is_desc(),
+            pagination.limit,
+            pagination.offset,
         )
-        .fetch_all(self)
+        .fetch_all(&mut *self)
         .await?;
 
-        let result = records
+        let items = records
             .into_iter()
             .map(|r| Sitzung {
                 id: r.id,
@@ -125,14 +255,19 @@ impl SitzungRepo for PgConnection {
                 ort: r.ort,
                 typ: r.typ,
                 antragsfrist: r.antragsfrist,
+                serie_id: r.serie_id,
+                status: r.status,
+                status_grund: r.status_grund,
                 legislatur_periode: LegislaturPeriode {
                     id: r.legislative_id,
                     name: r.legislative_name,
+                    start: r.legislative_start,
+                    ende: r.legislative_ende,
                 },
             })
             .collect();
 
-        Ok(result)
+        Ok(Page { items, total })
     }
 
     async fn sitzung_by_id(&mut self, id: Uuid) -> Result<Option<Sitzung>> {
@@ -144,8 +279,13 @@ impl SitzungRepo for PgConnection {
                     ort, 
                     typ AS "typ!: SitzungTyp", 
                     antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
                     legislatur_perioden.id AS legislative_id,
-                    legislatur_perioden.name AS legislative_name
+                    legislatur_perioden.name AS legislative_name,
+                    legislatur_perioden.start AS legislative_start,
+                    legislatur_perioden.ende AS legislative_ende
                 FROM sitzungen
                 JOIN legislatur_perioden
                 ON sitzungen.legislatur_periode_id = legislatur_perioden.id
@@ -162,9 +302,14 @@ impl SitzungRepo for PgConnection {
             ort: r.ort,
             typ: r.typ,
             antragsfrist: r.antragsfrist,
+            serie_id: r.serie_id,
+            status: r.status,
+            status_grund: r.status_grund,
             legislatur_periode: LegislaturPeriode {
                 id: r.legislative_id,
                 name: r.legislative_name,
+                start: r.legislative_start,
+                ende: r.legislative_ende,
             },
         });
 
@@ -184,8 +329,13 @@ impl SitzungRepo for PgConnection {
                     ort, 
                     typ AS "typ!: SitzungTyp", 
                     antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
                     legislatur_perioden.id AS legislative_id,
-                    legislatur_perioden.name AS legislative_name
+                    legislatur_perioden.name AS legislative_name,
+                    legislatur_perioden.start AS legislative_start,
+                    legislatur_perioden.ende AS legislative_ende
                 FROM sitzungen
                 JOIN legislatur_perioden 
                 ON sitzungen.legislatur_periode_id = legislatur_perioden.id
@@ -207,9 +357,14 @@ impl SitzungRepo for PgConnection {
                 ort: r.ort,
                 typ: r.typ,
                 antragsfrist: r.antragsfrist,
+                serie_id: r.serie_id,
+                status: r.status,
+                status_grund: r.status_grund,
                 legislatur_periode: LegislaturPeriode {
                     id: r.legislative_id,
                     name: r.legislative_name,
+                    start: r.legislative_start,
+                    ende: r.legislative_ende,
                 },
             })
             .collect();
@@ -217,6 +372,52 @@ impl SitzungRepo for PgConnection {
         Ok(result)
     }
 
+    async fn next_scheduled_sitzung(&mut self, after: DateTime<Utc>) -> Result<Option<Sitzung>> {
+        let record = sqlx::query!(
+            r#"
+                SELECT 
+                    sitzungen.id, 
+                    datetime, 
+                    ort, 
+                    typ AS "typ!: SitzungTyp", 
+                    antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
+                    legislatur_perioden.id AS legislative_id,
+                    legislatur_perioden.name AS legislative_name,
+                    legislatur_perioden.start AS legislative_start,
+                    legislatur_perioden.ende AS legislative_ende
+                FROM sitzungen
+                JOIN legislatur_perioden 
+                ON sitzungen.legislatur_periode_id = legislatur_perioden.id
+                WHERE datetime >= $1 AND status <> 'abgesagt'
+                ORDER BY datetime ASC
+                LIMIT 1
+            "#,
+            after,
+        )
+        .fetch_optional(self)
+        .await?;
+
+        Ok(record.map(|r| Sitzung {
+            id: r.id,
+            datetime: r.datetime,
+            ort: r.ort,
+            typ: r.typ,
+            antragsfrist: r.antragsfrist,
+            serie_id: r.serie_id,
+            status: r.status,
+            status_grund: r.status_grund,
+            legislatur_periode: LegislaturPeriode {
+                id: r.legislative_id,
+                name: r.legislative_name,
+                start: r.legislative_start,
+                ende: r.legislative_ende,
+            },
+        }))
+    }
+
     async fn sitzungen_between(
         &mut self,
         start: DateTime<Utc>,
@@ -230,8 +431,13 @@ impl SitzungRepo for PgConnection {
                     ort, 
                     typ AS "typ!: SitzungTyp", 
                     antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
                     legislatur_perioden.id AS legislative_id,
-                    legislatur_perioden.name AS legislative_name
+                    legislatur_perioden.name AS legislative_name,
+                    legislatur_perioden.start AS legislative_start,
+                    legislatur_perioden.ende AS legislative_ende
                 FROM sitzungen
                 JOIN legislatur_perioden
                 ON sitzungen.legislatur_periode_id = legislatur_perioden.id
@@ -252,9 +458,14 @@ impl SitzungRepo for PgConnection {
                 ort: r.ort,
                 typ: r.typ,
                 antragsfrist: r.antragsfrist,
+                serie_id: r.serie_id,
+                status: r.status,
+                status_grund: r.status_grund,
                 legislatur_periode: LegislaturPeriode {
                     id: r.legislative_id,
                     name: r.legislative_name,
+                    start: r.legislative_start,
+                    ende: r.legislative_ende,
                 },
             })
             .collect();
@@ -262,6 +473,101 @@ impl SitzungRepo for PgConnection {
         Ok(result)
     }
 
+    async fn sitzung_serien(&mut self) -> Result<Vec<SitzungSerie>> {
+        let result = sqlx::query_as!(
+            SitzungSerie,
+            r#"
+                SELECT * FROM sitzung_serien
+            "#
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn sitzung_serie_by_id(&mut self, id: Uuid) -> Result<Option<SitzungSerie>> {
+        let result = sqlx::query_as!(
+            SitzungSerie,
+            r#"
+                SELECT * FROM sitzung_serien
+                WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn sitzungen_by_serie(&mut self, serie_id: Uuid) -> Result<Vec<Sitzung>> {
+        let records = sqlx::query!(
+            r#"
+                SELECT 
+                    sitzungen.id, 
+                    datetime, 
+                    ort, 
+                    typ AS "typ!: SitzungTyp", 
+                    antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
+                    legislatur_perioden.id AS legislative_id,
+                    legislatur_perioden.name AS legislative_name,
+                    legislatur_perioden.start AS legislative_start,
+                    legislatur_perioden.ende AS legislative_ende
+                FROM sitzungen
+                JOIN legislatur_perioden
+                ON sitzungen.legislatur_periode_id = legislatur_perioden.id
+                WHERE serie_id = $1
+                ORDER BY datetime ASC
+            "#,
+            serie_id
+        )
+        .fetch_all(self)
+        .await?;
+
+        let result = records
+            .into_iter()
+            .map(|r| Sitzung {
+                id: r.id,
+                datetime: r.datetime,
+                ort: r.ort,
+                typ: r.typ,
+                antragsfrist: r.antragsfrist,
+                serie_id: r.serie_id,
+                status: r.status,
+                status_grund: r.status_grund,
+                legislatur_periode: LegislaturPeriode {
+                    id: r.legislative_id,
+                    name: r.legislative_name,
+                    start: r.legislative_start,
+                    ende: r.legislative_ende,
+                },
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    async fn verschiebungen_by_sitzung(&mut self, sitzung_id: Uuid) -> Result<Vec<Verschiebung>> {
+        let result = sqlx::query_as!(
+            Verschiebung,
+            r#"
+                SELECT *
+                FROM verschiebungen
+                WHERE sitzung_id = $1
+                ORDER BY verschoben_am ASC
+            "#,
+            sitzung_id
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(result)
+    }
+
     async fn top_by_id(&mut self, id: Uuid) -> Result<Option<Top>> {
         let result = sqlx::query_as!(
             Top,
@@ -295,11 +601,33 @@ impl SitzungRepo for PgConnection {
         Ok(result)
     }
 
-    async fn update_sitzung<'a>(
+    async fn vertagungen_by_top(
+        &mut self,
+        top_id: Uuid,
+        sitzung_id: Uuid,
+    ) -> Result<Vec<Vertagung>> {
+        let result = sqlx::query_as!(
+            Vertagung,
+            r#"
+                SELECT *
+                FROM vertagungen
+                WHERE top_id = $1 AND (von_sitzung_id = $2 OR nach_sitzung_id = $2)
+                ORDER BY vertagt_am ASC
+            "#,
+            top_id,
+            sitzung_id
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn update_sitzung(
         &mut self,
         id: Uuid,
         datetime: Option<DateTime<Utc>>,
-        ort: Option<&'a str>,
+        ort: Option<&str>,
         typ: Option<SitzungTyp>,
         antragsfrist: Option<DateTime<Utc>>,
         legislative_period_id: Option<Uuid>,
@@ -315,15 +643,20 @@ impl SitzungRepo for PgConnection {
                         antragsfrist = COALESCE($4, antragsfrist),
                         legislatur_periode_id = COALESCE($5, legislatur_periode_id)
                     WHERE id = $6 
-                    RETURNING id, datetime, ort, typ, antragsfrist, legislatur_periode_id
+                    RETURNING id, datetime, ort, typ, antragsfrist, legislatur_periode_id, serie_id, status, status_grund
                 ) SELECT 
                     updated.id, 
                     datetime, 
                     ort, 
                     typ AS "typ!: SitzungTyp", 
                     antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
                     legislatur_perioden.id as legislative_id, 
-                    legislatur_perioden.name as legislative_name
+                    legislatur_perioden.name as legislative_name,
+                    legislatur_perioden.start as legislative_start,
+                    legislatur_perioden.ende as legislative_ende
                 FROM updated 
                 JOIN legislatur_perioden
                 on updated.legislatur_periode_id = legislatur_perioden.id
@@ -344,9 +677,73 @@ impl SitzungRepo for PgConnection {
             ort: r.ort,
             typ: r.typ,
             antragsfrist: r.antragsfrist,
+            serie_id: r.serie_id,
+            status: r.status,
+            status_grund: r.status_grund,
+            legislatur_periode: LegislaturPeriode {
+                id: r.legislative_id,
+                name: r.legislative_name,
+                start: r.legislative_start,
+                ende: r.legislative_ende,
+            },
+        });
+
+        Ok(result)
+    }
+
+    async fn set_sitzung_status(
+        &mut self,
+        id: Uuid,
+        status: SitzungStatus,
+        grund: Option<&str>,
+    ) -> Result<Option<Sitzung>> {
+        let record = sqlx::query!(
+            r#"
+                WITH updated AS (
+                    UPDATE sitzungen 
+                    SET 
+                        status = $1,
+                        status_grund = $2
+                    WHERE id = $3 
+                    RETURNING id, datetime, ort, typ, antragsfrist, legislatur_periode_id, serie_id, status, status_grund
+                ) SELECT 
+                    updated.id, 
+                    datetime, 
+                    ort, 
+                    typ AS "typ!: SitzungTyp", 
+                    antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
+                    legislatur_perioden.id as legislative_id, 
+                    legislatur_perioden.name as legislative_name,
+                    legislatur_perioden.start as legislative_start,
+                    legislatur_perioden.ende as legislative_ende
+                FROM updated 
+                JOIN legislatur_perioden
+                on updated.legislatur_periode_id = legislatur_perioden.id
+            "#,
+            status as SitzungStatus,
+            grund,
+            id
+        )
+        .fetch_optional(self)
+        .await?;
+
+        let result = record.map(|r| Sitzung {
+            id: r.id,
+            datetime: r.datetime,
+            ort: r.ort,
+            typ: r.typ,
+            antragsfrist: r.antragsfrist,
+            serie_id: r.serie_id,
+            status: r.status,
+            status_grund: r.status_grund,
             legislatur_periode: LegislaturPeriode {
                 id: r.legislative_id,
                 name: r.legislative_name,
+                start: r.legislative_start,
+                ende: r.legislative_ende,
             },
         });
 
@@ -394,15 +791,20 @@ impl SitzungRepo for PgConnection {
                 WITH deleted AS (
                     DELETE FROM sitzungen
                     WHERE id = $1
-                    RETURNING id, datetime, ort, typ, antragsfrist, legislatur_periode_id
+                    RETURNING id, datetime, ort, typ, antragsfrist, legislatur_periode_id, serie_id, status, status_grund
                 ) SELECT 
                     deleted.id, 
                     datetime, 
                     ort, 
                     typ AS "typ!: SitzungTyp", 
                     antragsfrist, 
+                    serie_id,
+                    status AS "status!: SitzungStatus",
+                    status_grund,
                     legislatur_perioden.id as legislative_id, 
-                    legislatur_perioden.name as legislative_name
+                    legislatur_perioden.name as legislative_name,
+                    legislatur_perioden.start as legislative_start,
+                    legislatur_perioden.ende as legislative_ende
                 FROM deleted 
                 JOIN legislatur_perioden 
                 on deleted.legislatur_periode_id = legislatur_perioden.id
@@ -418,9 +820,14 @@ impl SitzungRepo for PgConnection {
             ort: r.ort,
             typ: r.typ,
             antragsfrist: r.antragsfrist,
+            serie_id: r.serie_id,
+            status: r.status,
+            status_grund: r.status_grund,
             legislatur_periode: LegislaturPeriode {
                 id: r.legislative_id,
                 name: r.legislative_name,
+                start: r.legislative_start,
+                ende: r.legislative_ende,
             },
         });
 
@@ -442,6 +849,72 @@ impl SitzungRepo for PgConnection {
 
         Ok(result)
     }
+
+    async fn add_anhang_to_top(&mut self, top_id: Uuid, anhang_id: Uuid) -> Result<()> {
+        sqlx::query!(
+            r#"
+                INSERT INTO top_attachment_mapping (top_id, attachment_id)
+                VALUES ($1, $2)
+                ON CONFLICT DO NOTHING
+            "#,
+            top_id,
+            anhang_id
+        )
+        .execute(&mut *self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_anhang_from_top(&mut self, top_id: Uuid, anhang_id: Uuid) -> Result<()> {
+        sqlx::query!(
+            r#"
+                DELETE FROM top_attachment_mapping
+                WHERE top_id = $1 AND attachment_id = $2
+            "#,
+            top_id,
+            anhang_id
+        )
+        .execute(&mut *self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn add_anhang_to_sitzung(&mut self, sitzung_id: Uuid, anhang_id: Uuid) -> Result<()> {
+        sqlx::query!(
+            r#"
+                INSERT INTO sitzung_attachment_mapping (sitzung_id, attachment_id)
+                VALUES ($1, $2)
+                ON CONFLICT DO NOTHING
+            "#,
+            sitzung_id,
+            anhang_id
+        )
+        .execute(&mut *self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_anhang_from_sitzung(
+        &mut self,
+        sitzung_id: Uuid,
+        anhang_id: Uuid,
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"
+                DELETE FROM sitzung_attachment_mapping
+                WHERE sitzung_id = $1 AND attachment_id = $2
+            "#,
+            sitzung_id,
+            anhang_id
+        )
+        .execute(&mut *self)
+        .await?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -451,7 +924,10 @@ mod test {
     use sqlx::PgPool;
     use uuid::Uuid;
 
-    use crate::domain::sitzung::{SitzungRepo, SitzungTyp, TopTyp};
+    use crate::domain::pagination::{Pagination, SortOrder};
+    use crate::domain::sitzung::{
+        SitzungFilter, SitzungRepo, SitzungSort, SitzungStatus, SitzungTyp, TopTyp,
+    };
 
     #[sqlx::test(fixtures("gimme_legislative_period"))]
     async fn create_sitzung(pool: PgPool) -> Result<()> {
@@ -472,6 +948,7 @@ mod test {
                 sitzung_kind,
                 antragsfrist.into(),
                 legislative_period_id,
+                None,
             )
             .await?;
 
@@ -484,6 +961,60 @@ mod test {
         Ok(())
     }
 
+    #[sqlx::test(fixtures("gimme_sitzungen"))]
+    async fn sitzungen(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let alle = conn
+            .sitzungen(&SitzungFilter::default(), Pagination::alle())
+            .await?;
+
+        assert_eq!(alle.total, 8);
+        assert!(alle
+            .items
+            .windows(2)
+            .all(|w| w[0].datetime <= w[1].datetime));
+
+        let vvs = conn
+            .sitzungen(
+                &SitzungFilter {
+                    typ: Some(SitzungTyp::VV),
+                    ab: Some(DateTime::parse_from_rfc3339("2024-09-20T00:00:00+02:00")?.into()),
+                    sort: SitzungSort::Datetime,
+                    order: SortOrder::Desc,
+                    ..Default::default()
+                },
+                Pagination {
+                    limit: Some(2),
+                    offset: 1,
+                },
+            )
+            .await?;
+
+        assert_eq!(vvs.total, 3);
+        assert_eq!(
+            vvs.items.iter().map(|s| s.id).collect::<Vec<_>>(),
+            vec![
+                Uuid::parse_str("1e89dd3e-04fc-4f66-9ab3-e8e5bedcf053").unwrap(),
+                Uuid::parse_str("76f4a8a9-8944-4d89-b6b8-8cdbc1acedb2").unwrap(),
+            ]
+        );
+
+        let andere_periode = conn
+            .sitzungen(
+                &SitzungFilter {
+                    legislatur_periode_id: Some(Uuid::new_v4()),
+                    ..Default::default()
+                },
+                Pagination::alle(),
+            )
+            .await?;
+
+        assert_eq!(andere_periode.total, 0);
+
+        Ok(())
+    }
+
     #[sqlx::test(fixtures("gimme_sitzungen"))]
     async fn create_top(pool: PgPool) -> Result<()> {
         let mut conn = pool.acquire().await?;
@@ -723,4 +1254,82 @@ mod test {
 
         Ok(())
     }
+
+    #[sqlx::test(fixtures("gimme_sitzungen", "gimme_tops"))]
+    async fn create_vertagung(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let top_id = Uuid::parse_str("44e9af7f-c183-4e82-8f3c-c421cb87f506").unwrap();
+        let von_sitzung_id = Uuid::parse_str("dfe75b8c-8c24-4a2b-84e5-d0573a8e6f00").unwrap();
+        let nach_sitzung_id = Uuid::parse_str("177b861d-0447-45ce-bc56-9eb68991cbda").unwrap();
+
+        let vertagung = conn
+            .create_vertagung(top_id, von_sitzung_id, nach_sitzung_id)
+            .await?;
+
+        assert_eq!(vertagung.top_id, top_id);
+        assert_eq!(vertagung.von_sitzung_id, Some(von_sitzung_id));
+        assert_eq!(vertagung.nach_sitzung_id, nach_sitzung_id);
+
+        for sitzung_id in [von_sitzung_id, nach_sitzung_id] {
+            let vertagungen = conn.vertagungen_by_top(top_id, sitzung_id).await?;
+
+            assert_eq!(vertagungen.len(), 1);
+            assert_eq!(vertagungen[0].id, vertagung.id);
+        }
+
+        let andere_sitzung_id = Uuid::parse_str("76f4a8a9-8944-4d89-b6b8-8cdbc1acedb2").unwrap();
+        assert!(conn
+            .vertagungen_by_top(top_id, andere_sitzung_id)
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("gimme_sitzungen"))]
+    async fn set_sitzung_status(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let sitzung_id = Uuid::parse_str("76f4a8a9-8944-4d89-b6b8-8cdbc1acedb2").unwrap();
+
+        let sitzung = conn.sitzung_by_id(sitzung_id).await?.unwrap();
+
+        assert_eq!(sitzung.status, SitzungStatus::Geplant);
+        assert!(sitzung.status_grund.is_none());
+
+        let grund = "keiner hat zeit";
+
+        let abgesagt = conn
+            .set_sitzung_status(sitzung_id, SitzungStatus::Abgesagt, Some(grund))
+            .await?
+            .unwrap();
+
+        assert_eq!(abgesagt.status, SitzungStatus::Abgesagt);
+        assert_eq!(abgesagt.status_grund.as_deref(), Some(grund));
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("gimme_sitzungen"))]
+    async fn create_verschiebung(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let sitzung_id = Uuid::parse_str("76f4a8a9-8944-4d89-b6b8-8cdbc1acedb2").unwrap();
+        let alte_datetime = DateTime::parse_from_rfc3339("2024-09-24T12:30:00+02:00").unwrap();
+        let neue_datetime = DateTime::parse_from_rfc3339("2024-09-25T12:30:00+02:00").unwrap();
+
+        let verschiebung = conn
+            .create_verschiebung(sitzung_id, alte_datetime.into(), neue_datetime.into(), None)
+            .await?;
+
+        assert_eq!(verschiebung.alte_datetime, alte_datetime);
+        assert_eq!(verschiebung.neue_datetime, neue_datetime);
+
+        let verschiebungen = conn.verschiebungen_by_sitzung(sitzung_id).await?;
+
+        assert_eq!(verschiebungen.len(), 1);
+
+        Ok(())
+    }
 }
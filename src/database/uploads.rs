@@ -0,0 +1,114 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::domain::upload::{Upload, UploadRepo};
+use crate::domain::Result;
+
+impl UploadRepo for PgConnection {
+    async fn create_upload(
+        &mut self,
+        person_id: Uuid,
+        filename: &str,
+        size: i64,
+    ) -> Result<Upload> {
+        let result = sqlx::query_as!(
+            Upload,
+            r#"
+                INSERT INTO uploads (person_id, filename, size)
+                VALUES ($1, $2, $3)
+                RETURNING *
+            "#,
+            person_id,
+            filename,
+            size
+        )
+        .fetch_one(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn upload_by_id(&mut self, id: Uuid) -> Result<Option<Upload>> {
+        let result = sqlx::query_as!(
+            Upload,
+            r#"
+                SELECT * FROM uploads
+                WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_upload(&mut self, id: Uuid) -> Result<Option<Upload>> {
+        let result = sqlx::query_as!(
+            Upload,
+            r#"
+                DELETE FROM uploads
+                WHERE id = $1
+                RETURNING *
+            "#,
+            id
+        )
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn uploads_before(&mut self, erstellt_vor: DateTime<Utc>) -> Result<Vec<Upload>> {
+        let result = sqlx::query_as!(
+            Upload,
+            r#"
+                SELECT * FROM uploads
+                WHERE erstellt_am < $1
+            "#,
+            erstellt_vor
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use chrono::{TimeDelta, Utc};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::domain::upload::UploadRepo;
+
+    #[sqlx::test(fixtures("gimme_persons"))]
+    async fn create_and_delete_upload(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let person_id = Uuid::parse_str("5a5a134d-9345-4c36-a466-1c3bb806b240").unwrap();
+
+        let upload = conn.create_upload(person_id, "scan.pdf", 1234).await?;
+
+        assert_eq!(conn.upload_by_id(upload.id).await?.unwrap().size, 1234);
+        assert!(conn
+            .uploads_before(Utc::now() - TimeDelta::hours(1))
+            .await?
+            .is_empty());
+        assert_eq!(
+            conn.uploads_before(Utc::now() + TimeDelta::hours(1))
+                .await?
+                .len(),
+            1
+        );
+
+        conn.delete_upload(upload.id).await?;
+
+        assert!(conn.upload_by_id(upload.id).await?.is_none());
+
+        Ok(())
+    }
+}
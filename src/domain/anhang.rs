@@ -1,19 +1,279 @@
+use async_std::{fs, io::ReadExt, path::Path};
+use chrono::{DateTime, TimeDelta, Utc};
+use mime::Mime;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 use super::Result;
 
-#[derive(Debug, Serialize, IntoParams, ToSchema)]
+#[derive(Debug, Clone, Serialize, IntoParams, ToSchema)]
 pub struct Anhang {
     pub id: Uuid,
     pub filename: String,
+    pub content_type: String,
+    /// Size in bytes, unknown for attachments uploaded before it was recorded
+    pub size: Option<i64>,
+    /// Hex encoded SHA-256 hash of the content
+    pub sha256: Option<String>,
+    pub erstellt_am: DateTime<Utc>,
+    /// Staged Anhänge are stored, but the request uploading them did not finish yet
+    pub bestaetigt: bool,
+    /// The person who uploaded the Anhang, unknown for attachments uploaded before it was
+    /// recorded
+    #[serde(skip)]
+    pub hochgeladen_von: Option<Uuid>,
 }
 
+/// What comparing the Anhänge with the blobs in the storage found
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct AnhangReconciliation {
+    /// Keys of blobs that belonged to no Anhang, they were deleted
+    pub deleted_blobs: Vec<String>,
+    /// Anhänge whose content is missing in the storage. They are kept, a storage that is
+    /// configured wrongly would lose all of them otherwise
+    pub missing_blobs: Vec<Anhang>,
+}
+
+/// An uploaded file that is about to become an Anhang
+#[derive(Debug)]
+pub struct Datei {
+    pub filename: String,
+    pub content_type: Mime,
+    pub size: u64,
+    /// Hex encoded SHA-256 hash of the content
+    pub sha256: String,
+}
+
+/// Staged Anhänge older than this are considered failed uploads
+pub const STAGING_TIMEOUT: TimeDelta = TimeDelta::hours(1);
+
 pub trait AnhangRepo {
-    async fn create_anhang(&mut self, filename: String) -> Result<Anhang>;
+    /// Creates a staged Anhang, which has to be confirmed once its content is stored
+    async fn create_anhang(
+        &mut self,
+        filename: &str,
+        content_type: &str,
+        size: i64,
+        sha256: &str,
+        hochgeladen_von: Uuid,
+    ) -> Result<Anhang>;
+
+    /// Confirms a staged Anhang uploaded by the person. Anhänge that were confirmed already or
+    /// that were uploaded by someone else are left alone
+    async fn confirm_anhang(&mut self, id: Uuid, hochgeladen_von: Uuid) -> Result<Option<Anhang>>;
+
+    /// Deletes the Anhang, unless it is confirmed and attached to something or it is staged and
+    /// was created at or after `staged_before`. Without `staged_before`, staged Anhänge are kept.
+    /// Returns the deleted Anhang
+    async fn delete_unused_anhang(
+        &mut self,
+        id: Uuid,
+        staged_before: Option<DateTime<Utc>>,
+    ) -> Result<Option<Anhang>>;
+
+    /// Whether the Anhang is attached to a Sitzung or one of its Tops
+    async fn is_anhang_of_sitzung(&mut self, id: Uuid) -> Result<bool>;
+
+    /// Ids of the Anträge the Anhang is attached to
+    async fn antraege_by_anhang(&mut self, id: Uuid) -> Result<Vec<Uuid>>;
+
+    /// Stores the text extracted from an Anhang for the full-text search
+    async fn set_anhang_text(&mut self, id: Uuid, text: &str) -> Result<()>;
+
+    /// Anhänge of the given types whose text was not extracted yet
+    async fn anhaenge_without_text(&mut self, content_types: &[&str]) -> Result<Vec<Anhang>>;
 
-    async fn delete_anhang(&mut self, id: Uuid) -> Result<Option<Anhang>>;
+    /// Confirmed Anhänge that are not attached to any Antrag, Top or Sitzung and Anhänge staged
+    /// before the given time
+    async fn unused_anhaenge(&mut self, staged_before: DateTime<Utc>) -> Result<Vec<Anhang>>;
+
+    async fn anhaenge(&mut self) -> Result<Vec<Anhang>>;
 
     async fn anhang_by_id(&mut self, id: Uuid) -> Result<Option<Anhang>>;
+
+    /// The Anhang, if it is attached to the Antrag
+    async fn anhang_by_antrag(&mut self, antrag_id: Uuid, id: Uuid) -> Result<Option<Anhang>>;
+
+    /// The Anhang, if it is attached to the Top of the Sitzung
+    async fn anhang_by_top(
+        &mut self,
+        sitzung_id: Uuid,
+        top_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<Anhang>>;
+
+    /// The Anhang, if it is attached to the Sitzung
+    async fn anhang_by_sitzung(&mut self, sitzung_id: Uuid, id: Uuid) -> Result<Option<Anhang>>;
+
+    async fn anhaenge_by_antrag(&mut self, antrag_id: Uuid) -> Result<Vec<Anhang>>;
+
+    async fn anhaenge_by_top(&mut self, top_id: Uuid) -> Result<Vec<Anhang>>;
+
+    async fn anhaenge_by_sitzung(&mut self, sitzung_id: Uuid) -> Result<Vec<Anhang>>;
+}
+
+/// File signatures of the types we recognize by their content
+const SIGNATURES: [(&[u8], &str); 5] = [
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF8", "image/gif"),
+    (b"PK\x03\x04", "application/zip"),
+];
+
+/// Detects the type of an uploaded file from its content, falling back to its file extension.
+///
+/// The extension is only trusted for types without a file signature, so a file called `x.pdf`
+/// is not served as a PDF unless it actually is one.
+pub fn detect_content_type(content: &[u8], filename: &str) -> Mime {
+    let guessed = mime_guess::from_path(filename).first();
+
+    if content.len() >= 12 && &content[..4] == b"RIFF" && &content[8..12] == b"WEBP" {
+        return "image/webp".parse().unwrap();
+    }
+
+    if let Some((_, content_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| content.starts_with(signature))
+    {
+        // office documents are zip files, their extension tells which one
+        if *content_type == "application/zip" {
+            if let Some(office) = guessed.filter(|guess| {
+                guess.subtype() == "zip"
+                    || guess.subtype().as_str().starts_with("vnd.openxmlformats")
+                    || guess
+                        .subtype()
+                        .as_str()
+                        .starts_with("vnd.oasis.opendocument")
+            }) {
+                return office;
+            }
+        }
+
+        return content_type.parse().unwrap();
+    }
+
+    match guessed {
+        Some(guess)
+            if guess.essence_str() != "image/webp"
+                && !SIGNATURES
+                    .iter()
+                    .any(|(_, content_type)| guess.essence_str() == *content_type) =>
+        {
+            guess
+        }
+        _ => mime::APPLICATION_OCTET_STREAM,
+    }
+}
+
+/// Whether the type matches one of the allowed types, which may use wildcards like `image/*`
+pub fn is_content_type_allowed(content_type: &Mime, allowed: &[String]) -> bool {
+    allowed.iter().any(|allowed| {
+        allowed == content_type.essence_str()
+            || allowed
+                .strip_suffix("/*")
+                .is_some_and(|typ| typ == content_type.type_().as_str())
+    })
+}
+
+/// Detects type, size and hash of an uploaded file, reading it in chunks instead of loading it
+/// into memory
+pub async fn inspect_datei(path: &Path, filename: &str) -> Result<Datei> {
+    let mut file = fs::File::open(path).await?;
+
+    let mut hasher = Sha256::new();
+    // the longest signature we check is 12 bytes long
+    let mut kopf = Vec::with_capacity(12);
+    let mut size = 0;
+    let mut chunk = vec![0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut chunk).await?;
+
+        if read == 0 {
+            break;
+        }
+
+        if kopf.len() < 12 {
+            let fehlend = (12 - kopf.len()).min(read);
+            kopf.extend_from_slice(&chunk[..fehlend]);
+        }
+
+        hasher.update(&chunk[..read]);
+        size += read as u64;
+    }
+
+    Ok(Datei {
+        filename: filename.to_string(),
+        content_type: detect_content_type(&kopf, filename),
+        size,
+        sha256: format!("{:x}", hasher.finalize()),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use async_std::fs;
+    use uuid::Uuid;
+
+    use super::{detect_content_type, inspect_datei, is_content_type_allowed};
+
+    #[tokio::test]
+    async fn inspects_dateien() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let content = [b"%PDF-1.7\n".as_slice(), &vec![b'x'; 100_000]].concat();
+        fs::write(&path, &content).await?;
+
+        let datei = inspect_datei(path.as_path().into(), "antrag.pdf").await?;
+        fs::remove_file(&path).await?;
+
+        assert_eq!(datei.filename, "antrag.pdf");
+        assert_eq!(datei.content_type.essence_str(), "application/pdf");
+        assert_eq!(datei.size, content.len() as u64);
+        assert_eq!(
+            datei.sha256,
+            format!("{:x}", <sha2::Sha256 as sha2::Digest>::digest(&content))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn detect_content_types() {
+        assert_eq!(
+            detect_content_type(b"%PDF-1.7\n...", "antrag.pdf").essence_str(),
+            "application/pdf"
+        );
+        assert_eq!(
+            detect_content_type(b"%PDF-1.7\n...", "antrag").essence_str(),
+            "application/pdf"
+        );
+        assert_eq!(
+            detect_content_type(b"<script>alert(1)</script>", "antrag.pdf").essence_str(),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            detect_content_type(b"PK\x03\x04...", "haushalt.xlsx").essence_str(),
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+        );
+        assert_eq!(
+            detect_content_type(b"PK\x03\x04...", "haushalt.pdf").essence_str(),
+            "application/zip"
+        );
+        assert_eq!(
+            detect_content_type(b"Hallo", "notizen.txt").essence_str(),
+            "text/plain"
+        );
+    }
+
+    #[test]
+    fn allowed_content_types() {
+        let allowed = vec!["application/pdf".to_string(), "image/*".to_string()];
+
+        assert!(is_content_type_allowed(&mime::APPLICATION_PDF, &allowed));
+        assert!(is_content_type_allowed(&mime::IMAGE_PNG, &allowed));
+        assert!(!is_content_type_allowed(&mime::TEXT_HTML, &allowed));
+    }
 }
@@ -1,9 +1,27 @@
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use super::Result;
+use super::pagination::{Page, Pagination, SortOrder};
+use super::persons::Person;
+use super::{Error, Result};
+
+/// Who gets to see an Antrag. Antragstellende and persons invited to the Antrag can always see it
+#[derive(
+    Clone, Copy, Debug, Default, Serialize, Deserialize, sqlx::Type, ToSchema, PartialEq, Eq,
+)]
+#[sqlx(type_name = "antragsichtbarkeit", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Sichtbarkeit {
+    /// Only visible to its Antragstellende, invited persons and those managing Anträge
+    #[serde(alias = "privat")]
+    Entwurf,
+    /// Additionally visible to everyone allowed to see hidden or protected content
+    Intern,
+    #[default]
+    Oeffentlich,
+}
 
 #[derive(Debug, Serialize, ToSchema, PartialEq, Clone)]
 pub struct AntragData {
@@ -12,6 +30,7 @@ pub struct AntragData {
     pub antragstext: String,
     pub begruendung: String,
     pub erstellt_am: DateTime<Utc>,
+    pub sichtbarkeit: Sichtbarkeit,
 }
 
 #[derive(Debug, Serialize, IntoParams, ToSchema, PartialEq)]
@@ -22,6 +41,77 @@ pub struct Antrag {
     pub anhaenge: Vec<Uuid>,
 }
 
+/// Access to an Antrag granted to a Person that is not one of its Antragstellende
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, sqlx::Type, ToSchema, PartialEq, Eq)]
+#[sqlx(type_name = "antragsrolle", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AntragsRolle {
+    /// May edit the Antrag like one of its Antragstellende
+    Editor,
+    /// May only read the Antrag
+    Viewer,
+    /// May read and comment on the Antrag, but not edit it
+    Reviewer,
+}
+
+#[derive(Debug, Serialize, IntoParams, ToSchema, PartialEq)]
+pub struct AntragBerechtigung {
+    pub antrag_id: Uuid,
+    pub person_id: Uuid,
+    pub rolle: AntragsRolle,
+}
+
+#[derive(Debug, Serialize, IntoParams, ToSchema, PartialEq)]
+pub struct AntragKommentar {
+    pub id: Uuid,
+    pub antrag_id: Uuid,
+    pub person_id: Uuid,
+    pub text: String,
+    pub erstellt_am: DateTime<Utc>,
+}
+
+/// Whoever looks at Anträge, used to decide which of them they get to see
+#[derive(Debug, Default)]
+pub struct AntragBetrachter {
+    pub person: Option<Person>,
+    /// May see interne Anträge
+    pub intern: bool,
+    /// May see every Antrag, including the Entwürfe of others
+    pub alle: bool,
+}
+
+impl AntragBetrachter {
+    /// The person of the betrachter, everything beyond looking at Anträge needs one
+    pub fn require_person(&self) -> Result<&Person> {
+        self.person
+            .as_ref()
+            .ok_or_else(|| Error::Unauthorized("you need to be logged in to do this".to_string()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema, PartialEq, Eq, strum::AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AntragSort {
+    #[default]
+    ErstelltAm,
+    Titel,
+}
+
+/// Which Anträge to list and in which order
+#[derive(Debug, Default)]
+pub struct AntragFilter {
+    /// Only Anträge this person is one of the Antragstellenden of
+    pub ersteller: Option<Uuid>,
+    /// Only Anträge created at or after this time
+    pub erstellt_ab: Option<DateTime<Utc>>,
+    /// Only Anträge created at or before this time
+    pub erstellt_bis: Option<DateTime<Utc>>,
+    pub sichtbarkeit: Option<Sichtbarkeit>,
+    pub sort: AntragSort,
+    pub order: SortOrder,
+}
+
 pub trait AntragRepo {
     async fn create_antrag(
         &mut self,
@@ -30,12 +120,28 @@ pub trait AntragRepo {
         begruendung: &str,
         antragstext: &str,
         erstellt_am: DateTime<Utc>,
+        sichtbarkeit: Sichtbarkeit,
     ) -> Result<Antrag>;
 
-    async fn antraege(&mut self) -> Result<Vec<Antrag>>;
+    /// The Anträge matching the filter that the betrachter may see
+    async fn antraege(
+        &mut self,
+        betrachter: &AntragBetrachter,
+        filter: &AntragFilter,
+        pagination: Pagination,
+    ) -> Result<Page<Antrag>>;
 
     async fn antrag_by_id(&mut self, id: Uuid) -> Result<Option<Antrag>>;
 
+    /// Those of the Anträge that the betrachter may see
+    async fn visible_antrag_ids(
+        &mut self,
+        betrachter: &AntragBetrachter,
+        ids: &[Uuid],
+    ) -> Result<Vec<Uuid>>;
+
+    async fn antraege_by_ersteller(&mut self, person_id: Uuid) -> Result<Vec<Antrag>>;
+
     async fn update_antrag<'a>(
         &mut self,
         id: Uuid,
@@ -46,9 +152,48 @@ pub trait AntragRepo {
         antragstext: Option<&'a str>,
     ) -> Result<Option<Antrag>>;
 
+    async fn set_antrag_sichtbarkeit(
+        &mut self,
+        id: Uuid,
+        sichtbarkeit: Sichtbarkeit,
+    ) -> Result<Option<AntragData>>;
+
     async fn delete_antrag(&mut self, id: Uuid) -> Result<Option<AntragData>>;
 
     async fn add_anhang_to_antrag(&mut self, antrags_id: Uuid, anhang_id: Uuid) -> Result<()>;
 
     async fn delete_anhang_from_antrag(&mut self, antrags_id: Uuid, anhang_id: Uuid) -> Result<()>;
+
+    async fn berechtigungen_by_antrag(
+        &mut self,
+        antrag_id: Uuid,
+    ) -> Result<Vec<AntragBerechtigung>>;
+
+    async fn berechtigung(
+        &mut self,
+        antrag_id: Uuid,
+        person_id: Uuid,
+    ) -> Result<Option<AntragBerechtigung>>;
+
+    async fn set_berechtigung(
+        &mut self,
+        antrag_id: Uuid,
+        person_id: Uuid,
+        rolle: AntragsRolle,
+    ) -> Result<AntragBerechtigung>;
+
+    async fn delete_berechtigung(
+        &mut self,
+        antrag_id: Uuid,
+        person_id: Uuid,
+    ) -> Result<Option<AntragBerechtigung>>;
+
+    async fn create_kommentar(
+        &mut self,
+        antrag_id: Uuid,
+        person_id: Uuid,
+        text: &str,
+    ) -> Result<AntragKommentar>;
+
+    async fn kommentare_by_antrag(&mut self, antrag_id: Uuid) -> Result<Vec<AntragKommentar>>;
 }
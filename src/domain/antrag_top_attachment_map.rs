@@ -3,7 +3,7 @@ use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 use super::antrag::Antrag;
-use super::sitzung::Top;
+use super::sitzung::{Sitzung, Top, TopTyp};
 use super::Result;
 
 #[derive(Debug, Serialize, IntoParams, ToSchema)]
@@ -12,6 +12,21 @@ pub struct AntragTopMapping {
     pub top_id: Uuid,
 }
 
+/// A Top that is (or would be, in a dry run) created for orphaned Anträge
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlannedTop {
+    pub top_id: Option<Uuid>,
+    pub name: String,
+    pub typ: TopTyp,
+    pub antraege: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrphanPlan {
+    pub sitzung: Sitzung,
+    pub tops: Vec<PlannedTop>,
+}
+
 pub trait AntragTopAttachmentMap {
     async fn antraege_by_top(&mut self, top_id: Uuid) -> Result<Vec<Antrag>>;
 
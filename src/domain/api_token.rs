@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::{Capability, Result};
+
+/// Prefix of every token secret, used to tell them apart from OAuth access tokens
+pub const API_TOKEN_PREFIX: &str = "fscs_";
+
+const API_TOKEN_LENGTH: usize = 40;
+
+#[derive(Debug, Serialize, IntoParams, ToSchema, Clone)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub person_id: Uuid,
+    pub name: String,
+    pub capabilities: Vec<Capability>,
+    pub erstellt_am: DateTime<Utc>,
+    pub ablaufdatum: DateTime<Utc>,
+    pub widerrufen_am: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    pub fn is_valid(&self, now: DateTime<Utc>) -> bool {
+        self.widerrufen_am.is_none() && self.ablaufdatum > now
+    }
+
+    /// Whether the owner still holds every capability the token is scoped to. Tokens never grant
+    /// more than their owner may currently do themselves
+    pub fn is_scope_held(&self, owner_capabilities: &HashSet<Capability>) -> bool {
+        owner_capabilities.contains(&Capability::Admin)
+            || self
+                .capabilities
+                .iter()
+                .all(|cap| owner_capabilities.contains(cap))
+    }
+}
+
+/// A freshly created ApiToken together with its secret. The secret is only known at this point,
+/// the database just stores its hash.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    pub token: ApiToken,
+    pub secret: String,
+}
+
+pub trait ApiTokenRepo {
+    async fn create_api_token(
+        &mut self,
+        person_id: Uuid,
+        name: &str,
+        token_hash: &str,
+        capabilities: &[Capability],
+        ablaufdatum: DateTime<Utc>,
+    ) -> Result<ApiToken>;
+
+    async fn api_token_by_hash(&mut self, token_hash: &str) -> Result<Option<ApiToken>>;
+
+    async fn api_tokens_by_person(&mut self, person_id: Uuid) -> Result<Vec<ApiToken>>;
+
+    async fn revoke_api_token(
+        &mut self,
+        id: Uuid,
+        person_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ApiToken>>;
+}
+
+pub fn hash_api_token(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+fn generate_api_token() -> String {
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+
+    format!("{API_TOKEN_PREFIX}{random}")
+}
+
+pub async fn create_api_token(
+    repo: &mut impl ApiTokenRepo,
+    person_id: Uuid,
+    name: &str,
+    capabilities: &[Capability],
+    ablaufdatum: DateTime<Utc>,
+) -> Result<CreatedApiToken> {
+    let secret = generate_api_token();
+
+    let token = repo
+        .create_api_token(
+            person_id,
+            name,
+            &hash_api_token(&secret),
+            capabilities,
+            ablaufdatum,
+        )
+        .await?;
+
+    Ok(CreatedApiToken { token, secret })
+}
+
+/// Looks up the token belonging to `secret`, as long as it is neither expired nor revoked
+pub async fn valid_api_token(
+    repo: &mut impl ApiTokenRepo,
+    secret: &str,
+    now: DateTime<Utc>,
+) -> Result<Option<ApiToken>> {
+    let token = repo.api_token_by_hash(&hash_api_token(secret)).await?;
+
+    Ok(token.filter(|token| token.is_valid(now)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::ApiToken;
+    use crate::domain::Capability;
+
+    #[test]
+    fn scope_has_to_be_held_by_owner() {
+        let token = ApiToken {
+            id: Uuid::new_v4(),
+            person_id: Uuid::new_v4(),
+            name: "bot".to_string(),
+            capabilities: vec![Capability::Admin],
+            erstellt_am: Utc::now(),
+            ablaufdatum: Utc::now(),
+            widerrufen_am: None,
+        };
+
+        assert!(token.is_scope_held(&HashSet::from([Capability::Admin])));
+        assert!(!token.is_scope_held(&HashSet::from([Capability::ManageSitzungen])));
+        assert!(!token.is_scope_held(&HashSet::new()));
+
+        let sitzungen = ApiToken {
+            capabilities: vec![Capability::ManageSitzungen],
+            ..token
+        };
+
+        assert!(sitzungen.is_scope_held(&HashSet::from([Capability::ManageSitzungen])));
+        assert!(sitzungen.is_scope_held(&HashSet::from([Capability::Admin])));
+        assert!(!sitzungen.is_scope_held(&HashSet::from([Capability::ManageAntraege])));
+    }
+}
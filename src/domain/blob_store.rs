@@ -0,0 +1,103 @@
+use std::ops::Range;
+use std::pin::Pin;
+
+use async_std::fs;
+use async_std::io::WriteExt;
+use async_std::path::Path;
+use bytes::Bytes;
+use futures_util::{Stream, TryStreamExt};
+use sha2::{Digest, Sha256};
+
+use super::Result;
+
+/// The content of a blob, delivered in chunks
+pub type BlobStream = Pin<Box<dyn Stream<Item = Result<Bytes>>>>;
+
+/// Stores the content of Anhänge, addressed by a key
+pub trait BlobStore {
+    async fn put(&self, key: &str, content: Vec<u8>) -> Result<()>;
+
+    /// Stores the content of a local file, without loading it into memory
+    async fn put_file(&self, key: &str, path: &Path) -> Result<()>;
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Streams the blob, or only the given range of bytes of it
+    async fn get_stream(&self, key: &str, range: Option<Range<u64>>) -> Result<Option<BlobStream>>;
+
+    /// Deletes the blob, if it exists
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// The keys of all blobs in the store
+    async fn keys(&self) -> Result<Vec<String>>;
+}
+
+/// Reads a blob into memory, but stops as soon as it is larger than `limit` bytes. `None` if the
+/// blob does not exist, `Some(None)` if it is too large
+pub async fn read_limited(
+    store: &impl BlobStore,
+    key: &str,
+    limit: u64,
+) -> Result<Option<Option<Vec<u8>>>> {
+    let Some(mut stream) = store.get_stream(key, None).await? else {
+        return Ok(None);
+    };
+
+    let mut content = Vec::new();
+
+    while let Some(chunk) = stream.try_next().await? {
+        if (content.len() + chunk.len()) as u64 > limit {
+            return Ok(Some(None));
+        }
+
+        content.extend_from_slice(&chunk);
+    }
+
+    Ok(Some(Some(content)))
+}
+
+/// The hex encoded SHA-256 of a blob, computed while streaming it. `None` if it does not exist
+pub async fn sha256(store: &impl BlobStore, key: &str) -> Result<Option<String>> {
+    let Some(mut stream) = store.get_stream(key, None).await? else {
+        return Ok(None);
+    };
+
+    let mut hasher = Sha256::new();
+
+    while let Some(chunk) = stream.try_next().await? {
+        hasher.update(&chunk);
+    }
+
+    Ok(Some(format!("{:x}", hasher.finalize())))
+}
+
+/// Copies a blob from one BlobStore to another through the local file at `path`, so it is never
+/// held in memory as a whole. Returns the hex encoded SHA-256 of the copied content, `None` if
+/// the blob does not exist
+pub async fn copy(
+    from: &impl BlobStore,
+    to: &impl BlobStore,
+    key: &str,
+    path: &Path,
+) -> Result<Option<String>> {
+    let Some(mut stream) = from.get_stream(key, None).await? else {
+        return Ok(None);
+    };
+
+    let mut file = fs::File::create(path).await?;
+    let mut hasher = Sha256::new();
+
+    while let Some(chunk) = stream.try_next().await? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+
+    file.flush().await?;
+    drop(file);
+
+    let result = to.put_file(key, path).await;
+    fs::remove_file(path).await?;
+    result?;
+
+    Ok(Some(format!("{:x}", hasher.finalize())))
+}
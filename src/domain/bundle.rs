@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+
+use super::anhang::Anhang;
+use super::antrag::Antrag;
+
+/// Longest file or directory name we put into a bundle, in characters
+const MAX_DATEINAME_LAENGE: usize = 100;
+
+/// Longest extension including its dot that is kept apart from the stem of a name
+const MAX_ENDUNG_LAENGE: usize = 10;
+
+/// A file of the ZIP bundle of a Sitzung
+#[derive(Debug)]
+pub enum BundleDatei {
+    /// A file generated for the bundle, like the Einladung or an Antrag
+    Text { pfad: String, inhalt: String },
+    /// An Anhang, its content is only loaded from the storage while the bundle is written
+    Anhang { pfad: String, anhang: Anhang },
+}
+
+#[cfg(test)]
+impl BundleDatei {
+    pub fn pfad(&self) -> &str {
+        match self {
+            BundleDatei::Text { pfad, .. } | BundleDatei::Anhang { pfad, .. } => pfad,
+        }
+    }
+}
+
+/// Makes a name usable as a single path component on every common file system. Long names are
+/// shortened, keeping their extension
+pub fn sanitize_dateiname(name: &str) -> String {
+    let sanitized = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+
+    // leading dots would hide the file or allow `..`, trailing ones are dropped by Windows
+    let sanitized = sanitized.trim_matches(|c: char| c == '.' || c.is_whitespace());
+
+    let (stamm, endung) = split_endung(sanitized);
+    let stamm = stamm
+        .chars()
+        .take(MAX_DATEINAME_LAENGE - endung.chars().count())
+        .collect::<String>();
+    let stamm = stamm.trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+
+    if stamm.is_empty() {
+        "unbenannt".to_string()
+    } else {
+        format!("{stamm}{endung}")
+    }
+}
+
+/// Splits a name into its stem and its extension including the dot, which may be empty
+fn split_endung(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(index) if index > 0 && name[index..].chars().count() <= MAX_ENDUNG_LAENGE => {
+            name.split_at(index)
+        }
+        _ => (name, ""),
+    }
+}
+
+/// Renders an Antrag as a Markdown document
+pub fn antrag_markdown(antrag: &Antrag) -> String {
+    format!(
+        "# {}\n\n## Antragstext\n\n{}\n\n## Begründung\n\n{}\n",
+        antrag.data.titel, antrag.data.antragstext, antrag.data.begruendung
+    )
+}
+
+/// Hands out unique paths, numbering names that were already taken like `name (2).pdf`
+#[derive(Debug, Default)]
+pub struct BundlePfade {
+    vergeben: HashSet<String>,
+}
+
+impl BundlePfade {
+    pub fn pfad(&mut self, verzeichnis: Option<&str>, name: &str) -> String {
+        let name = sanitize_dateiname(name);
+        let (stamm, endung) = split_endung(&name);
+
+        let mut nummer = 1;
+        loop {
+            let dateiname = match nummer {
+                1 => format!("{stamm}{endung}"),
+                n => format!("{stamm} ({n}){endung}"),
+            };
+            let pfad = match verzeichnis {
+                Some(verzeichnis) => format!("{verzeichnis}/{dateiname}"),
+                None => dateiname,
+            };
+
+            if self.vergeben.insert(pfad.to_lowercase()) {
+                return pfad;
+            }
+
+            nummer += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sanitize_dateiname, BundlePfade};
+
+    #[test]
+    fn sanitizes_dateinamen() {
+        assert_eq!(sanitize_dateiname("Haushalt 2024.pdf"), "Haushalt 2024.pdf");
+        assert_eq!(sanitize_dateiname("../../etc/passwd"), "_.._etc_passwd");
+        assert_eq!(
+            sanitize_dateiname("a\\b:c*d?e\"f<g>h|i"),
+            "a_b_c_d_e_f_g_h_i"
+        );
+        assert_eq!(sanitize_dateiname(".versteckt"), "versteckt");
+        assert_eq!(sanitize_dateiname(" .. "), "unbenannt");
+        assert_eq!(sanitize_dateiname("zeile\numbruch"), "zeile_umbruch");
+        assert_eq!(sanitize_dateiname(&"x".repeat(300)).len(), 100);
+
+        let lang = sanitize_dateiname(&format!("{}.pdf", "x".repeat(300)));
+        assert_eq!(lang.len(), 100);
+        assert!(lang.ends_with("x.pdf"));
+    }
+
+    #[test]
+    fn numbers_duplicate_pfade() {
+        let mut pfade = BundlePfade::default();
+
+        assert_eq!(
+            pfade.pfad(Some("01 Finanzen"), "Plan.pdf"),
+            "01 Finanzen/Plan.pdf"
+        );
+        assert_eq!(
+            pfade.pfad(Some("01 Finanzen"), "plan.pdf"),
+            "01 Finanzen/plan (2).pdf"
+        );
+        assert_eq!(
+            pfade.pfad(Some("01 Finanzen"), "Plan.pdf"),
+            "01 Finanzen/Plan (3).pdf"
+        );
+        assert_eq!(
+            pfade.pfad(Some("02 Sonstiges"), "Plan.pdf"),
+            "02 Sonstiges/Plan.pdf"
+        );
+        assert_eq!(pfade.pfad(None, "README"), "README");
+        assert_eq!(pfade.pfad(None, "README"), "README (2)");
+    }
+}
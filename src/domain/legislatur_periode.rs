@@ -1,34 +1,113 @@
-use serde::Serialize;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use crate::domain::sitzung::Sitzung;
+use crate::domain::{persons::Person, sitzung::Sitzung};
 
+use super::pagination::{Page, Pagination, SortOrder};
 use super::Result;
 
 #[derive(Debug, Serialize, IntoParams, ToSchema, Clone)]
 pub struct LegislaturPeriode {
     pub id: Uuid,
     pub name: String,
+    pub start: Option<NaiveDate>,
+    pub ende: Option<NaiveDate>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, sqlx::Type, ToSchema, PartialEq, Eq)]
+#[sqlx(type_name = "mitgliedsfunktion", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MitgliedsFunktion {
+    Gewaehlt,
+    Kooptiert,
+    Beratend,
+}
+
+#[derive(Debug, Serialize, IntoParams, ToSchema)]
+pub struct Mitgliedschaft {
+    pub legislatur_periode_id: Uuid,
+    pub person_id: Uuid,
+    pub funktion: MitgliedsFunktion,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Mitglied {
+    #[serde(flatten)]
+    pub person: Person,
+    pub funktion: MitgliedsFunktion,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema, PartialEq, Eq, strum::AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum LegislaturPeriodeSort {
+    #[default]
+    Start,
+    Name,
+}
+
+/// Which Legislaturperioden to list and in which order
+#[derive(Debug, Default)]
+pub struct LegislaturPeriodeFilter {
+    /// Only Legislaturperioden this date lies in
+    pub am: Option<NaiveDate>,
+    pub sort: LegislaturPeriodeSort,
+    pub order: SortOrder,
 }
 
 pub trait LegislaturPeriodeRepo {
-    async fn create_legislatur_periode(&mut self, name: String) -> Result<LegislaturPeriode>;
+    async fn create_legislatur_periode(
+        &mut self,
+        name: String,
+        start: Option<NaiveDate>,
+        ende: Option<NaiveDate>,
+    ) -> Result<LegislaturPeriode>;
 
     async fn legislatur_periode_by_id(&mut self, id: Uuid) -> Result<Option<LegislaturPeriode>>;
 
-    async fn legislatur_perioden(&mut self) -> Result<Vec<LegislaturPeriode>>;
+    async fn legislatur_periode_by_date(
+        &mut self,
+        date: NaiveDate,
+    ) -> Result<Option<LegislaturPeriode>>;
+
+    async fn legislatur_perioden(
+        &mut self,
+        filter: &LegislaturPeriodeFilter,
+        pagination: Pagination,
+    ) -> Result<Page<LegislaturPeriode>>;
 
     async fn sitzungen_by_legislatur_periode(
         &mut self,
         legislatur_periode_id: Uuid,
     ) -> Result<Vec<Sitzung>>;
 
+    async fn mitglieder_by_legislatur_periode(
+        &mut self,
+        legislatur_periode_id: Uuid,
+    ) -> Result<Vec<Mitglied>>;
+
+    async fn set_mitglied(
+        &mut self,
+        legislatur_periode_id: Uuid,
+        person_id: Uuid,
+        funktion: MitgliedsFunktion,
+    ) -> Result<Mitgliedschaft>;
+
     async fn update_legislatur_periode(
         &mut self,
         id: Uuid,
         name: String,
+        start: Option<NaiveDate>,
+        ende: Option<NaiveDate>,
     ) -> Result<Option<LegislaturPeriode>>;
 
     async fn delete_legislatur_periode(&mut self, id: Uuid) -> Result<Option<LegislaturPeriode>>;
+
+    async fn delete_mitglied(
+        &mut self,
+        legislatur_periode_id: Uuid,
+        person_id: Uuid,
+    ) -> Result<Option<Mitgliedschaft>>;
 }
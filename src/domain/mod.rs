@@ -1,19 +1,38 @@
-use antrag::Antrag;
-use antrag_top_attachment_map::AntragTopAttachmentMap;
-use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+
+use anhang::{Anhang, AnhangReconciliation, AnhangRepo, Datei};
+use antrag::{Antrag, AntragBetrachter, AntragRepo, AntragsRolle, Sichtbarkeit};
+use antrag_top_attachment_map::{AntragTopAttachmentMap, OrphanPlan, PlannedTop};
+use async_std::path::Path;
+use blob_store::BlobStore;
+use bundle::{BundleDatei, BundlePfade};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use search::{AnhangTreffer, AntragTreffer, SearchRepo, SearchResult};
 use uuid::Uuid;
 
 pub mod anhang;
 pub mod antrag;
 pub mod antrag_top_attachment_map;
+pub mod api_token;
+pub mod blob_store;
+pub mod bundle;
 pub mod calendar;
 pub mod legislatur_periode;
+pub mod pagination;
 pub mod persons;
+pub mod preview;
+pub mod search;
+pub mod session;
 pub mod sitzung;
 pub mod templates;
+pub mod upload;
 
+use legislatur_periode::{LegislaturPeriode, LegislaturPeriodeRepo};
 use persons::{Abmeldung, Person, PersonRepo};
-use sitzung::{SitzungRepo, SitzungWithTops, TopWithAntraege};
+use sitzung::{
+    SerienRegel, Sitzung, SitzungRepo, SitzungSerieWithSitzungen, SitzungStatus, SitzungTyp,
+    SitzungWithTops, TopTyp, TopWithAntraege,
+};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -29,9 +48,31 @@ pub enum Error {
     Message(String),
     #[error("{0}")]
     Template(#[from] upon::Error),
+    #[error("token error: {0}")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("zip error: {0}")]
+    Zip(#[from] async_zip::error::ZipError),
+    /// The user may not do what they asked for
+    #[error("{0}")]
+    Unauthorized(String),
 }
 
-#[derive(Debug, Clone, Copy, strum::EnumString, strum::Display, Eq, Hash, PartialEq)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    strum::EnumString,
+    strum::Display,
+    strum::EnumIter,
+    Eq,
+    Hash,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+)]
 #[strum(ascii_case_insensitive)]
 pub enum Capability {
     Admin,
@@ -41,29 +82,622 @@ pub enum Capability {
     CreateAntrag,
     ViewHidden,
     ViewProtected,
+    ManageLegislaturPerioden,
+    ManageRoles,
+    ManageCalendars,
+    ManageTemplates,
+    ViewAudit,
 }
 
-pub trait SitzungAntragService: SitzungRepo + AntragTopAttachmentMap {}
+pub trait SitzungAntragService:
+    SitzungRepo + AntragRepo + AntragTopAttachmentMap + AnhangRepo
+{
+}
 
-impl<T> SitzungAntragService for T where T: SitzungRepo + AntragTopAttachmentMap {}
+impl<T> SitzungAntragService for T where
+    T: SitzungRepo + AntragRepo + AntragTopAttachmentMap + AnhangRepo
+{
+}
 
 pub trait SitzungPersonService: SitzungRepo + PersonRepo {}
 
 impl<T> SitzungPersonService for T where T: SitzungRepo + PersonRepo {}
 
+pub trait SitzungLegislaturService: SitzungRepo + LegislaturPeriodeRepo {}
+
+impl<T> SitzungLegislaturService for T where T: SitzungRepo + LegislaturPeriodeRepo {}
+
+pub trait AntragService: AntragRepo + AntragTopAttachmentMap {}
+
+impl<T> AntragService for T where T: AntragRepo + AntragTopAttachmentMap {}
+
+/// The role of a person on an Antrag. Antragstellende are not listed in the Berechtigungen, they
+/// implicitly have every right on their Anträge.
+async fn antrags_rolle(
+    repo: &mut impl AntragRepo,
+    person: &Person,
+    antrag: &Antrag,
+) -> Result<Option<AntragsRolle>> {
+    let result = repo
+        .berechtigung(antrag.data.id, person.id)
+        .await?
+        .map(|berechtigung| berechtigung.rolle);
+
+    Ok(result)
+}
+
+pub async fn can_person_view_antrag(
+    repo: &mut impl AntragRepo,
+    person: &Person,
+    antrag: &Antrag,
+) -> Result<bool> {
+    if antrag.ersteller.contains(&person.id) {
+        return Ok(true);
+    }
+
+    Ok(antrags_rolle(repo, person, antrag).await?.is_some())
+}
+
+pub async fn can_person_comment_antrag(
+    repo: &mut impl AntragRepo,
+    person: &Person,
+    antrag: &Antrag,
+) -> Result<bool> {
+    if antrag.ersteller.contains(&person.id) {
+        return Ok(true);
+    }
+
+    let result = matches!(
+        antrags_rolle(repo, person, antrag).await?,
+        Some(AntragsRolle::Editor | AntragsRolle::Reviewer)
+    );
+
+    Ok(result)
+}
+
+/// Anträge can only be modified until they are put onto the agenda of a Sitzung
 pub async fn can_person_modify_antrag(
-    repo: &mut impl AntragTopAttachmentMap,
+    repo: &mut impl AntragService,
     person: &Person,
     antrag: &Antrag,
 ) -> Result<bool> {
-    let result = antrag.ersteller.contains(&person.id)
-        && repo.tops_by_antrag(antrag.data.id).await?.is_empty();
+    let is_editor = antrag.ersteller.contains(&person.id)
+        || antrags_rolle(repo, person, antrag).await? == Some(AntragsRolle::Editor);
+
+    let result = is_editor && repo.tops_by_antrag(antrag.data.id).await?.is_empty();
+
+    Ok(result)
+}
+
+/// Only Antragstellende may invite others to their Anträge
+pub fn can_person_manage_antrag_berechtigungen(person: &Person, antrag: &Antrag) -> bool {
+    antrag.ersteller.contains(&person.id)
+}
+
+/// Fails unless the betrachter may edit the Antrag and its Anhänge
+pub async fn ensure_can_modify_antrag(
+    repo: &mut impl AntragService,
+    betrachter: &AntragBetrachter,
+    antrag: &Antrag,
+) -> Result<()> {
+    if betrachter.alle {
+        return Ok(());
+    }
+
+    let person = betrachter.require_person()?;
+
+    if !can_person_modify_antrag(repo, person, antrag).await? {
+        return Err(Error::Unauthorized(
+            "you are not allowed to edit this antrag".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fails unless the betrachter may change who sees the Antrag. Invited Editors may edit the
+/// Antrag, but not publish it
+pub fn ensure_can_change_antrag_sichtbarkeit(
+    betrachter: &AntragBetrachter,
+    antrag: &Antrag,
+) -> Result<()> {
+    if betrachter.alle {
+        return Ok(());
+    }
+
+    let person = betrachter.require_person()?;
+
+    if !antrag.ersteller.contains(&person.id) {
+        return Err(Error::Unauthorized(
+            "only antragstellende may change the sichtbarkeit of this antrag".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fails unless the betrachter works on the Antrag, as only they see its Berechtigungen and
+/// Kommentare
+pub async fn ensure_can_view_antrag_details(
+    repo: &mut impl AntragRepo,
+    betrachter: &AntragBetrachter,
+    antrag: &Antrag,
+) -> Result<()> {
+    if betrachter.alle {
+        return Ok(());
+    }
+
+    let person = betrachter.require_person()?;
+
+    if !can_person_view_antrag(repo, person, antrag).await? {
+        return Err(Error::Unauthorized(
+            "you are not allowed to view this antrag".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fails unless the betrachter may comment on the Antrag. Returns the person the Kommentar is
+/// written by
+pub async fn ensure_can_comment_antrag<'a>(
+    repo: &mut impl AntragRepo,
+    betrachter: &'a AntragBetrachter,
+    antrag: &Antrag,
+) -> Result<&'a Person> {
+    let person = betrachter.require_person()?;
+
+    if !betrachter.alle && !can_person_comment_antrag(repo, person, antrag).await? {
+        return Err(Error::Unauthorized(
+            "you are not allowed to comment on this antrag".to_string(),
+        ));
+    }
+
+    Ok(person)
+}
+
+/// Fails unless the betrachter may change the Berechtigung of the person on the Antrag. Everyone
+/// may give up their own access
+pub fn ensure_can_manage_antrag_berechtigung(
+    betrachter: &AntragBetrachter,
+    antrag: &Antrag,
+    person_id: Uuid,
+    is_removal: bool,
+) -> Result<()> {
+    if betrachter.alle {
+        return Ok(());
+    }
+
+    let person = betrachter.require_person()?;
+
+    let gibt_auf = is_removal && person.id == person_id;
+
+    if !gibt_auf && !can_person_manage_antrag_berechtigungen(person, antrag) {
+        return Err(Error::Unauthorized(
+            "only antragstellende may manage the berechtigungen of this antrag".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+pub async fn can_view_antrag(
+    repo: &mut impl AntragRepo,
+    betrachter: &AntragBetrachter,
+    antrag: &Antrag,
+) -> Result<bool> {
+    let visible = match antrag.data.sichtbarkeit {
+        Sichtbarkeit::Oeffentlich => true,
+        Sichtbarkeit::Intern => betrachter.intern || betrachter.alle,
+        Sichtbarkeit::Entwurf => betrachter.alle,
+    };
+
+    if visible {
+        return Ok(true);
+    }
+
+    match &betrachter.person {
+        Some(person) => can_person_view_antrag(repo, person, antrag).await,
+        None => Ok(false),
+    }
+}
+
+/// Removes all Anträge the betrachter is not allowed to see
+pub async fn visible_antraege(
+    repo: &mut impl AntragRepo,
+    betrachter: &AntragBetrachter,
+    mut antraege: Vec<Antrag>,
+) -> Result<Vec<Antrag>> {
+    let ids = antraege
+        .iter()
+        .map(|antrag| antrag.data.id)
+        .collect::<Vec<_>>();
+
+    let visible = repo.visible_antrag_ids(betrachter, &ids).await?;
+
+    antraege.retain(|antrag| visible.contains(&antrag.data.id));
+
+    Ok(antraege)
+}
+
+/// The Anhang of the Antrag, if it is attached to it and the betrachter may view the Antrag
+pub async fn anhang_by_antrag(
+    repo: &mut (impl AntragRepo + AnhangRepo),
+    betrachter: &AntragBetrachter,
+    antrag_id: Uuid,
+    anhang_id: Uuid,
+) -> Result<Option<Anhang>> {
+    let Some(antrag) = repo.antrag_by_id(antrag_id).await? else {
+        return Ok(None);
+    };
+
+    if !can_view_antrag(repo, betrachter, &antrag).await? {
+        return Ok(None);
+    }
+
+    repo.anhang_by_antrag(antrag_id, anhang_id).await
+}
+
+/// Lists the Anhänge of an Antrag, if the betrachter is allowed to view the Antrag
+pub async fn anhaenge_by_antrag(
+    repo: &mut (impl AntragRepo + AnhangRepo),
+    betrachter: &AntragBetrachter,
+    antrag_id: Uuid,
+) -> Result<Option<Vec<Anhang>>> {
+    let Some(antrag) = repo.antrag_by_id(antrag_id).await? else {
+        return Ok(None);
+    };
+
+    if !can_view_antrag(repo, betrachter, &antrag).await? {
+        return Ok(None);
+    }
+
+    Ok(Some(repo.anhaenge_by_antrag(antrag_id).await?))
+}
+
+/// The Anhang, if the betrachter may see it. Anhänge of Sitzungen and Tops are public, those of
+/// Anträge share the sichtbarkeit of the Antrag
+pub async fn visible_anhang(
+    repo: &mut (impl AntragRepo + AnhangRepo),
+    betrachter: &AntragBetrachter,
+    id: Uuid,
+) -> Result<Option<Anhang>> {
+    let Some(anhang) = repo.anhang_by_id(id).await? else {
+        return Ok(None);
+    };
+
+    if repo.is_anhang_of_sitzung(id).await? {
+        return Ok(Some(anhang));
+    }
+
+    for antrag_id in repo.antraege_by_anhang(id).await? {
+        let Some(antrag) = repo.antrag_by_id(antrag_id).await? else {
+            continue;
+        };
+
+        if can_view_antrag(repo, betrachter, &antrag).await? {
+            return Ok(Some(anhang));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The PNG preview of an Anhang. It is rendered on first use and then cached in the storage.
+/// `None` if there is no preview for the Anhang
+pub async fn anhang_preview(storage: &impl BlobStore, anhang: &Anhang) -> Result<Option<Vec<u8>>> {
+    if !preview::has_preview(&anhang.content_type)
+        || anhang
+            .size
+            .is_some_and(|size| size > preview::MAX_PREVIEW_SOURCE_SIZE)
+    {
+        return Ok(None);
+    }
+
+    let key = preview::preview_key(anhang.id);
+
+    // an empty preview marks Anhänge that could not be rendered, so they are not tried again
+    if let Some(cached) = storage.get(&key).await? {
+        return Ok(Some(cached).filter(|cached| !cached.is_empty()));
+    }
+
+    // Anhänge uploaded before their size was recorded may turn out to be too large only now
+    let Some(content) = blob_store::read_limited(
+        storage,
+        &anhang.id.to_string(),
+        preview::MAX_PREVIEW_SOURCE_SIZE as u64,
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let content_type = anhang.content_type.clone();
+    let rendered = match content {
+        Some(content) => {
+            async_std::task::spawn_blocking(move || {
+                preview::render_preview(&content, &content_type)
+            })
+            .await
+        }
+        None => None,
+    };
+
+    storage
+        .put(&key, rendered.clone().unwrap_or_default())
+        .await?;
+
+    Ok(rendered)
+}
+
+/// Creates an Anhang and stores the uploaded file as its content. The Anhang stays staged until
+/// the transaction attaching it confirms it, so failed uploads are cleaned up by
+/// `delete_unused_anhaenge`. Only the person who uploaded it can confirm it
+pub async fn stage_anhang(
+    repo: &mut impl AnhangRepo,
+    storage: &impl BlobStore,
+    datei: &Datei,
+    path: &Path,
+    hochgeladen_von: Uuid,
+) -> Result<Anhang> {
+    let anhang = repo
+        .create_anhang(
+            &datei.filename,
+            datei.content_type.essence_str(),
+            datei.size as i64,
+            &datei.sha256,
+            hochgeladen_von,
+        )
+        .await?;
+
+    storage.put_file(&anhang.id.to_string(), path).await?;
+
+    Ok(anhang)
+}
+
+/// Extracts the text of an Anhang for the full-text search and stores it
+async fn store_anhang_text(
+    repo: &mut impl AnhangRepo,
+    anhang: &Anhang,
+    content: Vec<u8>,
+) -> Result<()> {
+    let content_type = anhang.content_type.clone();
+    let text =
+        async_std::task::spawn_blocking(move || search::extract_text(&content, &content_type))
+            .await;
+
+    repo.set_anhang_text(anhang.id, &text).await
+}
+
+/// Extracts the text of all Anhänge it was not extracted from yet, for the full-text search.
+/// Returns how many were processed
+pub async fn extract_missing_anhang_texts(
+    repo: &mut impl AnhangRepo,
+    storage: &impl BlobStore,
+) -> Result<usize> {
+    let missing = repo
+        .anhaenge_without_text(&search::TEXT_CONTENT_TYPES)
+        .await?;
+
+    for anhang in &missing {
+        // an empty text marks Anhänge without one, so they are not tried again
+        let content = if search::has_text(&anhang.content_type, anhang.size) {
+            blob_store::read_limited(
+                storage,
+                &anhang.id.to_string(),
+                search::MAX_TEXT_SOURCE_SIZE as u64,
+            )
+            .await?
+            .flatten()
+        } else {
+            None
+        };
+
+        match content {
+            Some(content) => store_anhang_text(repo, anhang, content).await?,
+            None => repo.set_anhang_text(anhang.id, "").await?,
+        }
+    }
+
+    Ok(missing.len())
+}
+
+/// Searches Anträge, Tops and Anhänge, leaving out everything the betrachter may not see
+pub async fn search(
+    repo: &mut (impl SearchRepo + AntragRepo + AnhangRepo),
+    betrachter: &AntragBetrachter,
+    query: &str,
+) -> Result<SearchResult> {
+    let limit = search::MAX_TREFFER as i64;
+
+    let mut antraege = vec![];
+
+    for fundstelle in repo.search_antraege(betrachter, query, limit).await? {
+        if let Some(antrag) = repo.antrag_by_id(fundstelle.id).await? {
+            antraege.push(AntragTreffer {
+                antrag,
+                rang: fundstelle.rang,
+                auszug: fundstelle.auszug,
+            });
+        }
+    }
+
+    let mut anhaenge = vec![];
+
+    for fundstelle in repo.search_anhaenge(betrachter, query, limit).await? {
+        if let Some(anhang) = repo.anhang_by_id(fundstelle.id).await? {
+            anhaenge.push(AnhangTreffer {
+                anhang,
+                rang: fundstelle.rang,
+                auszug: fundstelle.auszug,
+            });
+        }
+    }
+
+    Ok(SearchResult {
+        antraege,
+        tops: repo.search_tops(query, limit).await?,
+        anhaenge,
+    })
+}
+
+/// Deletes a confirmed Anhang and its content, once it is no longer attached to anything
+pub async fn delete_anhang_if_unused(
+    repo: &mut impl AnhangRepo,
+    storage: &impl BlobStore,
+    id: Uuid,
+) -> Result<bool> {
+    let Some(anhang) = repo.delete_unused_anhang(id, None).await? else {
+        return Ok(false);
+    };
+
+    delete_anhang_content(storage, anhang.id).await?;
+
+    Ok(true)
+}
+
+/// Deletes Anhänge that are not attached to anything anymore and failed uploads, together with
+/// their content
+pub async fn delete_unused_anhaenge(
+    repo: &mut impl AnhangRepo,
+    storage: &impl BlobStore,
+    now: DateTime<Utc>,
+) -> Result<Vec<Anhang>> {
+    let staged_before = now - anhang::STAGING_TIMEOUT;
+    let unused = repo.unused_anhaenge(staged_before).await?;
+
+    let mut deleted = vec![];
+
+    // an Anhang may have been attached since it was found, so only those still unused are deleted
+    for anhang in unused {
+        if let Some(anhang) = repo
+            .delete_unused_anhang(anhang.id, Some(staged_before))
+            .await?
+        {
+            delete_anhang_content(storage, anhang.id).await?;
+            deleted.push(anhang);
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Deletes the content of a deleted Anhang. The Anhang goes first, so content is never missing
+/// for an existing Anhang. Content that is left behind is cleaned up by `reconcile_anhaenge`
+async fn delete_anhang_content(storage: &impl BlobStore, id: Uuid) -> Result<()> {
+    storage.delete(&preview::preview_key(id)).await?;
+    storage.delete(&id.to_string()).await?;
+
+    Ok(())
+}
+
+/// Deletes the blobs of Anhänge and previews that no longer exist and finds Anhänge whose blob is
+/// missing. Other blobs in the storage are left alone
+pub async fn reconcile_anhaenge(
+    repo: &mut impl AnhangRepo,
+    storage: &impl BlobStore,
+    now: DateTime<Utc>,
+) -> Result<AnhangReconciliation> {
+    // the keys are listed first, as a blob is only ever stored after its Anhang was created
+    let keys = storage.keys().await?;
+    let anhaenge = repo.anhaenge().await?;
+
+    let ids = anhaenge
+        .iter()
+        .map(|anhang| anhang.id)
+        .collect::<HashSet<_>>();
+
+    let mut result = AnhangReconciliation::default();
+    let mut stored = HashSet::new();
+
+    for key in keys {
+        let (id, is_preview) = match key.strip_suffix(preview::PREVIEW_KEY_SUFFIX) {
+            Some(id) => (id, true),
+            None => (key.as_str(), false),
+        };
+
+        let Ok(id) = Uuid::parse_str(id) else {
+            continue;
+        };
+
+        if !ids.contains(&id) {
+            storage.delete(&key).await?;
+            result.deleted_blobs.push(key);
+        } else if !is_preview {
+            stored.insert(id);
+        }
+    }
+
+    // the content of recent uploads may still be on its way
+    result.missing_blobs = anhaenge
+        .into_iter()
+        .filter(|anhang| {
+            !stored.contains(&anhang.id) && anhang.erstellt_am < now - anhang::STAGING_TIMEOUT
+        })
+        .collect();
 
     Ok(result)
 }
 
+/// Whether both BlobStores store their blobs in the same place, found out by storing a blob in
+/// one and looking for it in the other
+async fn is_same_blob_store(a: &impl BlobStore, b: &impl BlobStore) -> Result<bool> {
+    let key = format!("marker-{}", Uuid::new_v4());
+
+    a.put(&key, vec![]).await?;
+    let same = b.get(&key).await?.is_some();
+    a.delete(&key).await?;
+
+    Ok(same)
+}
+
+/// Moves the content of all Anhänge from one BlobStore to another. Anhänge missing in the source
+/// are skipped, so an interrupted move can be resumed. Every Anhang is copied through a file in
+/// `spool_dir`. Returns how many were moved
+pub async fn move_anhaenge(
+    repo: &mut impl AnhangRepo,
+    from: &impl BlobStore,
+    to: &impl BlobStore,
+    spool_dir: &Path,
+) -> Result<usize> {
+    // deleting the moved blobs from the source would delete them from the target as well
+    if is_same_blob_store(from, to).await? {
+        return Err(Error::Message(
+            "the attachments can not be moved to the storage they are in".to_string(),
+        ));
+    }
+
+    async_std::fs::create_dir_all(spool_dir).await?;
+
+    let mut moved = 0;
+
+    for anhang in repo.anhaenge().await? {
+        let key = anhang.id.to_string();
+
+        let Some(sha256) = blob_store::copy(from, to, &key, &spool_dir.join(&key)).await? else {
+            continue;
+        };
+
+        // the source is only cleaned up once the target is known to have the whole blob
+        if blob_store::sha256(to, &key).await? != Some(sha256) {
+            return Err(Error::Message(format!(
+                "attachment {key} differs after moving it, it was kept in the source"
+            )));
+        }
+
+        from.delete(&key).await?;
+        // previews are not moved, they are rendered again on demand
+        from.delete(&preview::preview_key(anhang.id)).await?;
+
+        moved += 1;
+    }
+
+    Ok(moved)
+}
+
 pub async fn top_with_antraege(
     repo: &mut impl SitzungAntragService,
+    betrachter: &AntragBetrachter,
     top_id: Uuid,
 ) -> Result<Option<TopWithAntraege>> {
     let Some(top) = repo.top_by_id(top_id).await? else {
@@ -71,8 +705,10 @@ pub async fn top_with_antraege(
     };
 
     let anträge = repo.antraege_by_top(top_id).await?;
+    let anträge = visible_antraege(repo, betrachter, anträge).await?;
 
     Ok(Some(TopWithAntraege {
+        anhaenge: repo.anhaenge_by_top(top_id).await?,
         top,
         antraege: anträge,
     }))
@@ -80,6 +716,7 @@ pub async fn top_with_antraege(
 
 pub async fn top_with_antraege_by_sitzung(
     repo: &mut impl SitzungAntragService,
+    betrachter: &AntragBetrachter,
     sitzung_id: Uuid,
 ) -> Result<Vec<TopWithAntraege>> {
     let tops = repo.tops_by_sitzung(sitzung_id).await?;
@@ -87,7 +724,7 @@ pub async fn top_with_antraege_by_sitzung(
     let mut tops_with_anträge = vec![];
 
     for top in tops {
-        let top_and_anträge = top_with_antraege(repo, top.id).await?.unwrap();
+        let top_and_anträge = top_with_antraege(repo, betrachter, top.id).await?.unwrap();
 
         tops_with_anträge.push(top_and_anträge);
     }
@@ -97,22 +734,162 @@ pub async fn top_with_antraege_by_sitzung(
 
 pub async fn sitzung_with_tops(
     repo: &mut impl SitzungAntragService,
+    betrachter: &AntragBetrachter,
     sitzung_id: Uuid,
 ) -> Result<Option<SitzungWithTops>> {
     let Some(sitzung) = repo.sitzung_by_id(sitzung_id).await? else {
         return Ok(None);
     };
 
-    let tops_with_antraege = top_with_antraege_by_sitzung(repo, sitzung_id).await?;
+    let tops_with_antraege = top_with_antraege_by_sitzung(repo, betrachter, sitzung_id).await?;
 
     Ok(Some(SitzungWithTops {
         sitzung,
         tops: tops_with_antraege,
+        anhaenge: repo.anhaenge_by_sitzung(sitzung_id).await?,
+    }))
+}
+
+/// Lists the files of the ZIP bundle of a Sitzung: the Einladung, the Anhänge of the Sitzung and
+/// for every Top in order its Anhänge and its Anträge together with their Anhänge.
+///
+/// The Sitzung has to come from `sitzung_with_tops` for the same betrachter, so it only contains
+/// the Anträge they may see.
+pub async fn sitzung_bundle(
+    repo: &mut impl AnhangRepo,
+    sitzung: &SitzungWithTops,
+    einladung: Option<String>,
+) -> Result<Vec<BundleDatei>> {
+    let mut pfade = BundlePfade::default();
+    let mut dateien = vec![];
+
+    if let Some(einladung) = einladung {
+        dateien.push(BundleDatei::Text {
+            pfad: pfade.pfad(None, "Einladung.md"),
+            inhalt: einladung,
+        });
+    }
+
+    for anhang in &sitzung.anhaenge {
+        dateien.push(BundleDatei::Anhang {
+            pfad: pfade.pfad(None, &anhang.filename),
+            anhang: anhang.clone(),
+        });
+    }
+
+    for (index, top) in sitzung.tops.iter().enumerate() {
+        let verzeichnis = format!(
+            "{:02} {}",
+            index + 1,
+            bundle::sanitize_dateiname(&top.top.name)
+        );
+
+        for anhang in &top.anhaenge {
+            dateien.push(BundleDatei::Anhang {
+                pfad: pfade.pfad(Some(&verzeichnis), &anhang.filename),
+                anhang: anhang.clone(),
+            });
+        }
+
+        for antrag in &top.antraege {
+            dateien.push(BundleDatei::Text {
+                pfad: pfade.pfad(Some(&verzeichnis), &format!("{}.md", antrag.data.titel)),
+                inhalt: bundle::antrag_markdown(antrag),
+            });
+
+            for anhang in repo.anhaenge_by_antrag(antrag.data.id).await? {
+                dateien.push(BundleDatei::Anhang {
+                    pfad: pfade.pfad(Some(&verzeichnis), &anhang.filename),
+                    anhang,
+                });
+            }
+        }
+    }
+
+    Ok(dateien)
+}
+
+/// Moves a Top, together with its Anträge, to the end of another Sitzung and records the
+/// Vertagung. If `copy` is set, the Top is duplicated instead and the original is left untouched.
+/// `None` if the Top does not belong to the Sitzung it is vertagt from.
+pub async fn vertage_top(
+    repo: &mut impl SitzungAntragService,
+    top_id: Uuid,
+    von_sitzung_id: Uuid,
+    nach_sitzung_id: Uuid,
+    copy: bool,
+) -> Result<Option<TopWithAntraege>> {
+    let Some(top) = repo
+        .tops_by_sitzung(von_sitzung_id)
+        .await?
+        .into_iter()
+        .find(|top| top.id == top_id)
+    else {
+        return Ok(None);
+    };
+
+    if repo.sitzung_by_id(nach_sitzung_id).await?.is_none() {
+        return Ok(None);
+    }
+
+    let antraege = repo.antraege_by_top(top_id).await?;
+
+    let top = if copy {
+        let new_top = repo
+            .create_top(nach_sitzung_id, &top.name, &top.inhalt, top.typ)
+            .await?;
+
+        for antrag in &antraege {
+            repo.attach_antrag_to_top(antrag.data.id, new_top.id)
+                .await?;
+        }
+
+        for anhang in repo.anhaenge_by_top(top_id).await? {
+            repo.add_anhang_to_top(new_top.id, anhang.id).await?;
+        }
+
+        new_top
+    } else {
+        let weight = repo
+            .tops_by_sitzung(nach_sitzung_id)
+            .await?
+            .iter()
+            .filter(|t| t.typ == top.typ)
+            .map(|t| t.weight)
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        let Some(moved_top) = repo
+            .update_top(
+                top_id,
+                Some(nach_sitzung_id),
+                None,
+                None,
+                None,
+                Some(weight),
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        moved_top
+    };
+
+    repo.create_vertagung(top.id, von_sitzung_id, nach_sitzung_id)
+        .await?;
+
+    Ok(Some(TopWithAntraege {
+        anhaenge: repo.anhaenge_by_top(top.id).await?,
+        top,
+        antraege,
     }))
 }
 
 pub async fn sitzungen_after_with_tops(
     repo: &mut impl SitzungAntragService,
+    betrachter: &AntragBetrachter,
     timestamp: DateTime<Utc>,
     limit: Option<i64>,
 ) -> Result<Option<Vec<SitzungWithTops>>> {
@@ -123,28 +900,1394 @@ pub async fn sitzungen_after_with_tops(
         let tops = repo.tops_by_sitzung(sitzung.id).await?;
         let mut tops_with_antraege = vec![];
         for top in tops {
-            let top_and_antraege = top_with_antraege(repo, top.id).await?.unwrap();
+            let top_and_antraege = top_with_antraege(repo, betrachter, top.id).await?.unwrap();
 
             tops_with_antraege.push(top_and_antraege);
         }
         sitzungen_with_tops.push(SitzungWithTops {
             sitzung: sitzung.clone(),
             tops: tops_with_antraege,
+            anhaenge: repo.anhaenge_by_sitzung(sitzung.id).await?,
         });
     }
 
     Ok(Some(sitzungen_with_tops))
 }
 
-pub async fn abmeldungen_by_sitzung(
-    repo: &mut impl SitzungPersonService,
-    sitzung_id: Uuid,
-) -> Result<Option<Vec<Abmeldung>>> {
-    let Some(sitzung) = repo.sitzung_by_id(sitzung_id).await? else {
+/// Puts all orphaned Anträge, that were submitted before the Antragsfrist of the next Sitzung
+/// after `now`, onto the agenda of that Sitzung. Either creates one Top per Antrag, or a single
+/// Top containing all of them if `group` is set. If `dry_run` is set, nothing is written and only
+/// the planned Tops are returned.
+pub async fn schedule_orphan_antraege(
+    repo: &mut impl SitzungAntragService,
+    now: DateTime<Utc>,
+    group: bool,
+    dry_run: bool,
+) -> Result<Option<OrphanPlan>> {
+    let Some(sitzung) = repo.next_scheduled_sitzung(now).await? else {
         return Ok(None);
     };
 
-    let abmeldungen = repo.abmeldungen_at(sitzung.datetime.date_naive()).await?;
+    let mut antraege: Vec<Antrag> = repo
+        .orphan_antraege()
+        .await?
+        .into_iter()
+        .filter(|a| a.data.erstellt_am <= sitzung.antragsfrist)
+        // Entwürfe are not finished yet
+        .filter(|a| a.data.sichtbarkeit != Sichtbarkeit::Entwurf)
+        .collect();
 
-    Ok(Some(abmeldungen))
+    antraege.sort_by_key(|a| a.data.erstellt_am);
+
+    let mut tops = if group && !antraege.is_empty() {
+        vec![PlannedTop {
+            top_id: None,
+            name: "Anträge".to_string(),
+            typ: TopTyp::Normal,
+            antraege: antraege.iter().map(|a| a.data.id).collect(),
+        }]
+    } else {
+        antraege
+            .into_iter()
+            .map(|a| PlannedTop {
+                top_id: None,
+                name: a.data.titel,
+                typ: TopTyp::Normal,
+                antraege: vec![a.data.id],
+            })
+            .collect()
+    };
+
+    if !dry_run {
+        for planned in &mut tops {
+            let top = repo
+                .create_top(sitzung.id, &planned.name, "", planned.typ)
+                .await?;
+
+            for antrag_id in &planned.antraege {
+                repo.attach_antrag_to_top(*antrag_id, top.id).await?;
+            }
+
+            planned.top_id = Some(top.id);
+        }
+    }
+
+    Ok(Some(OrphanPlan { sitzung, tops }))
+}
+
+/// Returns the Legislaturperiode whose date range covers the day of `datetime`
+pub async fn legislatur_periode_at(
+    repo: &mut impl LegislaturPeriodeRepo,
+    datetime: DateTime<Utc>,
+) -> Result<Option<LegislaturPeriode>> {
+    let date = datetime
+        .with_timezone(&chrono_tz::Europe::Berlin)
+        .date_naive();
+
+    repo.legislatur_periode_by_date(date).await
+}
+
+/// Creates a new Sitzung for every date of the SerienRegel. The Antragsfrist of each Sitzung is
+/// set to `antragsfrist_vorlauf` before it.
+///
+/// Without an explicit `legislatur_periode` every Sitzung is assigned to the Legislaturperiode
+/// covering its date. Returns `None` if one of the dates is not covered by any.
+pub async fn create_sitzung_serie(
+    repo: &mut impl SitzungLegislaturService,
+    name: &str,
+    regel: &SerienRegel,
+    antragsfrist_vorlauf: TimeDelta,
+    ort: &str,
+    typ: SitzungTyp,
+    legislatur_periode: Option<Uuid>,
+) -> Result<Option<SitzungSerieWithSitzungen>> {
+    let mut termine = vec![];
+
+    for datetime in regel.termine() {
+        let periode = match legislatur_periode {
+            Some(periode) => periode,
+            None => match legislatur_periode_at(repo, datetime).await? {
+                Some(periode) => periode.id,
+                None => return Ok(None),
+            },
+        };
+
+        termine.push((datetime, periode));
+    }
+
+    let serie = repo.create_sitzung_serie(name).await?;
+
+    let mut sitzungen = vec![];
+
+    for (datetime, periode) in termine {
+        let sitzung = repo
+            .create_sitzung(
+                datetime,
+                ort,
+                typ,
+                datetime - antragsfrist_vorlauf,
+                periode,
+                Some(serie.id),
+            )
+            .await?;
+
+        sitzungen.push(sitzung);
+    }
+
+    Ok(Some(SitzungSerieWithSitzungen { serie, sitzungen }))
+}
+
+/// Updates all Sitzungen of a Serie that take place after `after`
+pub async fn update_sitzung_serie(
+    repo: &mut impl SitzungRepo,
+    serie_id: Uuid,
+    after: DateTime<Utc>,
+    ort: Option<&str>,
+    typ: Option<SitzungTyp>,
+    antragsfrist_vorlauf: Option<TimeDelta>,
+    legislatur_periode: Option<Uuid>,
+) -> Result<Option<Vec<Sitzung>>> {
+    if repo.sitzung_serie_by_id(serie_id).await?.is_none() {
+        return Ok(None);
+    }
+
+    let mut result = vec![];
+
+    for sitzung in repo.sitzungen_by_serie(serie_id).await? {
+        if sitzung.datetime < after {
+            continue;
+        }
+
+        let updated = repo
+            .update_sitzung(
+                sitzung.id,
+                None,
+                ort,
+                typ,
+                antragsfrist_vorlauf.map(|vorlauf| sitzung.datetime - vorlauf),
+                legislatur_periode,
+            )
+            .await?;
+
+        result.extend(updated);
+    }
+
+    Ok(Some(result))
+}
+
+/// Cancels all Sitzungen of a Serie that take place after `after`. They are kept, so the
+/// cancellation and its grund show up in the calendar. Sitzungen that were cancelled already keep
+/// their grund
+pub async fn cancel_sitzung_serie(
+    repo: &mut impl SitzungRepo,
+    serie_id: Uuid,
+    after: DateTime<Utc>,
+    grund: Option<&str>,
+) -> Result<Option<Vec<Sitzung>>> {
+    if repo.sitzung_serie_by_id(serie_id).await?.is_none() {
+        return Ok(None);
+    }
+
+    let mut result = vec![];
+
+    for sitzung in repo.sitzungen_by_serie(serie_id).await? {
+        if sitzung.datetime < after || sitzung.status == SitzungStatus::Abgesagt {
+            continue;
+        }
+
+        result.extend(
+            repo.set_sitzung_status(sitzung.id, SitzungStatus::Abgesagt, grund)
+                .await?,
+        );
+    }
+
+    Ok(Some(result))
+}
+
+/// Moves a Sitzung to a new date and records the original one. The Antragsfrist is moved by the
+/// same amount, unless a new one is given.
+pub async fn verschiebe_sitzung(
+    repo: &mut impl SitzungRepo,
+    sitzung_id: Uuid,
+    datetime: DateTime<Utc>,
+    antragsfrist: Option<DateTime<Utc>>,
+    grund: Option<&str>,
+) -> Result<Option<Sitzung>> {
+    let Some(sitzung) = repo.sitzung_by_id(sitzung_id).await? else {
+        return Ok(None);
+    };
+
+    let antragsfrist = antragsfrist.unwrap_or(sitzung.antragsfrist + (datetime - sitzung.datetime));
+
+    repo.create_verschiebung(sitzung_id, sitzung.datetime, datetime, grund)
+        .await?;
+
+    repo.update_sitzung(
+        sitzung_id,
+        Some(datetime),
+        None,
+        None,
+        Some(antragsfrist),
+        None,
+    )
+    .await?;
+
+    repo.set_sitzung_status(sitzung_id, SitzungStatus::Verschoben, grund)
+        .await
+}
+
+/// The local day a Sitzung takes place on, which Abmeldungen for it refer to
+fn sitzungstag(sitzung: &Sitzung) -> NaiveDate {
+    sitzung
+        .datetime
+        .with_timezone(&chrono_tz::Europe::Berlin)
+        .date_naive()
+}
+
+pub async fn abmeldungen_by_sitzung(
+    repo: &mut impl SitzungPersonService,
+    sitzung_id: Uuid,
+) -> Result<Option<Vec<Abmeldung>>> {
+    let Some(sitzung) = repo.sitzung_by_id(sitzung_id).await? else {
+        return Ok(None);
+    };
+
+    let abmeldungen = repo.abmeldungen_at(sitzungstag(&sitzung)).await?;
+
+    Ok(Some(abmeldungen))
+}
+
+/// Creates an Abmeldung of the person for the day of the Sitzung
+pub async fn abmelden_for_sitzung(
+    repo: &mut impl SitzungPersonService,
+    person_id: Uuid,
+    sitzung_id: Uuid,
+    grund: Option<&str>,
+    privat: bool,
+) -> Result<Option<Abmeldung>> {
+    let Some(sitzung) = repo.sitzung_by_id(sitzung_id).await? else {
+        return Ok(None);
+    };
+
+    let datum = sitzungstag(&sitzung);
+
+    let abmeldung = repo
+        .create_abmeldung(person_id, datum, datum, grund, privat)
+        .await?;
+
+    Ok(Some(abmeldung))
+}
+
+/// Revokes the Abmeldung of the person for the day of the Sitzung
+pub async fn revoke_abmeldung_for_sitzung(
+    repo: &mut impl SitzungPersonService,
+    person_id: Uuid,
+    sitzung_id: Uuid,
+) -> Result<Option<()>> {
+    let Some(sitzung) = repo.sitzung_by_id(sitzung_id).await? else {
+        return Ok(None);
+    };
+
+    let datum = sitzungstag(&sitzung);
+
+    repo.revoke_abmeldung_from_person(person_id, datum, datum)
+        .await?;
+
+    Ok(Some(()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use async_std::path::Path;
+    use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::domain::anhang::{inspect_datei, Anhang, AnhangRepo};
+    use crate::domain::antrag::{
+        AntragBetrachter, AntragFilter, AntragRepo, AntragsRolle, Sichtbarkeit,
+    };
+    use crate::domain::antrag_top_attachment_map::AntragTopAttachmentMap;
+    use crate::domain::blob_store::BlobStore;
+    use crate::domain::legislatur_periode::LegislaturPeriodeRepo;
+    use crate::domain::pagination::Pagination;
+    use crate::domain::persons::PersonRepo;
+    use crate::domain::preview::preview_key;
+    use crate::domain::sitzung::{SerienRegel, SitzungRepo, SitzungStatus, SitzungTyp};
+    use crate::domain::Error;
+    use crate::storage::memory::MemoryStore;
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts(
+            "gimme_persons",
+            "gimme_sitzungen",
+            "gimme_tops",
+            "gimme_antraege",
+            "gimme_antrag_mappings"
+        )
+    ))]
+    async fn schedule_orphan_antraege(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let now = DateTime::parse_from_rfc3339("2024-09-01T00:00:00+02:00").unwrap();
+
+        let sitzung_id = Uuid::parse_str("dfe75b8c-8c24-4a2b-84e5-d0573a8e6f00").unwrap();
+        let antrag_id = Uuid::parse_str("5c51d5c0-3943-4695-844d-4c47da854fac").unwrap();
+
+        let plan = super::schedule_orphan_antraege(&mut *conn, now.into(), false, false)
+            .await?
+            .unwrap();
+
+        assert_eq!(plan.sitzung.id, sitzung_id);
+        assert_eq!(plan.tops.len(), 1);
+        assert_eq!(plan.tops[0].antraege, vec![antrag_id]);
+
+        let top_id = plan.tops[0].top_id.unwrap();
+        let anträge = conn.antraege_by_top(top_id).await?;
+
+        assert_eq!(anträge.len(), 1);
+        assert_eq!(anträge[0].data.id, antrag_id);
+        assert!(conn.orphan_antraege().await?.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts(
+            "gimme_persons",
+            "gimme_sitzungen",
+            "gimme_tops",
+            "gimme_antraege",
+            "gimme_antrag_mappings"
+        )
+    ))]
+    async fn schedule_orphan_antraege_skips_abgesagte_sitzung(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let now = DateTime::parse_from_rfc3339("2024-09-01T00:00:00+02:00").unwrap();
+
+        let abgesagt_id = Uuid::parse_str("dfe75b8c-8c24-4a2b-84e5-d0573a8e6f00").unwrap();
+        let naechste_id = Uuid::parse_str("177b861d-0447-45ce-bc56-9eb68991cbda").unwrap();
+
+        conn.set_sitzung_status(abgesagt_id, SitzungStatus::Abgesagt, Some("krank"))
+            .await?
+            .unwrap();
+
+        let plan = super::schedule_orphan_antraege(&mut *conn, now.into(), false, true)
+            .await?
+            .unwrap();
+
+        assert_eq!(plan.sitzung.id, naechste_id);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts(
+            "gimme_persons",
+            "gimme_sitzungen",
+            "gimme_tops",
+            "gimme_antraege",
+            "gimme_antrag_mappings"
+        )
+    ))]
+    async fn schedule_orphan_antraege_dry_run(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let now = DateTime::parse_from_rfc3339("2024-09-01T00:00:00+02:00").unwrap();
+
+        let plan = super::schedule_orphan_antraege(&mut *conn, now.into(), true, true)
+            .await?
+            .unwrap();
+
+        assert_eq!(plan.tops.len(), 1);
+        assert!(plan.tops[0].top_id.is_none());
+        assert_eq!(conn.orphan_antraege().await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts(
+            "gimme_persons",
+            "gimme_sitzungen",
+            "gimme_tops",
+            "gimme_antraege",
+            "gimme_antrag_mappings"
+        )
+    ))]
+    async fn vertage_top(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let top_id = Uuid::parse_str("fd6b67df-60f2-453a-9ffc-93514c5ccdb1").unwrap();
+        let von_sitzung_id = Uuid::parse_str("dfe75b8c-8c24-4a2b-84e5-d0573a8e6f00").unwrap();
+        let nach_sitzung_id = Uuid::parse_str("177b861d-0447-45ce-bc56-9eb68991cbda").unwrap();
+
+        let moved = super::vertage_top(&mut *conn, top_id, von_sitzung_id, nach_sitzung_id, false)
+            .await?
+            .unwrap();
+
+        assert_eq!(moved.top.id, top_id);
+        assert_eq!(moved.top.weight, 5);
+        assert_eq!(moved.antraege.len(), 1);
+
+        let tops = conn.tops_by_sitzung(nach_sitzung_id).await?;
+
+        assert!(tops.iter().any(|t| t.id == top_id));
+        assert_eq!(conn.antraege_by_top(top_id).await?.len(), 1);
+        assert_eq!(
+            conn.vertagungen_by_top(top_id, nach_sitzung_id)
+                .await?
+                .len(),
+            1
+        );
+
+        // the Top does not belong to the Sitzung it would be moved from anymore
+        assert!(
+            super::vertage_top(&mut *conn, top_id, von_sitzung_id, nach_sitzung_id, false)
+                .await?
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts(
+            "gimme_persons",
+            "gimme_sitzungen",
+            "gimme_tops",
+            "gimme_antraege",
+            "gimme_antrag_mappings"
+        )
+    ))]
+    async fn vertage_top_copy(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let top_id = Uuid::parse_str("fd6b67df-60f2-453a-9ffc-93514c5ccdb1").unwrap();
+        let von_sitzung_id = Uuid::parse_str("dfe75b8c-8c24-4a2b-84e5-d0573a8e6f00").unwrap();
+        let nach_sitzung_id = Uuid::parse_str("177b861d-0447-45ce-bc56-9eb68991cbda").unwrap();
+
+        let copied = super::vertage_top(&mut *conn, top_id, von_sitzung_id, nach_sitzung_id, true)
+            .await?
+            .unwrap();
+
+        assert_ne!(copied.top.id, top_id);
+        assert_eq!(conn.antraege_by_top(copied.top.id).await?.len(), 1);
+        assert_eq!(conn.antraege_by_top(top_id).await?.len(), 1);
+        assert!(conn
+            .tops_by_sitzung(von_sitzung_id)
+            .await?
+            .iter()
+            .any(|t| t.id == top_id));
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(path = "../database/fixtures", scripts("gimme_legislative_period")))]
+    async fn sitzung_serie(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let legislatur_periode_id =
+            Uuid::parse_str("f2b2b2b2-2b2b-2b2b-2b2b-2b2b2b2b2b2b").unwrap();
+
+        let regel = SerienRegel {
+            start: NaiveDate::from_ymd_opt(2024, 10, 1).unwrap(),
+            ende: NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(),
+            wochentag: Weekday::Wed,
+            uhrzeit: NaiveTime::from_hms_opt(18, 30, 0).unwrap(),
+            zeitzone: chrono_tz::Europe::Berlin,
+            ausnahmen: vec![],
+        };
+
+        let serie = super::create_sitzung_serie(
+            &mut *conn,
+            "WiSe 24/25",
+            &regel,
+            TimeDelta::days(3),
+            "ein uni raum",
+            SitzungTyp::Normal,
+            Some(legislatur_periode_id),
+        )
+        .await?
+        .unwrap();
+
+        assert_eq!(serie.sitzungen.len(), 5);
+        assert!(serie
+            .sitzungen
+            .iter()
+            .all(|s| s.antragsfrist == s.datetime - TimeDelta::days(3)
+                && s.serie_id == Some(serie.serie.id)));
+
+        let after = DateTime::parse_from_rfc3339("2024-10-20T00:00:00+02:00").unwrap();
+
+        let updated = super::update_sitzung_serie(
+            &mut *conn,
+            serie.serie.id,
+            after.into(),
+            Some("ein anderer raum"),
+            None,
+            None,
+            None,
+        )
+        .await?
+        .unwrap();
+
+        assert_eq!(updated.len(), 2);
+        assert!(updated.iter().all(|s| s.ort == "ein anderer raum"));
+
+        let cancelled = super::cancel_sitzung_serie(
+            &mut *conn,
+            serie.serie.id,
+            after.into(),
+            Some("semesterferien"),
+        )
+        .await?
+        .unwrap();
+
+        assert_eq!(cancelled.len(), 2);
+        assert!(cancelled.iter().all(|s| s.status == SitzungStatus::Abgesagt
+            && s.status_grund.as_deref() == Some("semesterferien")));
+
+        // the cancelled Sitzungen are kept
+        let sitzungen = conn.sitzungen_by_serie(serie.serie.id).await?;
+
+        assert_eq!(sitzungen.len(), 5);
+        assert_eq!(
+            sitzungen
+                .iter()
+                .filter(|s| s.status == SitzungStatus::Geplant && s.ort == "ein uni raum")
+                .count(),
+            3
+        );
+
+        // cancelling again changes nothing
+        let again = super::cancel_sitzung_serie(&mut *conn, serie.serie.id, after.into(), None)
+            .await?
+            .unwrap();
+
+        assert!(again.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(path = "../database/fixtures", scripts("gimme_sitzungen")))]
+    async fn verschiebe_sitzung(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let sitzung_id = Uuid::parse_str("76f4a8a9-8944-4d89-b6b8-8cdbc1acedb2").unwrap();
+        let alte_datetime = DateTime::parse_from_rfc3339("2024-09-24T12:30:00+02:00").unwrap();
+        let neue_datetime = DateTime::parse_from_rfc3339("2024-09-26T12:30:00+02:00").unwrap();
+
+        let before = conn.sitzung_by_id(sitzung_id).await?.unwrap();
+
+        let sitzung = super::verschiebe_sitzung(
+            &mut *conn,
+            sitzung_id,
+            neue_datetime.into(),
+            None,
+            Some("raum belegt"),
+        )
+        .await?
+        .unwrap();
+
+        assert_eq!(sitzung.datetime, neue_datetime);
+        assert_eq!(
+            sitzung.antragsfrist,
+            before.antragsfrist + TimeDelta::days(2)
+        );
+        assert_eq!(sitzung.status, SitzungStatus::Verschoben);
+        assert_eq!(sitzung.status_grund.as_deref(), Some("raum belegt"));
+
+        let verschiebungen = conn.verschiebungen_by_sitzung(sitzung_id).await?;
+
+        assert_eq!(verschiebungen.len(), 1);
+        assert_eq!(verschiebungen[0].alte_datetime, alte_datetime);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn sitzung_serie_legislatur_periode(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let alt = conn
+            .create_legislatur_periode(
+                "FSR 23/24".to_string(),
+                NaiveDate::from_ymd_opt(2023, 10, 1),
+                NaiveDate::from_ymd_opt(2024, 10, 15),
+            )
+            .await?;
+        let neu = conn
+            .create_legislatur_periode(
+                "FSR 24/25".to_string(),
+                NaiveDate::from_ymd_opt(2024, 10, 16),
+                NaiveDate::from_ymd_opt(2025, 9, 30),
+            )
+            .await?;
+
+        let mut regel = SerienRegel {
+            start: NaiveDate::from_ymd_opt(2024, 10, 1).unwrap(),
+            ende: NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(),
+            wochentag: Weekday::Wed,
+            uhrzeit: NaiveTime::from_hms_opt(18, 30, 0).unwrap(),
+            zeitzone: chrono_tz::Europe::Berlin,
+            ausnahmen: vec![],
+        };
+
+        let serie = super::create_sitzung_serie(
+            &mut *conn,
+            "WiSe 24/25",
+            &regel,
+            TimeDelta::days(3),
+            "ein uni raum",
+            SitzungTyp::Normal,
+            None,
+        )
+        .await?
+        .unwrap();
+
+        let perioden = serie
+            .sitzungen
+            .iter()
+            .map(|s| s.legislatur_periode.id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(perioden, vec![alt.id, alt.id, neu.id, neu.id, neu.id]);
+
+        regel.ende = NaiveDate::from_ymd_opt(2025, 10, 31).unwrap();
+
+        let uncovered = super::create_sitzung_serie(
+            &mut *conn,
+            "zu lang",
+            &regel,
+            TimeDelta::days(3),
+            "ein uni raum",
+            SitzungTyp::Normal,
+            None,
+        )
+        .await?;
+
+        assert!(uncovered.is_none());
+        assert_eq!(conn.sitzung_serien().await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts("gimme_persons", "gimme_antraege")
+    ))]
+    async fn antrag_berechtigungen(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let person_id = Uuid::parse_str("78be7f57-8340-43e0-bba2-074da360ddf4").unwrap();
+
+        let antrag = conn.antrag_by_id(antrag_id).await?.unwrap();
+        let person = conn.person_by_id(person_id).await?.unwrap();
+
+        assert!(!super::can_person_view_antrag(&mut *conn, &person, &antrag).await?);
+
+        conn.set_berechtigung(antrag_id, person_id, AntragsRolle::Reviewer)
+            .await?;
+
+        assert!(super::can_person_view_antrag(&mut *conn, &person, &antrag).await?);
+        assert!(super::can_person_comment_antrag(&mut *conn, &person, &antrag).await?);
+        assert!(!super::can_person_modify_antrag(&mut *conn, &person, &antrag).await?);
+
+        conn.set_berechtigung(antrag_id, person_id, AntragsRolle::Editor)
+            .await?;
+
+        assert!(super::can_person_modify_antrag(&mut *conn, &person, &antrag).await?);
+        assert!(!super::can_person_manage_antrag_berechtigungen(
+            &person, &antrag
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts("gimme_persons", "gimme_antraege")
+    ))]
+    async fn antrag_permission_errors(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let person_id = Uuid::parse_str("78be7f57-8340-43e0-bba2-074da360ddf4").unwrap();
+
+        let antrag = conn.antrag_by_id(antrag_id).await?.unwrap();
+
+        let anonym = AntragBetrachter::default();
+        let eingeladen = AntragBetrachter {
+            person: conn.person_by_id(person_id).await?,
+            ..Default::default()
+        };
+        let alle = AntragBetrachter {
+            alle: true,
+            ..Default::default()
+        };
+
+        conn.set_berechtigung(antrag_id, person_id, AntragsRolle::Reviewer)
+            .await?;
+
+        assert!(matches!(
+            super::ensure_can_view_antrag_details(&mut *conn, &anonym, &antrag).await,
+            Err(Error::Unauthorized(_))
+        ));
+        super::ensure_can_view_antrag_details(&mut *conn, &eingeladen, &antrag).await?;
+        assert!(matches!(
+            super::ensure_can_modify_antrag(&mut *conn, &eingeladen, &antrag).await,
+            Err(Error::Unauthorized(_))
+        ));
+        super::ensure_can_modify_antrag(&mut *conn, &alle, &antrag).await?;
+
+        let kommentator =
+            super::ensure_can_comment_antrag(&mut *conn, &eingeladen, &antrag).await?;
+        assert_eq!(kommentator.id, person_id);
+
+        // those managing Anträge still need a person to write Kommentare as
+        assert!(matches!(
+            super::ensure_can_comment_antrag(&mut *conn, &alle, &antrag).await,
+            Err(Error::Unauthorized(_))
+        ));
+
+        assert!(matches!(
+            super::ensure_can_manage_antrag_berechtigung(&eingeladen, &antrag, person_id, false),
+            Err(Error::Unauthorized(_))
+        ));
+        super::ensure_can_manage_antrag_berechtigung(&eingeladen, &antrag, person_id, true)?;
+
+        conn.set_berechtigung(antrag_id, person_id, AntragsRolle::Editor)
+            .await?;
+
+        super::ensure_can_modify_antrag(&mut *conn, &eingeladen, &antrag).await?;
+        assert!(matches!(
+            super::ensure_can_change_antrag_sichtbarkeit(&eingeladen, &antrag),
+            Err(Error::Unauthorized(_))
+        ));
+        super::ensure_can_change_antrag_sichtbarkeit(&alle, &antrag)?;
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts("gimme_persons", "gimme_antraege")
+    ))]
+    async fn antrag_sichtbarkeit(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let ersteller_id = Uuid::parse_str("5a5a134d-9345-4c36-a466-1c3bb806b240").unwrap();
+        let person_id = Uuid::parse_str("78be7f57-8340-43e0-bba2-074da360ddf4").unwrap();
+
+        conn.set_antrag_sichtbarkeit(antrag_id, Sichtbarkeit::Intern)
+            .await?;
+        let intern_antrag = conn.antrag_by_id(antrag_id).await?.unwrap();
+
+        let anonym = AntragBetrachter::default();
+        let intern = AntragBetrachter {
+            intern: true,
+            ..Default::default()
+        };
+
+        assert!(!super::can_view_antrag(&mut *conn, &anonym, &intern_antrag).await?);
+        assert!(super::can_view_antrag(&mut *conn, &intern, &intern_antrag).await?);
+
+        conn.set_antrag_sichtbarkeit(antrag_id, Sichtbarkeit::Entwurf)
+            .await?;
+        let entwurf = conn.antrag_by_id(antrag_id).await?.unwrap();
+
+        let ersteller = AntragBetrachter {
+            person: conn.person_by_id(ersteller_id).await?,
+            ..Default::default()
+        };
+        let eingeladen = AntragBetrachter {
+            person: conn.person_by_id(person_id).await?,
+            ..Default::default()
+        };
+
+        assert!(!super::can_view_antrag(&mut *conn, &intern, &entwurf).await?);
+        assert!(super::can_view_antrag(&mut *conn, &ersteller, &entwurf).await?);
+        assert!(!super::can_view_antrag(&mut *conn, &eingeladen, &entwurf).await?);
+
+        conn.set_berechtigung(antrag_id, person_id, AntragsRolle::Viewer)
+            .await?;
+
+        assert!(super::can_view_antrag(&mut *conn, &eingeladen, &entwurf).await?);
+
+        let alle = AntragBetrachter {
+            alle: true,
+            ..Default::default()
+        };
+
+        // listing Anträge checks the sichtbarkeit in the database, it has to agree with
+        // can_view_antrag
+        for betrachter in [&anonym, &intern, &ersteller, &eingeladen, &alle] {
+            let antraege = conn
+                .antraege(&alle, &AntragFilter::default(), Pagination::alle())
+                .await?
+                .items;
+            let visible = super::visible_antraege(&mut *conn, betrachter, antraege).await?;
+
+            let listed = conn
+                .antraege(betrachter, &AntragFilter::default(), Pagination::alle())
+                .await?;
+
+            assert_eq!(listed.items, visible);
+            assert_eq!(listed.total, visible.len() as i64);
+        }
+
+        assert_eq!(
+            conn.antraege(&anonym, &AntragFilter::default(), Pagination::alle())
+                .await?
+                .total,
+            1
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts("gimme_persons", "gimme_sitzungen", "gimme_abmeldungen")
+    ))]
+    async fn abmelden_for_sitzung(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let person_id = Uuid::parse_str("78be7f57-8340-43e0-bba2-074da360ddf4").unwrap();
+        let sitzung_id = Uuid::parse_str("177b861d-0447-45ce-bc56-9eb68991cbda").unwrap();
+
+        let abmeldung =
+            super::abmelden_for_sitzung(&mut *conn, person_id, sitzung_id, Some("Krank"), true)
+                .await?
+                .unwrap();
+
+        let datum = NaiveDate::from_ymd_opt(2024, 9, 17).unwrap();
+        assert_eq!(abmeldung.anfangsdatum, datum);
+        assert_eq!(abmeldung.ablaufdatum, datum);
+
+        let abgemeldet = super::abmeldungen_by_sitzung(&mut *conn, sitzung_id)
+            .await?
+            .unwrap();
+        assert!(abgemeldet.iter().any(|a| a.person_id == person_id));
+
+        super::revoke_abmeldung_for_sitzung(&mut *conn, person_id, sitzung_id)
+            .await?
+            .unwrap();
+
+        let zurueckgemeldet = super::abmeldungen_by_sitzung(&mut *conn, sitzung_id)
+            .await?
+            .unwrap();
+        assert!(!zurueckgemeldet.iter().any(|a| a.person_id == person_id));
+
+        // shortly after midnight the Sitzung is on the next day already, though not in UTC
+        let vorige = conn.sitzung_by_id(sitzung_id).await?.unwrap();
+        let nach_mitternacht = conn
+            .create_sitzung(
+                DateTime::parse_from_rfc3339("2024-09-25T00:30:00+02:00")
+                    .unwrap()
+                    .into(),
+                "Hörsaal",
+                SitzungTyp::Normal,
+                DateTime::parse_from_rfc3339("2024-09-22T00:00:00+02:00")
+                    .unwrap()
+                    .into(),
+                vorige.legislatur_periode.id,
+                None,
+            )
+            .await?;
+
+        let spaet =
+            super::abmelden_for_sitzung(&mut *conn, person_id, nach_mitternacht.id, None, true)
+                .await?
+                .unwrap();
+        assert_eq!(
+            spaet.anfangsdatum,
+            NaiveDate::from_ymd_opt(2024, 9, 25).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts(
+            "gimme_persons",
+            "gimme_antraege",
+            "gimme_attachments",
+            "gimme_attachment_mappings"
+        )
+    ))]
+    async fn anhang_by_antrag(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let anhang_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let ersteller_id = Uuid::parse_str("5a5a134d-9345-4c36-a466-1c3bb806b240").unwrap();
+
+        let anonym = AntragBetrachter::default();
+
+        assert!(
+            super::anhang_by_antrag(&mut *conn, &anonym, antrag_id, anhang_id)
+                .await?
+                .is_some()
+        );
+
+        // the Anhang is not attached to a random Antrag
+        let fremder_antrag = conn
+            .create_antrag(
+                &[],
+                "Titel",
+                "Begründung",
+                "Antragstext",
+                Utc::now(),
+                Sichtbarkeit::Oeffentlich,
+            )
+            .await?;
+        assert!(
+            super::anhang_by_antrag(&mut *conn, &anonym, fremder_antrag.data.id, anhang_id)
+                .await?
+                .is_none()
+        );
+
+        conn.set_antrag_sichtbarkeit(antrag_id, Sichtbarkeit::Entwurf)
+            .await?;
+
+        let ersteller = AntragBetrachter {
+            person: conn.person_by_id(ersteller_id).await?,
+            ..Default::default()
+        };
+
+        assert!(
+            super::anhang_by_antrag(&mut *conn, &anonym, antrag_id, anhang_id)
+                .await?
+                .is_none()
+        );
+        assert!(
+            super::anhang_by_antrag(&mut *conn, &ersteller, antrag_id, anhang_id)
+                .await?
+                .is_some()
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(path = "../database/fixtures", scripts("gimme_attachments")))]
+    async fn move_anhaenge(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let key = "9b5104a9-6a7d-468e-bbf2-f72a9086a3dc";
+
+        let from = MemoryStore::default();
+        let to = MemoryStore::default();
+
+        let spool_dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let spool_dir = Path::new(&spool_dir);
+
+        from.put(key, b"Tolles Excel Sheet".to_vec()).await?;
+        from.put("kein-anhang", b"bleibt liegen".to_vec()).await?;
+
+        assert_eq!(
+            super::move_anhaenge(&mut *conn, &from, &to, spool_dir).await?,
+            1
+        );
+
+        assert_eq!(to.get(key).await?, Some(b"Tolles Excel Sheet".to_vec()));
+        assert_eq!(from.get(key).await?, None);
+        assert!(from.get("kein-anhang").await?.is_some());
+
+        // nothing left to move
+        assert_eq!(
+            super::move_anhaenge(&mut *conn, &from, &to, spool_dir).await?,
+            0
+        );
+
+        // moving into the same storage would delete everything
+        to.put(key, b"Tolles Excel Sheet".to_vec()).await?;
+        assert!(super::move_anhaenge(&mut *conn, &to, &to, spool_dir)
+            .await
+            .is_err());
+        assert!(to.get(key).await?.is_some());
+
+        async_std::fs::remove_dir(spool_dir).await?;
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts(
+            "gimme_persons",
+            "gimme_antraege",
+            "gimme_attachments",
+            "gimme_attachment_mappings"
+        )
+    ))]
+    async fn delete_unused_anhaenge(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let storage = MemoryStore::default();
+        let now = Utc::now();
+
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+
+        let path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        async_std::fs::write(&path, b"a").await?;
+        let datei = inspect_datei(path.as_path().into(), "a.txt").await?;
+
+        let person_id = Uuid::parse_str("5a5a134d-9345-4c36-a466-1c3bb806b240").unwrap();
+
+        let fehlgeschlagen = super::stage_anhang(
+            &mut *conn,
+            &storage,
+            &datei,
+            path.as_path().into(),
+            person_id,
+        )
+        .await?;
+        let angehaengt = super::stage_anhang(
+            &mut *conn,
+            &storage,
+            &datei,
+            path.as_path().into(),
+            person_id,
+        )
+        .await?;
+        async_std::fs::remove_file(&path).await?;
+
+        conn.confirm_anhang(angehaengt.id, person_id).await?;
+        conn.add_anhang_to_antrag(antrag_id, angehaengt.id).await?;
+
+        // uploads in progress are kept
+        assert!(super::delete_unused_anhaenge(&mut *conn, &storage, now)
+            .await?
+            .is_empty());
+
+        let deleted =
+            super::delete_unused_anhaenge(&mut *conn, &storage, now + TimeDelta::hours(2)).await?;
+
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].id, fehlgeschlagen.id);
+        assert!(storage.get(&fehlgeschlagen.id.to_string()).await?.is_none());
+        assert!(conn.anhang_by_id(fehlgeschlagen.id).await?.is_none());
+
+        conn.delete_anhang_from_antrag(antrag_id, angehaengt.id)
+            .await?;
+
+        assert!(super::delete_anhang_if_unused(&mut *conn, &storage, angehaengt.id).await?);
+        assert!(storage.get(&angehaengt.id.to_string()).await?.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts("gimme_persons", "gimme_antraege")
+    ))]
+    async fn delete_unused_anhaenge_while_attaching(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let storage = Arc::new(MemoryStore::default());
+        let now = Utc::now();
+
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let person_id = Uuid::parse_str("5a5a134d-9345-4c36-a466-1c3bb806b240").unwrap();
+
+        let path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        async_std::fs::write(&path, b"a").await?;
+        let datei = inspect_datei(path.as_path().into(), "a.txt").await?;
+
+        let anhang = super::stage_anhang(
+            &mut *conn,
+            &*storage,
+            &datei,
+            path.as_path().into(),
+            person_id,
+        )
+        .await?;
+        async_std::fs::remove_file(&path).await?;
+
+        // the Anhang is attached while the cleanup still considers it a failed upload
+        let mut anhaengen = pool.begin().await?;
+        anhaengen.confirm_anhang(anhang.id, person_id).await?;
+        anhaengen.add_anhang_to_antrag(antrag_id, anhang.id).await?;
+
+        let aufraeumen = tokio::spawn({
+            let pool = pool.clone();
+            let storage = storage.clone();
+
+            async move {
+                let mut aufraeumer = pool.acquire().await?;
+
+                super::delete_unused_anhaenge(
+                    &mut *aufraeumer,
+                    &*storage,
+                    now + TimeDelta::hours(2),
+                )
+                .await
+            }
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        anhaengen.commit().await?;
+
+        assert!(aufraeumen.await??.is_empty());
+        assert!(conn.anhang_by_id(anhang.id).await?.is_some());
+        assert_eq!(conn.antraege_by_anhang(anhang.id).await?, vec![antrag_id]);
+        assert!(storage.get(&anhang.id.to_string()).await?.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(path = "../database/fixtures", scripts("gimme_attachments")))]
+    async fn reconcile_anhaenge(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let storage = MemoryStore::default();
+        let now = Utc::now();
+
+        let anhang_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
+        let verwaist = Uuid::new_v4();
+
+        storage.put(&anhang_id.to_string(), b"a".to_vec()).await?;
+        storage.put(&preview_key(anhang_id), vec![]).await?;
+        storage.put(&verwaist.to_string(), b"b".to_vec()).await?;
+        storage.put(&preview_key(verwaist), vec![]).await?;
+        storage.put("etwas-anderes", b"c".to_vec()).await?;
+
+        let aufgeraeumt = super::reconcile_anhaenge(&mut *conn, &storage, now).await?;
+
+        let mut deleted = aufgeraeumt.deleted_blobs;
+        deleted.sort();
+        let mut verwaiste_keys = vec![verwaist.to_string(), preview_key(verwaist)];
+        verwaiste_keys.sort();
+
+        assert_eq!(deleted, verwaiste_keys);
+        assert!(aufgeraeumt.missing_blobs.is_empty());
+
+        let mut keys = storage.keys().await?;
+        keys.sort();
+        let mut uebrig = vec![
+            anhang_id.to_string(),
+            preview_key(anhang_id),
+            "etwas-anderes".to_string(),
+        ];
+        uebrig.sort();
+
+        assert_eq!(keys, uebrig);
+
+        storage.delete(&anhang_id.to_string()).await?;
+
+        let fehlend =
+            super::reconcile_anhaenge(&mut *conn, &storage, now + TimeDelta::hours(2)).await?;
+
+        assert_eq!(fehlend.missing_blobs.len(), 1);
+        assert_eq!(fehlend.missing_blobs[0].id, anhang_id);
+        // the preview of an Anhang whose content is missing is kept like the Anhang itself
+        assert!(fehlend.deleted_blobs.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts(
+            "gimme_sitzungen",
+            "gimme_tops",
+            "gimme_antraege",
+            "gimme_attachments",
+            "gimme_attachment_mappings"
+        )
+    ))]
+    async fn visible_anhang(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let anonym = AntragBetrachter::default();
+        let attachment_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
+        let sitzung_id = Uuid::parse_str("dfe75b8c-8c24-4a2b-84e5-d0573a8e6f00").unwrap();
+
+        assert!(super::visible_anhang(&mut *conn, &anonym, attachment_id)
+            .await?
+            .is_some());
+
+        for antrag_id in [
+            "46148231-87b0-4486-8043-c55038178518",
+            "5c51d5c0-3943-4695-844d-4c47da854fac",
+        ] {
+            conn.delete_anhang_from_antrag(Uuid::parse_str(antrag_id).unwrap(), attachment_id)
+                .await?;
+        }
+
+        // Anhänge attached to nothing are not visible at all
+        assert!(super::visible_anhang(&mut *conn, &anonym, attachment_id)
+            .await?
+            .is_none());
+
+        conn.add_anhang_to_sitzung(sitzung_id, attachment_id)
+            .await?;
+
+        assert!(super::visible_anhang(&mut *conn, &anonym, attachment_id)
+            .await?
+            .is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts(
+            "gimme_sitzungen",
+            "gimme_tops",
+            "gimme_antraege",
+            "gimme_attachments",
+            "gimme_attachment_mappings"
+        )
+    ))]
+    async fn search(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let anonym = AntragBetrachter::default();
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let attachment_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
+
+        let oeffentlich = super::search(&mut *conn, &anonym, "volt").await?;
+        assert_eq!(oeffentlich.antraege.len(), 1);
+        assert_eq!(oeffentlich.antraege[0].antrag.data.id, antrag_id);
+
+        for id in [
+            antrag_id,
+            Uuid::parse_str("5c51d5c0-3943-4695-844d-4c47da854fac").unwrap(),
+        ] {
+            conn.set_antrag_sichtbarkeit(id, Sichtbarkeit::Entwurf)
+                .await?;
+        }
+
+        // neither the Entwürfe nor the Anhang only attached to them are found
+        let versteckt = super::search(&mut *conn, &anonym, "volt or excel").await?;
+        assert!(versteckt.antraege.is_empty());
+        assert!(versteckt.anhaenge.is_empty());
+
+        let alle = AntragBetrachter {
+            alle: true,
+            ..Default::default()
+        };
+
+        let alles = super::search(&mut *conn, &alle, "volt or excel").await?;
+        assert_eq!(alles.antraege.len(), 1);
+        assert_eq!(alles.anhaenge.len(), 1);
+        assert_eq!(alles.anhaenge[0].anhang.id, attachment_id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn anhang_preview() -> Result<()> {
+        let storage = MemoryStore::default();
+
+        let mut png = Vec::new();
+        image::RgbImage::new(640, 640)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+
+        let bild = Anhang {
+            id: Uuid::new_v4(),
+            filename: "bild.png".to_string(),
+            content_type: "image/png".to_string(),
+            size: Some(png.len() as i64),
+            sha256: None,
+            erstellt_am: Utc::now(),
+            bestaetigt: true,
+            hochgeladen_von: None,
+        };
+        let kaputt = Anhang {
+            id: Uuid::new_v4(),
+            ..bild.clone()
+        };
+        storage.put(&bild.id.to_string(), png).await?;
+        storage
+            .put(&kaputt.id.to_string(), b"kein Bild".to_vec())
+            .await?;
+
+        let vorschau = super::anhang_preview(&storage, &bild).await?.unwrap();
+        assert_eq!(
+            storage.get(&preview_key(bild.id)).await?,
+            Some(vorschau.clone())
+        );
+        assert_eq!(
+            super::anhang_preview(&storage, &bild).await?,
+            Some(vorschau)
+        );
+
+        assert!(super::anhang_preview(&storage, &kaputt).await?.is_none());
+        assert_eq!(storage.get(&preview_key(kaputt.id)).await?, Some(vec![]));
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../database/fixtures",
+        scripts(
+            "gimme_persons",
+            "gimme_sitzungen",
+            "gimme_tops",
+            "gimme_antraege",
+            "gimme_antrag_mappings",
+            "gimme_attachments",
+            "gimme_attachment_mappings"
+        )
+    ))]
+    async fn sitzung_bundle(pool: PgPool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+
+        let sitzung_id = Uuid::parse_str("dfe75b8c-8c24-4a2b-84e5-d0573a8e6f00").unwrap();
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let anhang_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
+
+        conn.add_anhang_to_sitzung(sitzung_id, anhang_id).await?;
+
+        let anonym = AntragBetrachter::default();
+
+        let sitzung = super::sitzung_with_tops(&mut *conn, &anonym, sitzung_id)
+            .await?
+            .unwrap();
+        let dateien = super::sitzung_bundle(&mut *conn, &sitzung, Some("Hallo".to_string()))
+            .await?
+            .iter()
+            .map(|datei| datei.pfad().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            dateien,
+            vec![
+                "Einladung.md",
+                "Tolles Excel Sheet",
+                "01 one/Volthahn.md",
+                "01 one/Tolles Excel Sheet",
+            ]
+        );
+
+        // hidden Anträge and their Anhänge are left out
+        conn.set_antrag_sichtbarkeit(antrag_id, Sichtbarkeit::Entwurf)
+            .await?;
+
+        let ohne_antrag = super::sitzung_with_tops(&mut *conn, &anonym, sitzung_id)
+            .await?
+            .unwrap();
+        let ohne_antrag = super::sitzung_bundle(&mut *conn, &ohne_antrag, None).await?;
+
+        assert_eq!(ohne_antrag.len(), 1);
+        assert_eq!(ohne_antrag[0].pfad(), "Tolles Excel Sheet");
+
+        Ok(())
+    }
 }
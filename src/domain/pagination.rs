@@ -0,0 +1,48 @@
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// Which part of a list to return
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pagination {
+    /// How many items to return at most, everything after the offset if `None`
+    pub limit: Option<i64>,
+    /// How many items to skip
+    pub offset: i64,
+}
+
+impl Pagination {
+    /// The whole list
+    pub fn alle() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn is_desc(self) -> bool {
+        self == SortOrder::Desc
+    }
+}
+
+/// One page of a list, together with the length of the whole list
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+        }
+    }
+}
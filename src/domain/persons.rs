@@ -1,9 +1,10 @@
 use chrono::NaiveDate;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use super::Result;
+use super::pagination::{Page, Pagination, SortOrder};
+use super::{Capability, Result};
 
 #[derive(Debug, Serialize, IntoParams, ToSchema)]
 pub struct PersonRoleMapping {
@@ -30,6 +31,58 @@ pub struct Abmeldung {
     pub person_id: Uuid,
     pub anfangsdatum: NaiveDate,
     pub ablaufdatum: NaiveDate,
+    pub grund: Option<String>,
+    /// Whether the grund is only visible to the person itself and to those managing persons
+    pub privat: bool,
+}
+
+impl Abmeldung {
+    /// Removes the grund if it is private
+    pub fn ohne_privaten_grund(self) -> Abmeldung {
+        Abmeldung {
+            grund: if self.privat { None } else { self.grund },
+            ..self
+        }
+    }
+}
+
+/// What a Person wants to be notified about
+#[derive(Debug, Serialize, ToSchema, PartialEq, Clone, Copy)]
+pub struct BenachrichtigungsEinstellungen {
+    /// New, moved and cancelled Sitzungen
+    pub sitzungen: bool,
+    /// Changes to Anträge of the Person
+    pub antraege: bool,
+    /// Kommentare on Anträge of the Person
+    pub kommentare: bool,
+}
+
+impl Default for BenachrichtigungsEinstellungen {
+    fn default() -> Self {
+        Self {
+            sitzungen: true,
+            antraege: true,
+            kommentare: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema, PartialEq, Eq, strum::AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum PersonSort {
+    #[default]
+    Name,
+    UserName,
+}
+
+/// Which persons to list and in which order
+#[derive(Debug, Default)]
+pub struct PersonFilter {
+    /// Only persons whose name contains this, ignoring case
+    pub name: Option<String>,
+    pub sort: PersonSort,
+    pub order: SortOrder,
 }
 
 pub trait PersonRepo {
@@ -42,19 +95,30 @@ pub trait PersonRepo {
 
     async fn create_role(&mut self, name: &str) -> Result<()>;
 
+    /// Creates an Abmeldung, merging it with all overlapping Abmeldungen of the person
     async fn create_abmeldung(
         &mut self,
         person_id: Uuid,
         start: NaiveDate,
         end: NaiveDate,
+        grund: Option<&str>,
+        privat: bool,
     ) -> Result<Abmeldung>;
 
-    async fn persons(&mut self) -> Result<Vec<Person>>;
+    async fn persons(
+        &mut self,
+        filter: &PersonFilter,
+        pagination: Pagination,
+    ) -> Result<Page<Person>>;
 
     async fn roles(&mut self) -> Result<Vec<String>>;
 
     async fn roles_by_person(&mut self, id: Uuid) -> Result<Vec<String>>;
 
+    async fn capabilities_by_role(&mut self, role: &str) -> Result<Vec<Capability>>;
+
+    async fn capabilities_by_person(&mut self, id: Uuid) -> Result<Vec<Capability>>;
+
     async fn person_by_id(&mut self, id: Uuid) -> Result<Option<Person>>;
 
     async fn person_by_matrix_id(&mut self, matrix_id: &str) -> Result<Option<Person>>;
@@ -63,14 +127,41 @@ pub trait PersonRepo {
 
     async fn persons_with_role(&mut self, role: &str) -> Result<Vec<Person>>;
 
+    async fn identities_by_person(&mut self, person_id: Uuid) -> Result<Vec<String>>;
+
     async fn abmeldungen_by_person(&mut self, person_id: Uuid) -> Result<Vec<Abmeldung>>;
 
     async fn abmeldungen_at(&mut self, date: NaiveDate) -> Result<Vec<Abmeldung>>;
 
+    async fn benachrichtigungen_by_person(
+        &mut self,
+        person_id: Uuid,
+    ) -> Result<BenachrichtigungsEinstellungen>;
+
+    async fn set_benachrichtigungen(
+        &mut self,
+        person_id: Uuid,
+        einstellungen: BenachrichtigungsEinstellungen,
+    ) -> Result<BenachrichtigungsEinstellungen>;
+
     async fn assign_role_to_person(&mut self, person_id: Uuid, role: &str) -> Result<()>;
 
     async fn revoke_role_from_person(&mut self, person_id: Uuid, role: &str) -> Result<()>;
 
+    /// Links the identity to the Person. Returns false if it is linked to a Person already
+    async fn link_identity(&mut self, person_id: Uuid, user_name: &str) -> Result<bool>;
+
+    async fn unlink_identity(&mut self, person_id: Uuid, user_name: &str)
+        -> Result<Option<String>>;
+
+    async fn grant_capability_to_role(&mut self, role: &str, capability: Capability) -> Result<()>;
+
+    async fn revoke_capability_from_role(
+        &mut self,
+        role: &str,
+        capability: Capability,
+    ) -> Result<()>;
+
     async fn revoke_abmeldung_from_person(
         &mut self,
         person_id: Uuid,
@@ -78,7 +169,7 @@ pub trait PersonRepo {
         end: NaiveDate,
     ) -> Result<()>;
 
-    async fn update_person<'a>(
+    async fn update_person(
         &mut self,
         id: Uuid,
         full_name: Option<&str>,
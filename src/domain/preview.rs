@@ -0,0 +1,185 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, GrayImage, ImageFormat, ImageReader, Limits, RgbImage};
+use lopdf::{Document, LoadOptions};
+use uuid::Uuid;
+
+/// Previews fit into a square of this many pixels
+pub const PREVIEW_SIZE: u32 = 320;
+
+/// Content type of all previews
+pub const PREVIEW_CONTENT_TYPE: &str = "image/png";
+
+/// Anhänge larger than this get no preview, rendering them would take too long
+pub const MAX_PREVIEW_SOURCE_SIZE: i64 = 20 * 1024 * 1024;
+
+/// Most memory decoding an image, or a single stream of a PDF, may take
+const MAX_DECODED_SIZE: usize = 256 * 1024 * 1024;
+
+/// Appended to the key of an Anhang to get the key of its preview
+pub const PREVIEW_KEY_SUFFIX: &str = ".preview";
+
+/// Key the preview of an Anhang is cached at, next to its content
+pub fn preview_key(anhang_id: Uuid) -> String {
+    format!("{anhang_id}{PREVIEW_KEY_SUFFIX}")
+}
+
+/// Whether we can render previews for Anhänge of this type at all
+pub fn has_preview(content_type: &str) -> bool {
+    matches!(
+        content_type,
+        "image/png" | "image/jpeg" | "image/gif" | "image/webp" | "application/pdf"
+    )
+}
+
+/// Renders a PNG thumbnail of an image or of the first page of a PDF. `None` if the content
+/// could not be rendered.
+///
+/// There is no PDF renderer in pure Rust, so the first page is only rendered as far as it is an
+/// image, like in scanned documents: the largest image on the page becomes the preview.
+pub fn render_preview(content: &[u8], content_type: &str) -> Option<Vec<u8>> {
+    let image = match content_type {
+        "application/pdf" => first_page_image(content)?,
+        _ => decode_image(content)?,
+    };
+
+    let mut png = Vec::new();
+    image
+        .thumbnail(PREVIEW_SIZE, PREVIEW_SIZE)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .ok()?;
+
+    Some(png)
+}
+
+fn decode_image(content: &[u8]) -> Option<DynamicImage> {
+    let mut reader = ImageReader::new(Cursor::new(content))
+        .with_guessed_format()
+        .ok()?;
+
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(MAX_DECODED_SIZE as u64);
+    reader.limits(limits);
+
+    reader.decode().ok()
+}
+
+/// The largest image on the first page of a PDF, if we can decode it
+fn first_page_image(content: &[u8]) -> Option<DynamicImage> {
+    let document = Document::load_mem_with_options(
+        content,
+        LoadOptions::with_max_decompressed_size(MAX_DECODED_SIZE),
+    )
+    .ok()?;
+
+    let (_, first_page) = document.get_pages().into_iter().next()?;
+
+    let image = document
+        .get_page_images(first_page)
+        .ok()?
+        .into_iter()
+        .max_by_key(|image| image.width.saturating_mul(image.height))?;
+
+    let filters = image.filters.clone().unwrap_or_default();
+
+    match filters.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        // JPEGs are embedded as they are
+        ["DCTDecode"] => decode_image(image.content),
+        [] | ["FlateDecode"] if image.bits_per_component == Some(8) => {
+            let pixels = document
+                .get_object(image.id)
+                .ok()?
+                .as_stream()
+                .ok()?
+                .get_plain_content_with_limit(MAX_DECODED_SIZE)
+                .ok()?;
+            let width = u32::try_from(image.width).ok()?;
+            let height = u32::try_from(image.height).ok()?;
+
+            match image.color_space.as_deref() {
+                Some("DeviceRGB") => RgbImage::from_raw(width, height, pixels).map(Into::into),
+                Some("DeviceGray") => GrayImage::from_raw(width, height, pixels).map(Into::into),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, RgbImage};
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    use super::render_preview;
+
+    fn preview_dimensions(preview: &[u8]) -> (u32, u32) {
+        let image = image::load_from_memory_with_format(preview, ImageFormat::Png).unwrap();
+
+        (image.width(), image.height())
+    }
+
+    #[test]
+    fn renders_image_previews() {
+        let mut png = Vec::new();
+        RgbImage::new(1000, 500)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+
+        let preview = render_preview(&png, "image/png").unwrap();
+        assert_eq!(preview_dimensions(&preview), (320, 160));
+
+        assert!(render_preview(b"kein Bild", "image/png").is_none());
+    }
+
+    #[test]
+    fn renders_scanned_pdf_previews() {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+
+        let scan = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 400,
+                "Height" => 640,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            vec![0x80; 400 * 640],
+        );
+        let scan_id = document.add_object(scan);
+
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 400.into(), 640.into()],
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Scan" => scan_id },
+            },
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let mut pdf = Vec::new();
+        document.save_to(&mut pdf).unwrap();
+
+        let preview = render_preview(&pdf, "application/pdf").unwrap();
+        assert_eq!(preview_dimensions(&preview), (200, 320));
+
+        assert!(render_preview(b"%PDF-1.5 kaputt", "application/pdf").is_none());
+    }
+}
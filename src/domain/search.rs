@@ -0,0 +1,212 @@
+use lopdf::{Document, LoadOptions};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::anhang::Anhang;
+use super::antrag::{Antrag, AntragBetrachter};
+use super::Result;
+
+/// Types of Anhänge we extract text from for the full-text search
+pub const TEXT_CONTENT_TYPES: [&str; 4] =
+    ["application/pdf", "text/plain", "text/csv", "text/markdown"];
+
+/// Text is not extracted from larger Anhänge
+pub const MAX_TEXT_SOURCE_SIZE: i64 = 20 * 1024 * 1024;
+
+/// Most results of each type a search returns
+pub const MAX_TREFFER: usize = 20;
+
+/// Longest text kept for an Anhang in bytes, so its search vector stays within the limits of
+/// Postgres
+const MAX_TEXT_LENGTH: usize = 256 * 1024;
+
+/// Most memory decompressing a single page of a PDF may take
+const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Where a query matched, without the matching object itself
+#[derive(Debug)]
+pub struct Fundstelle {
+    pub id: Uuid,
+    pub rang: f32,
+    /// Parts of the text around the matches, which are marked like `**this**`
+    pub auszug: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AntragTreffer {
+    #[serde(flatten)]
+    pub antrag: Antrag,
+    pub rang: f32,
+    pub auszug: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TopTreffer {
+    pub id: Uuid,
+    pub sitzung_id: Uuid,
+    pub name: String,
+    pub rang: f32,
+    pub auszug: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnhangTreffer {
+    #[serde(flatten)]
+    pub anhang: Anhang,
+    pub rang: f32,
+    pub auszug: String,
+}
+
+/// The results of a full-text search grouped by type, the best matches first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResult {
+    pub antraege: Vec<AntragTreffer>,
+    pub tops: Vec<TopTreffer>,
+    pub anhaenge: Vec<AnhangTreffer>,
+}
+
+/// Full-text search using the german Postgres configuration. Queries are parsed like in search
+/// engines, so `"exact phrase"`, `or` and `-excluded` work
+pub trait SearchRepo {
+    /// Searches the Anträge the betrachter may see
+    async fn search_antraege(
+        &mut self,
+        betrachter: &AntragBetrachter,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<Fundstelle>>;
+
+    async fn search_tops(&mut self, query: &str, limit: i64) -> Result<Vec<TopTreffer>>;
+
+    /// Searches the filenames and extracted texts of the confirmed Anhänge the betrachter may see
+    async fn search_anhaenge(
+        &mut self,
+        betrachter: &AntragBetrachter,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<Fundstelle>>;
+}
+
+/// Whether we extract text from an Anhang of this type and size
+pub fn has_text(content_type: &str, size: Option<i64>) -> bool {
+    TEXT_CONTENT_TYPES.contains(&content_type)
+        && size.is_none_or(|size| size <= MAX_TEXT_SOURCE_SIZE)
+}
+
+/// Extracts the text of a PDF or plain text file. Empty if there is none, like for scanned PDFs
+pub fn extract_text(content: &[u8], content_type: &str) -> String {
+    let mut text = match content_type {
+        "application/pdf" => extract_pdf_text(content),
+        _ => String::from_utf8_lossy(content).into_owned(),
+    };
+
+    // Postgres does not allow NUL in text
+    text.retain(|c| c != '\0');
+
+    if text.len() > MAX_TEXT_LENGTH {
+        let mut end = MAX_TEXT_LENGTH;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+
+    text
+}
+
+fn extract_pdf_text(content: &[u8]) -> String {
+    let Ok(document) = Document::load_mem_with_options(
+        content,
+        LoadOptions::with_max_decompressed_size(MAX_DECOMPRESSED_SIZE),
+    ) else {
+        return String::new();
+    };
+
+    let pages = document.get_pages().into_keys().collect::<Vec<_>>();
+
+    // pages we cannot read are skipped, the rest is still worth searching
+    document
+        .extract_text_chunks_with_limit(&pages, MAX_DECOMPRESSED_SIZE)
+        .into_iter()
+        .filter_map(|chunk| chunk.ok())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    use super::{extract_text, has_text, MAX_TEXT_LENGTH, MAX_TEXT_SOURCE_SIZE};
+
+    #[test]
+    fn extracts_plain_text() {
+        assert_eq!(
+            extract_text("Grüße\0 aus dem Haushalt".as_bytes(), "text/plain"),
+            "Grüße aus dem Haushalt"
+        );
+
+        let lang = "ä".repeat(MAX_TEXT_LENGTH);
+        assert_eq!(
+            extract_text(lang.as_bytes(), "text/plain").len(),
+            MAX_TEXT_LENGTH
+        );
+
+        assert!(has_text("text/csv", None));
+        assert!(!has_text("text/csv", Some(MAX_TEXT_SOURCE_SIZE + 1)));
+        assert!(!has_text("image/png", Some(42)));
+    }
+
+    #[test]
+    fn extracts_pdf_text() {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+
+        let font_id = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                Operation::new("Td", vec![100.into(), 600.into()]),
+                Operation::new("Tj", vec![Object::string_literal("Haushaltsplan 2024")]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id =
+            document.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! {
+                "Font" => dictionary! { "F1" => font_id },
+            },
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let mut pdf = Vec::new();
+        document.save_to(&mut pdf).unwrap();
+
+        assert!(extract_text(&pdf, "application/pdf").contains("Haushaltsplan 2024"));
+        assert_eq!(extract_text(b"%PDF-1.5 kaputt", "application/pdf"), "");
+    }
+}
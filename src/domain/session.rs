@@ -0,0 +1,86 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::Result;
+
+/// A login of a Person. The id is handed out as the session cookie, everything else stays on the
+/// server.
+#[derive(Debug, Serialize, IntoParams, ToSchema, Clone)]
+pub struct Session {
+    pub id: Uuid,
+    pub person_id: Uuid,
+    pub oauth_provider: Option<String>,
+    #[serde(skip)]
+    pub user_info: serde_json::Value,
+    #[serde(skip)]
+    pub refresh_token: Option<String>,
+    pub erstellt_am: DateTime<Utc>,
+    pub zuletzt_aktiv: DateTime<Utc>,
+    pub ablaufdatum: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn is_active(&self, now: DateTime<Utc>, idle_timeout: TimeDelta) -> bool {
+        self.ablaufdatum > now && self.zuletzt_aktiv + idle_timeout > now
+    }
+}
+
+pub trait SessionRepo {
+    async fn create_session(
+        &mut self,
+        person_id: Uuid,
+        oauth_provider: Option<&str>,
+        user_info: &serde_json::Value,
+        refresh_token: Option<&str>,
+        ablaufdatum: DateTime<Utc>,
+    ) -> Result<Session>;
+
+    async fn session_by_id(&mut self, id: Uuid) -> Result<Option<Session>>;
+
+    async fn sessions_by_person(&mut self, person_id: Uuid) -> Result<Vec<Session>>;
+
+    async fn touch_session(&mut self, id: Uuid, now: DateTime<Utc>) -> Result<()>;
+
+    async fn update_session_tokens(
+        &mut self,
+        id: Uuid,
+        user_info: &serde_json::Value,
+        refresh_token: Option<&str>,
+    ) -> Result<Option<Session>>;
+
+    async fn delete_session(&mut self, id: Uuid) -> Result<Option<Session>>;
+
+    async fn delete_sessions_by_person(&mut self, person_id: Uuid) -> Result<Vec<Session>>;
+
+    async fn delete_inactive_sessions(
+        &mut self,
+        now: DateTime<Utc>,
+        idle_timeout: TimeDelta,
+    ) -> Result<u64>;
+}
+
+/// Looks up a session and marks it as used. Sessions past their idle or absolute timeout are
+/// deleted instead.
+pub async fn active_session(
+    repo: &mut impl SessionRepo,
+    id: Uuid,
+    now: DateTime<Utc>,
+    idle_timeout: TimeDelta,
+) -> Result<Option<Session>> {
+    let Some(mut session) = repo.session_by_id(id).await? else {
+        return Ok(None);
+    };
+
+    if !session.is_active(now, idle_timeout) {
+        repo.delete_session(id).await?;
+
+        return Ok(None);
+    }
+
+    repo.touch_session(id, now).await?;
+    session.zuletzt_aktiv = now;
+
+    Ok(Some(session))
+}
@@ -1,10 +1,13 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+use super::anhang::Anhang;
 use super::antrag::Antrag;
 use super::legislatur_periode::LegislaturPeriode;
+use super::pagination::{Page, Pagination, SortOrder};
 use super::Result;
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, sqlx::Type, ToSchema, PartialEq, Eq)]
@@ -19,6 +22,19 @@ pub enum SitzungTyp {
     Dringlichkeit,
 }
 
+impl SitzungTyp {
+    pub fn bezeichnung(&self) -> &'static str {
+        match self {
+            SitzungTyp::Normal => "Sitzung",
+            SitzungTyp::VV => "Vollversammlung",
+            SitzungTyp::WahlVV => "Wahlvollversammlung",
+            SitzungTyp::Ersatz => "Ersatzsitzung",
+            SitzungTyp::Konsti => "Konstituierende Sitzung",
+            SitzungTyp::Dringlichkeit => "Dringlichkeitssitzung",
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, sqlx::Type, ToSchema, PartialEq, Eq)]
 #[sqlx(type_name = "topkind", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
@@ -29,6 +45,16 @@ pub enum TopTyp {
     Verschiedenes,
 }
 
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, sqlx::Type, ToSchema, PartialEq, Eq)]
+#[sqlx(type_name = "sitzungstatus", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum SitzungStatus {
+    Geplant,
+    Abgesagt,
+    Verschoben,
+    Abgeschlossen,
+}
+
 #[derive(Debug, Serialize, IntoParams, ToSchema, Clone)]
 pub struct Sitzung {
     pub id: Uuid,
@@ -36,9 +62,53 @@ pub struct Sitzung {
     pub ort: String,
     pub typ: SitzungTyp,
     pub antragsfrist: DateTime<Utc>,
+    pub serie_id: Option<Uuid>,
+    pub status: SitzungStatus,
+    pub status_grund: Option<String>,
     pub legislatur_periode: LegislaturPeriode,
 }
 
+#[derive(Debug, Serialize, IntoParams, ToSchema, Clone)]
+pub struct SitzungSerie {
+    pub id: Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, IntoParams, ToSchema)]
+pub struct SitzungSerieWithSitzungen {
+    #[serde(flatten)]
+    pub serie: SitzungSerie,
+    pub sitzungen: Vec<Sitzung>,
+}
+
+/// Describes a Sitzung taking place every week on the same weekday and time
+#[derive(Debug, Clone)]
+pub struct SerienRegel {
+    pub start: NaiveDate,
+    pub ende: NaiveDate,
+    pub wochentag: Weekday,
+    pub uhrzeit: NaiveTime,
+    pub zeitzone: Tz,
+    pub ausnahmen: Vec<NaiveDate>,
+}
+
+impl SerienRegel {
+    /// All dates between start and ende (inclusive) that match the rule and are not excluded
+    pub fn termine(&self) -> Vec<DateTime<Utc>> {
+        self.start
+            .iter_days()
+            .take_while(|date| *date <= self.ende)
+            .filter(|date| date.weekday() == self.wochentag && !self.ausnahmen.contains(date))
+            .filter_map(|date| {
+                self.zeitzone
+                    .from_local_datetime(&date.and_time(self.uhrzeit))
+                    .earliest()
+            })
+            .map(|datetime| datetime.with_timezone(&Utc))
+            .collect()
+    }
+}
+
 #[derive(Debug, Serialize, IntoParams, ToSchema)]
 pub struct Top {
     pub id: Uuid,
@@ -48,11 +118,33 @@ pub struct Top {
     pub typ: TopTyp,
 }
 
+/// Records that a Top was moved or copied to another Sitzung
+#[derive(Debug, Serialize, IntoParams, ToSchema)]
+pub struct Vertagung {
+    pub id: Uuid,
+    pub top_id: Uuid,
+    pub von_sitzung_id: Option<Uuid>,
+    pub nach_sitzung_id: Uuid,
+    pub vertagt_am: DateTime<Utc>,
+}
+
+/// Records that a Sitzung was moved to another date
+#[derive(Debug, Serialize, IntoParams, ToSchema)]
+pub struct Verschiebung {
+    pub id: Uuid,
+    pub sitzung_id: Uuid,
+    pub alte_datetime: DateTime<Utc>,
+    pub neue_datetime: DateTime<Utc>,
+    pub grund: Option<String>,
+    pub verschoben_am: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, IntoParams, ToSchema)]
 pub struct SitzungWithTops {
     #[serde(flatten)]
     pub sitzung: Sitzung,
     pub tops: Vec<TopWithAntraege>,
+    pub anhaenge: Vec<Anhang>,
 }
 
 #[derive(Debug, Serialize, IntoParams, ToSchema)]
@@ -60,6 +152,30 @@ pub struct TopWithAntraege {
     #[serde(flatten)]
     pub top: Top,
     pub antraege: Vec<Antrag>,
+    pub anhaenge: Vec<Anhang>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, ToSchema, PartialEq, Eq, strum::AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SitzungSort {
+    #[default]
+    Datetime,
+    Antragsfrist,
+}
+
+/// Which Sitzungen to list and in which order
+#[derive(Debug, Default)]
+pub struct SitzungFilter {
+    pub typ: Option<SitzungTyp>,
+    pub status: Option<SitzungStatus>,
+    pub legislatur_periode_id: Option<Uuid>,
+    /// Only Sitzungen at or after this time
+    pub ab: Option<DateTime<Utc>>,
+    /// Only Sitzungen at or before this time
+    pub bis: Option<DateTime<Utc>>,
+    pub sort: SitzungSort,
+    pub order: SortOrder,
 }
 
 pub trait SitzungRepo {
@@ -70,6 +186,7 @@ pub trait SitzungRepo {
         typ: SitzungTyp,
         antragsfrist: DateTime<Utc>,
         legislatur_periode: Uuid,
+        serie_id: Option<Uuid>,
     ) -> Result<Sitzung>;
 
     async fn create_top(
@@ -80,7 +197,28 @@ pub trait SitzungRepo {
         typ: TopTyp,
     ) -> Result<Top>;
 
-    async fn sitzungen(&mut self) -> Result<Vec<Sitzung>>;
+    async fn create_vertagung(
+        &mut self,
+        top_id: Uuid,
+        von_sitzung_id: Uuid,
+        nach_sitzung_id: Uuid,
+    ) -> Result<Vertagung>;
+
+    async fn create_sitzung_serie(&mut self, name: &str) -> Result<SitzungSerie>;
+
+    async fn create_verschiebung(
+        &mut self,
+        sitzung_id: Uuid,
+        alte_datetime: DateTime<Utc>,
+        neue_datetime: DateTime<Utc>,
+        grund: Option<&str>,
+    ) -> Result<Verschiebung>;
+
+    async fn sitzungen(
+        &mut self,
+        filter: &SitzungFilter,
+        pagination: Pagination,
+    ) -> Result<Page<Sitzung>>;
 
     async fn sitzung_by_id(&mut self, id: Uuid) -> Result<Option<Sitzung>>;
 
@@ -90,26 +228,50 @@ pub trait SitzungRepo {
         limit: Option<i64>,
     ) -> Result<Vec<Sitzung>>;
 
+    async fn next_scheduled_sitzung(&mut self, after: DateTime<Utc>) -> Result<Option<Sitzung>>;
+
     async fn sitzungen_between(
         &mut self,
         start: DateTime<Utc>,
         end: DateTime<Utc>,
     ) -> Result<Vec<Sitzung>>;
 
+    async fn sitzung_serien(&mut self) -> Result<Vec<SitzungSerie>>;
+
+    async fn sitzung_serie_by_id(&mut self, id: Uuid) -> Result<Option<SitzungSerie>>;
+
+    async fn sitzungen_by_serie(&mut self, serie_id: Uuid) -> Result<Vec<Sitzung>>;
+
+    async fn verschiebungen_by_sitzung(&mut self, sitzung_id: Uuid) -> Result<Vec<Verschiebung>>;
+
     async fn top_by_id(&mut self, id: Uuid) -> Result<Option<Top>>;
 
     async fn tops_by_sitzung(&mut self, sitzung_id: Uuid) -> Result<Vec<Top>>;
 
-    async fn update_sitzung<'a>(
+    /// Vertagungen of the Top from or to the Sitzung
+    async fn vertagungen_by_top(
+        &mut self,
+        top_id: Uuid,
+        sitzung_id: Uuid,
+    ) -> Result<Vec<Vertagung>>;
+
+    async fn update_sitzung(
         &mut self,
         id: Uuid,
         datetime: Option<DateTime<Utc>>,
-        ort: Option<&'a str>,
+        ort: Option<&str>,
         typ: Option<SitzungTyp>,
         antragsfrist: Option<DateTime<Utc>>,
         legislatur_periode: Option<Uuid>,
     ) -> Result<Option<Sitzung>>;
 
+    async fn set_sitzung_status(
+        &mut self,
+        id: Uuid,
+        status: SitzungStatus,
+        grund: Option<&str>,
+    ) -> Result<Option<Sitzung>>;
+
     async fn update_top<'a>(
         &mut self,
         id: Uuid,
@@ -123,4 +285,45 @@ pub trait SitzungRepo {
     async fn delete_sitzung(&mut self, id: Uuid) -> Result<Option<Sitzung>>;
 
     async fn delete_top(&mut self, id: Uuid) -> Result<Option<Top>>;
+
+    async fn add_anhang_to_top(&mut self, top_id: Uuid, anhang_id: Uuid) -> Result<()>;
+
+    async fn delete_anhang_from_top(&mut self, top_id: Uuid, anhang_id: Uuid) -> Result<()>;
+
+    async fn add_anhang_to_sitzung(&mut self, sitzung_id: Uuid, anhang_id: Uuid) -> Result<()>;
+
+    async fn delete_anhang_from_sitzung(&mut self, sitzung_id: Uuid, anhang_id: Uuid)
+        -> Result<()>;
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, NaiveDate, NaiveTime, Weekday};
+
+    use super::SerienRegel;
+
+    #[test]
+    fn serien_termine() {
+        let regel = SerienRegel {
+            start: NaiveDate::from_ymd_opt(2024, 10, 1).unwrap(),
+            ende: NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(),
+            wochentag: Weekday::Wed,
+            uhrzeit: NaiveTime::from_hms_opt(18, 30, 0).unwrap(),
+            zeitzone: chrono_tz::Europe::Berlin,
+            ausnahmen: vec![NaiveDate::from_ymd_opt(2024, 10, 16).unwrap()],
+        };
+
+        let termine = regel.termine();
+
+        assert_eq!(
+            termine,
+            vec![
+                DateTime::parse_from_rfc3339("2024-10-02T18:30:00+02:00").unwrap(),
+                DateTime::parse_from_rfc3339("2024-10-09T18:30:00+02:00").unwrap(),
+                DateTime::parse_from_rfc3339("2024-10-23T18:30:00+02:00").unwrap(),
+                // daylight saving time ends on the 27th
+                DateTime::parse_from_rfc3339("2024-10-30T18:30:00+01:00").unwrap(),
+            ]
+        );
+    }
 }
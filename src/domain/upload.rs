@@ -0,0 +1,31 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::Result;
+
+/// A file uploaded in chunks, which becomes a staged Anhang once all of it arrived
+#[derive(Debug, Serialize, IntoParams, ToSchema)]
+pub struct Upload {
+    pub id: Uuid,
+    pub person_id: Uuid,
+    pub filename: String,
+    /// Size of the whole file in bytes
+    pub size: i64,
+    pub erstellt_am: DateTime<Utc>,
+}
+
+/// Uploads that are not complete after this are considered abandoned
+pub const UPLOAD_TIMEOUT: TimeDelta = TimeDelta::days(1);
+
+pub trait UploadRepo {
+    async fn create_upload(&mut self, person_id: Uuid, filename: &str, size: i64)
+        -> Result<Upload>;
+
+    async fn upload_by_id(&mut self, id: Uuid) -> Result<Option<Upload>>;
+
+    async fn delete_upload(&mut self, id: Uuid) -> Result<Option<Upload>>;
+
+    async fn uploads_before(&mut self, erstellt_vor: DateTime<Utc>) -> Result<Vec<Upload>>;
+}
@@ -0,0 +1,196 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use log::{error, info};
+
+use crate::{
+    database::DatabasePool,
+    domain::{
+        self,
+        session::SessionRepo,
+        sitzung::SitzungRepo,
+        upload::{UploadRepo, UPLOAD_TIMEOUT},
+        Result,
+    },
+    storage::{partial::PartialUploads, Storage},
+    ARGS,
+};
+
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(60 * 15);
+
+const SESSION_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const ANHANG_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const UPLOAD_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const TEXT_EXTRACTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Runs the job every interval, starting right away. Errors are logged and the job is tried
+/// again at the next tick
+async fn run_periodically<F, Fut>(interval: Duration, name: &str, mut job: F) -> !
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut interval = actix_web::rt::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = job().await {
+            error!("failed to {}: {:?}", name, e);
+        }
+    }
+}
+
+/// Periodically puts orphaned Anträge onto the agenda of the next Sitzung, once its Antragsfrist
+/// has passed
+pub async fn schedule_orphan_antraege(database: DatabasePool) {
+    run_periodically(SCHEDULE_INTERVAL, "schedule orphan anträge", || {
+        try_schedule_orphan_antraege(&database)
+    })
+    .await
+}
+
+async fn try_schedule_orphan_antraege(database: &DatabasePool) -> Result<()> {
+    let now = Utc::now();
+
+    let mut transaction = database.start_transaction().await?;
+
+    let Some(sitzung) = transaction.next_scheduled_sitzung(now).await? else {
+        return Ok(());
+    };
+
+    // wait until no new anträge can be submitted for this sitzung
+    if sitzung.antragsfrist > now {
+        return Ok(());
+    }
+
+    let Some(plan) = domain::schedule_orphan_antraege(&mut *transaction, now, false, false).await?
+    else {
+        return Ok(());
+    };
+
+    for top in &plan.tops {
+        info!(
+            "scheduled {} anträge as top '{}' on sitzung {}",
+            top.antraege.len(),
+            top.name,
+            plan.sitzung.id
+        );
+    }
+
+    transaction.commit().await
+}
+
+/// Periodically removes sessions that ran into their idle or absolute timeout
+pub async fn delete_inactive_sessions(database: DatabasePool) {
+    run_periodically(SESSION_CLEANUP_INTERVAL, "delete inactive sessions", || {
+        try_delete_inactive_sessions(&database)
+    })
+    .await
+}
+
+async fn try_delete_inactive_sessions(database: &DatabasePool) -> Result<()> {
+    let mut conn = database.aquire().await?;
+
+    let deleted = conn
+        .delete_inactive_sessions(Utc::now(), ARGS.session_idle_timeout())
+        .await?;
+
+    if deleted > 0 {
+        info!("deleted {} inactive sessions", deleted);
+    }
+
+    Ok(())
+}
+
+/// Periodically removes Anhänge that are not attached to anything anymore, failed uploads and
+/// stored files that belong to no Anhang
+pub async fn delete_unused_anhaenge(database: DatabasePool, storage: Arc<Storage>) {
+    run_periodically(ANHANG_CLEANUP_INTERVAL, "delete unused attachments", || {
+        try_delete_unused_anhaenge(&database, &storage)
+    })
+    .await
+}
+
+async fn try_delete_unused_anhaenge(database: &DatabasePool, storage: &Storage) -> Result<()> {
+    let mut conn = database.aquire().await?;
+
+    let deleted = domain::delete_unused_anhaenge(&mut *conn, storage, Utc::now()).await?;
+
+    if !deleted.is_empty() {
+        info!("deleted {} unused attachments", deleted.len());
+    }
+
+    let reconciliation = domain::reconcile_anhaenge(&mut *conn, storage, Utc::now()).await?;
+
+    if !reconciliation.deleted_blobs.is_empty() {
+        info!(
+            "deleted {} stored files without an attachment",
+            reconciliation.deleted_blobs.len()
+        );
+    }
+
+    for anhang in &reconciliation.missing_blobs {
+        error!("the content of attachment {} is missing", anhang.id);
+    }
+
+    Ok(())
+}
+
+/// Periodically extracts the text of new Anhänge for the full-text search
+pub async fn extract_anhang_texts(database: DatabasePool, storage: Arc<Storage>) {
+    run_periodically(TEXT_EXTRACTION_INTERVAL, "extract attachment texts", || {
+        try_extract_anhang_texts(&database, &storage)
+    })
+    .await
+}
+
+async fn try_extract_anhang_texts(database: &DatabasePool, storage: &Storage) -> Result<()> {
+    let mut conn = database.aquire().await?;
+
+    let extracted = domain::extract_missing_anhang_texts(&mut *conn, storage).await?;
+
+    if extracted > 0 {
+        info!("extracted the text of {} attachments", extracted);
+    }
+
+    Ok(())
+}
+
+/// Periodically removes chunked uploads that were not completed in time
+pub async fn delete_abandoned_uploads(
+    database: DatabasePool,
+    partial_uploads: Arc<PartialUploads>,
+) {
+    run_periodically(UPLOAD_CLEANUP_INTERVAL, "delete abandoned uploads", || {
+        try_delete_abandoned_uploads(&database, &partial_uploads)
+    })
+    .await
+}
+
+async fn try_delete_abandoned_uploads(
+    database: &DatabasePool,
+    partial_uploads: &PartialUploads,
+) -> Result<()> {
+    let mut conn = database.aquire().await?;
+
+    let abandoned = conn.uploads_before(Utc::now() - UPLOAD_TIMEOUT).await?;
+
+    for upload in &abandoned {
+        let _guard = partial_uploads.lock(upload.id).await;
+
+        partial_uploads.delete(upload.id).await?;
+        conn.delete_upload(upload.id).await?;
+    }
+
+    if !abandoned.is_empty() {
+        info!("deleted {} abandoned uploads", abandoned.len());
+    }
+
+    Ok(())
+}
@@ -1,17 +1,27 @@
 #![warn(clippy::shadow_unrelated)]
 
-use async_std::{fs, path::PathBuf, sync::RwLock};
+use async_std::{path::PathBuf, sync::RwLock};
 use clap::Parser;
 use domain::templates::TemplatesRepo;
-use log::LevelFilter;
-use std::{error::Error, str::FromStr, sync::LazyLock};
+use log::{info, LevelFilter};
+use std::{
+    error::Error,
+    str::FromStr,
+    sync::{Arc, LazyLock},
+};
 
 mod cache;
 mod database;
 mod domain;
+mod jobs;
+mod storage;
 mod web;
 
-use crate::{database::DatabasePool, domain::Capability};
+use crate::{
+    database::DatabasePool,
+    domain::Capability,
+    storage::{partial::PartialUploads, Storage, StorageBackend},
+};
 
 #[derive(Parser)]
 struct Args {
@@ -43,6 +53,22 @@ struct Args {
     /// Oauth Url to get user info from
     #[arg(short, long, requires_all = ["auth_url", "token_url", "oauth_source_name"])]
     user_info: Option<String>,
+    /// Configure an additional OAuth provider, formatted like
+    /// 'name=auth-url,token-url,user-info-url'. Its client credentials are read from the
+    /// NAME_CLIENT_ID and NAME_CLIENT_SECRET environment variables. Can be specified multiple times
+    #[arg(long = "oauth-provider", value_parser = parse_key_val::<String, String>)]
+    oauth_providers: Vec<(String, String)>,
+    /// OIDC issuer whose signed bearer tokens are verified locally, instead of asking the user
+    /// info endpoint on every request. Discovery document and signing keys are fetched from it
+    #[arg(long)]
+    oidc_issuer: Option<String>,
+    /// Audience expected in tokens verified using --oidc-issuer. Defaults to CLIENT_ID
+    #[arg(long, requires = "oidc_issuer")]
+    oidc_audience: Option<String>,
+    /// Name of the OAuth provider whose users sign in at --oidc-issuer, so their tokens map to
+    /// the same Persons. Defaults to OAUTH_SOURCE_NAME
+    #[arg(long, requires = "oidc_issuer")]
+    oidc_provider: Option<String>,
     /// Specifiy a group and grant it capabilities.. Parameter should be formatted like
     /// 'GroupName=CapName[,CapName]'
     #[arg(long = "group", value_parser = parse_key_val::<String, String>)]
@@ -50,6 +76,12 @@ struct Args {
     /// Specify Capabilities to be granted to Users that arent logged in
     #[arg(long = "default-capability")]
     default_capabilities: Vec<Capability>,
+    /// Hours after which an unused login session expires
+    #[arg(long, default_value_t = 24 * 7)]
+    session_idle_timeout: i64,
+    /// Days after which a login session expires, even if it is still used
+    #[arg(long, default_value_t = 30)]
+    session_max_age: i64,
 
     /// How many web workers to spawn. Default is the number of CPU cores
     #[arg(short = 'j', long)]
@@ -64,9 +96,57 @@ struct Args {
     /// Define the max file size for uploads in bytes
     #[arg(long, default_value_t = 1024 * 1024 * 10)]
     max_file_size: usize,
+    /// Allow users with a capability to upload larger files, formatted like
+    /// 'ManageSitzungen=104857600'. Users get the highest limit of their capabilities, or
+    /// --max-file-size if none of them has one. Can be specified multiple times
+    #[arg(long = "max-file-size-for", value_parser = parse_key_val::<Capability, usize>)]
+    max_file_sizes: Vec<(Capability, usize)>,
+    /// Define a MIME type that may be uploaded as attachment, like 'application/pdf' or
+    /// 'image/*'. Can be specified multiple times and replaces the defaults
+    #[arg(
+        long = "allowed-attachment-type",
+        default_values = [
+            "application/pdf",
+            "image/png",
+            "image/jpeg",
+            "image/gif",
+            "image/webp",
+            "text/plain",
+            "text/csv",
+            "application/zip",
+            "application/vnd.oasis.opendocument.text",
+            "application/vnd.oasis.opendocument.spreadsheet",
+            "application/vnd.oasis.opendocument.presentation",
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        ]
+    )]
+    allowed_attachment_types: Vec<String>,
     /// Define the datadir for the uploads
     #[arg(long)]
     data_dir: PathBuf,
+    /// Where to store attachments. The s3 storage reads its credentials from the
+    /// S3_ACCESS_KEY_ID and S3_SECRET_ACCESS_KEY environment variables
+    #[arg(long, value_enum, default_value_t = StorageBackend::Filesystem)]
+    attachment_storage: StorageBackend,
+    /// Url of the S3 compatible object storage, like 'https://s3.example.org'
+    #[arg(long)]
+    s3_endpoint: Option<String>,
+    /// Bucket to store attachments in
+    #[arg(long)]
+    s3_bucket: Option<String>,
+    /// Region of the S3 bucket
+    #[arg(long, default_value_t = {"us-east-1".to_string()})]
+    s3_region: String,
+    /// Move all attachments from --attachment-storage to this storage and exit. Afterwards start
+    /// the server with --attachment-storage set to this storage
+    #[arg(long, value_enum)]
+    migrate_attachments_to: Option<StorageBackend>,
+    /// Periodically put orphaned Anträge onto the agenda of the next Sitzung, once its
+    /// Antragsfrist has passed
+    #[arg(long)]
+    auto_schedule_antraege: bool,
 }
 
 fn parse_key_val<T, U>(s: &str) -> Result<(T, U), Box<dyn Error + Send + Sync + 'static>>
@@ -88,7 +168,37 @@ struct ContentDir {
     protected: PathBuf,
 }
 
+impl Args {
+    fn session_idle_timeout(&self) -> chrono::TimeDelta {
+        chrono::TimeDelta::hours(self.session_idle_timeout)
+    }
+
+    fn session_max_age(&self) -> chrono::TimeDelta {
+        chrono::TimeDelta::days(self.session_max_age)
+    }
+
+    /// The largest file anyone may upload
+    fn largest_max_file_size(&self) -> usize {
+        self.max_file_sizes
+            .iter()
+            .map(|(_, size)| *size)
+            .fold(self.max_file_size, usize::max)
+    }
+}
+
+#[cfg(not(test))]
 static ARGS: LazyLock<Args> = LazyLock::new(Args::parse);
+/// The arguments of the test harness are not ours, so tests run with the defaults
+#[cfg(test)]
+static ARGS: LazyLock<Args> = LazyLock::new(|| {
+    Args::parse_from([
+        "fscs-website-backend",
+        "--content-dir",
+        ".",
+        "--data-dir",
+        ".",
+    ])
+});
 static CONTENT_DIR: LazyLock<ContentDir> = LazyLock::new(|| ContentDir {
     public: ARGS.content_dir.join("public"),
     hidden: ARGS.content_dir.join("hidden"),
@@ -96,6 +206,8 @@ static CONTENT_DIR: LazyLock<ContentDir> = LazyLock::new(|| ContentDir {
 });
 
 static UPLOAD_DIR: LazyLock<PathBuf> = LazyLock::new(|| ARGS.data_dir.join("uploads/attachments/"));
+static PARTIAL_UPLOAD_DIR: LazyLock<PathBuf> =
+    LazyLock::new(|| ARGS.data_dir.join("uploads/partial/"));
 
 static TEMPLATE_ENGINE: LazyLock<RwLock<upon::Engine>> =
     LazyLock::new(|| RwLock::new(upon::Engine::new()));
@@ -109,8 +221,6 @@ async fn main() -> domain::Result<()> {
         )
         .init();
 
-    fs::create_dir_all(UPLOAD_DIR.as_path()).await?;
-
     let database_url = ARGS
         .database_url
         .clone()
@@ -128,6 +238,28 @@ async fn main() -> domain::Result<()> {
         .map_err(|e| domain::Error::Message(format!("error while running migrations: {:?}", e)))?;
     transaction.commit().await?;
 
+    let storage = Arc::new(Storage::new(ARGS.attachment_storage).await?);
+
+    if let Some(target) = ARGS.migrate_attachments_to {
+        if target == ARGS.attachment_storage {
+            return Err(domain::Error::Message(format!(
+                "the attachments are already stored in {target:?}, choose another storage to migrate them to"
+            )));
+        }
+
+        let moved = domain::move_anhaenge(
+            &mut *database.aquire().await?,
+            &*storage,
+            &Storage::new(target).await?,
+            &PARTIAL_UPLOAD_DIR,
+        )
+        .await?;
+
+        info!("moved {moved} attachments to {target:?}");
+
+        return Ok(());
+    }
+
     let templates = database.aquire().await?.templates().await?;
 
     for template in templates {
@@ -137,5 +269,25 @@ async fn main() -> domain::Result<()> {
             .add_template(template.name, template.inhalt)?;
     }
 
-    web::start_server(database).await
+    if ARGS.auto_schedule_antraege {
+        actix_web::rt::spawn(jobs::schedule_orphan_antraege(database.clone()));
+    }
+
+    let partial_uploads = Arc::new(PartialUploads::new(PARTIAL_UPLOAD_DIR.clone()).await?);
+
+    actix_web::rt::spawn(jobs::delete_inactive_sessions(database.clone()));
+    actix_web::rt::spawn(jobs::delete_unused_anhaenge(
+        database.clone(),
+        storage.clone(),
+    ));
+    actix_web::rt::spawn(jobs::extract_anhang_texts(
+        database.clone(),
+        storage.clone(),
+    ));
+    actix_web::rt::spawn(jobs::delete_abandoned_uploads(
+        database.clone(),
+        partial_uploads.clone(),
+    ));
+
+    web::start_server(database, storage, partial_uploads).await
 }
@@ -0,0 +1,82 @@
+use std::ops::Range;
+
+use async_std::io::{prelude::SeekExt, ErrorKind, ReadExt, SeekFrom};
+use async_std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use futures_util::TryStreamExt;
+
+use crate::domain::blob_store::{BlobStore, BlobStream};
+use crate::domain::Result;
+
+/// Stores every blob as a file named like its key
+pub struct FilesystemStore {
+    dir: PathBuf,
+}
+
+impl FilesystemStore {
+    pub async fn new(dir: PathBuf) -> Result<FilesystemStore> {
+        fs::create_dir_all(&dir).await?;
+
+        Ok(FilesystemStore { dir })
+    }
+}
+
+impl BlobStore for FilesystemStore {
+    async fn put(&self, key: &str, content: Vec<u8>) -> Result<()> {
+        fs::write(self.dir.join(key), content).await?;
+
+        Ok(())
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<()> {
+        fs::copy(path, self.dir.join(key)).await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.dir.join(key)).await {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_stream(&self, key: &str, range: Option<Range<u64>>) -> Result<Option<BlobStream>> {
+        let mut file = match fs::File::open(self.dir.join(key)).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let length = match range {
+            Some(range) => {
+                file.seek(SeekFrom::Start(range.start)).await?;
+                range.end - range.start
+            }
+            None => file.metadata().await?.len(),
+        };
+
+        Ok(Some(Box::pin(super::read_stream(file.take(length)))))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match fs::remove_file(self.dir.join(key)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let mut entries = fs::read_dir(&self.dir).await?;
+        let mut keys = vec![];
+
+        while let Some(entry) = entries.try_next().await? {
+            keys.push(entry.file_name().to_string_lossy().into_owned());
+        }
+
+        Ok(keys)
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Mutex;
+
+use async_std::{fs, path::Path};
+use bytes::Bytes;
+use futures_util::stream;
+
+use crate::domain::blob_store::{BlobStore, BlobStream};
+use crate::domain::Result;
+
+/// Keeps all blobs in memory, for tests
+#[derive(Default)]
+pub struct MemoryStore {
+    blobs: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl BlobStore for MemoryStore {
+    async fn put(&self, key: &str, content: Vec<u8>) -> Result<()> {
+        self.blobs.lock().unwrap().insert(key.to_string(), content);
+
+        Ok(())
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<()> {
+        self.put(key, fs::read(path).await?).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.blobs.lock().unwrap().get(key).cloned())
+    }
+
+    async fn get_stream(&self, key: &str, range: Option<Range<u64>>) -> Result<Option<BlobStream>> {
+        let Some(content) = self.get(key).await? else {
+            return Ok(None);
+        };
+
+        let content = match range {
+            Some(range) => content[range.start as usize..range.end as usize].to_vec(),
+            None => content,
+        };
+
+        Ok(Some(Box::pin(stream::iter([Ok(Bytes::from(content))]))))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.blobs.lock().unwrap().remove(key);
+
+        Ok(())
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        Ok(self.blobs.lock().unwrap().keys().cloned().collect())
+    }
+}
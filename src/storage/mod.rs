@@ -0,0 +1,133 @@
+use std::ops::Range;
+
+use async_std::io::{Read, ReadExt};
+use async_std::path::Path;
+use bytes::Bytes;
+use clap::ValueEnum;
+use futures_util::{stream, Stream};
+
+use crate::domain::blob_store::{BlobStore, BlobStream};
+use crate::domain::Result;
+use crate::{ARGS, UPLOAD_DIR};
+
+pub mod filesystem;
+#[cfg(test)]
+pub mod memory;
+pub mod partial;
+pub mod s3;
+
+use filesystem::FilesystemStore;
+use s3::S3Store;
+
+/// How much of a blob is read at once while streaming it
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Where attachments are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StorageBackend {
+    /// In the uploads directory inside the data dir
+    Filesystem,
+    /// In a bucket of an S3 compatible object storage
+    S3,
+}
+
+pub enum Storage {
+    Filesystem(FilesystemStore),
+    S3(S3Store),
+}
+
+impl Storage {
+    /// Creates the storage for the backend, configured by the command line arguments
+    pub async fn new(backend: StorageBackend) -> Result<Storage> {
+        match backend {
+            StorageBackend::Filesystem => Ok(Storage::Filesystem(
+                FilesystemStore::new(UPLOAD_DIR.clone()).await?,
+            )),
+            StorageBackend::S3 => {
+                let (Some(endpoint), Some(bucket)) = (&ARGS.s3_endpoint, &ARGS.s3_bucket) else {
+                    return Err(crate::domain::Error::Message(
+                        "the s3 storage needs --s3-endpoint and --s3-bucket".to_string(),
+                    ));
+                };
+
+                let (Ok(access_key_id), Ok(secret_access_key)) = (
+                    std::env::var("S3_ACCESS_KEY_ID"),
+                    std::env::var("S3_SECRET_ACCESS_KEY"),
+                ) else {
+                    return Err(crate::domain::Error::Message(
+                        "the s3 storage needs S3_ACCESS_KEY_ID and S3_SECRET_ACCESS_KEY to be set"
+                            .to_string(),
+                    ));
+                };
+
+                Ok(Storage::S3(S3Store::new(
+                    endpoint,
+                    bucket,
+                    &ARGS.s3_region,
+                    &access_key_id,
+                    &secret_access_key,
+                )?))
+            }
+        }
+    }
+}
+
+impl BlobStore for Storage {
+    async fn put(&self, key: &str, content: Vec<u8>) -> Result<()> {
+        match self {
+            Storage::Filesystem(store) => store.put(key, content).await,
+            Storage::S3(store) => store.put(key, content).await,
+        }
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> Result<()> {
+        match self {
+            Storage::Filesystem(store) => store.put_file(key, path).await,
+            Storage::S3(store) => store.put_file(key, path).await,
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match self {
+            Storage::Filesystem(store) => store.get(key).await,
+            Storage::S3(store) => store.get(key).await,
+        }
+    }
+
+    async fn get_stream(&self, key: &str, range: Option<Range<u64>>) -> Result<Option<BlobStream>> {
+        match self {
+            Storage::Filesystem(store) => store.get_stream(key, range).await,
+            Storage::S3(store) => store.get_stream(key, range).await,
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match self {
+            Storage::Filesystem(store) => store.delete(key).await,
+            Storage::S3(store) => store.delete(key).await,
+        }
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        match self {
+            Storage::Filesystem(store) => store.keys().await,
+            Storage::S3(store) => store.keys().await,
+        }
+    }
+}
+
+/// Streams everything the reader returns, in chunks of at most `CHUNK_SIZE` bytes
+fn read_stream<R: Read + Unpin>(reader: R) -> impl Stream<Item = Result<Bytes>> {
+    stream::try_unfold(reader, |mut reader| async move {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let read = reader.read(&mut chunk).await?;
+
+        if read == 0 {
+            return Ok(None);
+        }
+
+        chunk.truncate(read);
+
+        Ok(Some((Bytes::from(chunk), reader)))
+    })
+}
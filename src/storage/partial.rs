@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_std::fs::{self, OpenOptions};
+use async_std::io::{ErrorKind, SeekExt, SeekFrom, WriteExt};
+use async_std::path::PathBuf;
+use async_std::sync::{Mutex as AsyncMutex, MutexGuardArc};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use uuid::Uuid;
+
+use crate::domain::Result;
+
+/// Keeps the content of chunked uploads as local files until all of it arrived
+pub struct PartialUploads {
+    dir: PathBuf,
+    locks: Mutex<HashMap<Uuid, Arc<AsyncMutex<()>>>>,
+}
+
+impl PartialUploads {
+    pub async fn new(dir: PathBuf) -> Result<PartialUploads> {
+        fs::create_dir_all(&dir).await?;
+
+        Ok(PartialUploads {
+            dir,
+            locks: Mutex::default(),
+        })
+    }
+
+    /// Waits until no one else works on the upload, and keeps it that way until the guard is
+    /// dropped. Only guards against other requests of this process
+    pub async fn lock(&self, id: Uuid) -> MutexGuardArc<()> {
+        let lock = self.locks.lock().unwrap().entry(id).or_default().clone();
+
+        lock.lock_arc().await
+    }
+
+    pub fn path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(id.to_string())
+    }
+
+    pub async fn create(&self, id: Uuid) -> Result<()> {
+        fs::write(self.path(id), []).await?;
+
+        Ok(())
+    }
+
+    /// How many bytes of the upload arrived so far
+    pub async fn offset(&self, id: Uuid) -> Result<Option<u64>> {
+        match fs::metadata(self.path(id)).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Appends a chunk to the upload, which has to end at `offset`, and returns the offset after it.
+    /// Nothing is kept if the chunk is larger than what is left of `size`.
+    ///
+    /// If the chunk breaks off, everything that arrived until then is kept, so the client can
+    /// resume from there. Chunks of the same upload must not be written concurrently, see `lock`.
+    pub async fn write(
+        &self,
+        id: Uuid,
+        offset: u64,
+        size: u64,
+        mut chunk: impl Stream<Item = std::io::Result<Bytes>> + Unpin,
+    ) -> Result<Option<u64>> {
+        let mut file = OpenOptions::new().write(true).open(self.path(id)).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+
+        let mut new_offset = offset;
+
+        while let Some(bytes) = chunk.next().await {
+            let bytes = match bytes {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    file.flush().await?;
+                    return Err(e.into());
+                }
+            };
+
+            new_offset += bytes.len() as u64;
+
+            if new_offset > size {
+                file.set_len(offset).await?;
+                return Ok(None);
+            }
+
+            file.write_all(&bytes).await?;
+        }
+
+        file.flush().await?;
+
+        Ok(Some(new_offset))
+    }
+
+    pub async fn delete(&self, id: Uuid) -> Result<()> {
+        self.locks.lock().unwrap().remove(&id);
+
+        match fs::remove_file(self.path(id)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_std::path::PathBuf;
+    use bytes::Bytes;
+    use futures_util::stream;
+    use uuid::Uuid;
+
+    use super::PartialUploads;
+
+    #[tokio::test]
+    async fn writes_chunks() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let uploads = PartialUploads::new(PathBuf::from(&dir)).await?;
+        let id = Uuid::new_v4();
+
+        assert_eq!(uploads.offset(id).await?, None);
+
+        uploads.create(id).await?;
+
+        let chunk = stream::iter([Ok(Bytes::from("Hallo ")), Ok(Bytes::from("Welt"))]);
+        assert_eq!(uploads.write(id, 0, 12, chunk).await?, Some(10));
+
+        // a chunk larger than the rest of the upload is dropped completely
+        let too_large = stream::iter([Ok(Bytes::from("!")), Ok(Bytes::from("!!"))]);
+        assert_eq!(uploads.write(id, 10, 12, too_large).await?, None);
+        assert_eq!(uploads.offset(id).await?, Some(10));
+
+        let rest = stream::iter([Ok(Bytes::from("!!"))]);
+        assert_eq!(uploads.write(id, 10, 12, rest).await?, Some(12));
+        assert_eq!(
+            async_std::fs::read(uploads.path(id)).await?,
+            b"Hallo Welt!!"
+        );
+
+        uploads.delete(id).await?;
+        assert_eq!(uploads.offset(id).await?, None);
+
+        async_std::fs::remove_dir(&dir).await?;
+
+        Ok(())
+    }
+}
@@ -1,33 +1,38 @@
-use actix_http::{header, StatusCode};
-use actix_multipart::form::{tempfile::TempFile, MultipartForm};
+use actix_multipart::form::MultipartForm;
 use actix_web::{
-    delete, get, patch, post,
-    web::{self, Path},
-    HttpResponse, Responder, Scope,
+    delete, get, patch, post, put,
+    web::{self, Data, Path},
+    HttpRequest, HttpResponse, Responder, Scope,
 };
-use actix_web_validator::Json as ActixJson;
-use async_std::fs;
-use chrono::Utc;
-use log::debug;
+use actix_web_validator::{Json as ActixJson, Query};
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
-    database::{DatabaseConnection, DatabaseTransaction},
+    database::{DatabaseConnection, DatabasePool, DatabaseTransaction},
     domain::{
         self,
-        anhang::AnhangRepo,
-        antrag::{Antrag, AntragRepo},
-        antrag_top_attachment_map::AntragTopAttachmentMap,
-        Capability, Result,
+        anhang::{Anhang, AnhangRepo},
+        antrag::{
+            Antrag, AntragBerechtigung, AntragFilter, AntragKommentar, AntragRepo, AntragSort,
+            AntragsRolle, Sichtbarkeit,
+        },
+        antrag_top_attachment_map::{AntragTopAttachmentMap, OrphanPlan},
+        pagination::SortOrder,
+        persons::PersonRepo,
+        Result,
     },
+    storage::Storage,
     web::{
+        api::attachments::{self, AttachmentParams, UploadAnhang},
         auth::{self, User},
-        cors_permissive, cors_restrictive, RestStatus,
+        cors_permissive, cors_restrictive,
+        pagination::{Paginated, PaginationParams},
+        RestStatus,
     },
-    ARGS, UPLOAD_DIR,
 };
 
 /// Create the antrags service under /antraege
@@ -35,7 +40,8 @@ pub(crate) fn service() -> Scope {
     let scope = web::scope("/antraege")
         .service(get_antraege)
         .service(create_antrag)
-        .service(get_orphan_antraege);
+        .service(get_orphan_antraege)
+        .service(schedule_orphan_antraege);
 
     // must come last
     register_antrag_id_service(scope)
@@ -43,9 +49,16 @@ pub(crate) fn service() -> Scope {
 
 fn register_antrag_id_service(parent: Scope) -> Scope {
     parent
+        .service(get_antrag_attachments)
         .service(get_antrag_attachment)
         .service(add_antrag_attachment)
+        .service(put_antrag_attachment)
         .service(delete_antrag_attachment)
+        .service(get_antrag_berechtigungen)
+        .service(put_antrag_berechtigung)
+        .service(delete_antrag_berechtigung)
+        .service(get_antrag_kommentare)
+        .service(create_antrag_kommentar)
         .service(get_antrag_by_id)
         .service(patch_antrag)
         .service(delete_antrag)
@@ -59,6 +72,8 @@ pub struct CreateAntragParams {
     antragstext: String,
     #[validate(length(min = 1))]
     titel: String,
+    #[serde(default)]
+    sichtbarkeit: Sichtbarkeit,
 }
 
 #[derive(Debug, IntoParams, Deserialize, ToSchema, Validate)]
@@ -69,25 +84,79 @@ pub struct UpdateAntragParams {
     antragstext: Option<String>,
     #[validate(length(min = 1))]
     titel: Option<String>,
+    sichtbarkeit: Option<Sichtbarkeit>,
 }
 
-#[derive(MultipartForm)]
-pub struct UploadAntrag {
-    file: TempFile,
+#[derive(Debug, IntoParams, Deserialize, ToSchema, Validate)]
+pub struct ScheduleOrphansParams {
+    /// Only return the planned Tops, without creating them
+    #[serde(default)]
+    dry_run: bool,
+    /// Put all Anträge into a single Top instead of creating one per Antrag
+    #[serde(default)]
+    group: bool,
+}
+
+#[derive(Debug, IntoParams, Deserialize, ToSchema, Validate)]
+pub struct AntraegeParams {
+    /// Only Anträge this person is one of the Antragstellenden of
+    ersteller: Option<Uuid>,
+    /// Only Anträge created at or after this time
+    erstellt_ab: Option<DateTime<Utc>>,
+    /// Only Anträge created at or before this time
+    erstellt_bis: Option<DateTime<Utc>>,
+    sichtbarkeit: Option<Sichtbarkeit>,
+    #[serde(default)]
+    sort: AntragSort,
+    #[serde(default)]
+    order: SortOrder,
+}
+
+#[derive(Debug, IntoParams, Deserialize, ToSchema, Validate)]
+pub struct BerechtigungParams {
+    rolle: AntragsRolle,
+}
+
+#[derive(Debug, IntoParams, Deserialize, ToSchema, Validate)]
+pub struct KommentarParams {
+    #[validate(length(min = 1))]
+    text: String,
 }
 
 #[utoipa::path(
     path = "/api/antraege",
+    params(AntraegeParams, PaginationParams),
     responses(
-        (status = 200, description = "Success", body = Vec<Antrag>),
+        (status = 200, description = "Success", body = Vec<Antrag>, headers(
+            ("Link" = String, description = "Links to the first, previous, next and last page"),
+            ("X-Total-Count" = i64, description = "How many items there are on all pages"),
+        )),
+        (status = 400, description = "Bad Request"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
 #[get("", wrap = "cors_permissive()")]
-async fn get_antraege(mut conn: DatabaseConnection) -> Result<impl Responder> {
-    let result = conn.antraege().await?;
+async fn get_antraege(
+    user: User,
+    params: Query<AntraegeParams>,
+    pagination: Query<PaginationParams>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    let filter = AntragFilter {
+        ersteller: params.ersteller,
+        erstellt_ab: params.erstellt_ab,
+        erstellt_bis: params.erstellt_bis,
+        sichtbarkeit: params.sichtbarkeit,
+        sort: params.sort,
+        order: params.order,
+    };
+    let pagination = pagination.pagination();
 
-    Ok(RestStatus::Success(Some(result)))
+    let result = conn.antraege(&betrachter, &filter, pagination).await?;
+
+    Ok(Paginated::new(result, pagination))
 }
 
 #[utoipa::path(
@@ -98,12 +167,48 @@ async fn get_antraege(mut conn: DatabaseConnection) -> Result<impl Responder> {
     )
 )]
 #[get("/orphans", wrap = "cors_permissive()")]
-async fn get_orphan_antraege(mut conn: DatabaseConnection) -> Result<impl Responder> {
-    let result = conn.orphan_antraege().await?;
+async fn get_orphan_antraege(user: User, mut conn: DatabaseConnection) -> Result<impl Responder> {
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    let antraege = conn.orphan_antraege().await?;
+    let result = domain::visible_antraege(&mut *conn, &betrachter, antraege).await?;
 
     Ok(RestStatus::Success(Some(result)))
 }
 
+#[utoipa::path(
+    path = "/api/antraege/orphans/schedule",
+    params(ScheduleOrphansParams),
+    responses(
+        (status = 200, description = "Success", body = OrphanPlan),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[post(
+    "/orphans/schedule",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn schedule_orphan_antraege(
+    params: Query<ScheduleOrphansParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let result = domain::schedule_orphan_antraege(
+        &mut *transaction,
+        Utc::now(),
+        params.group,
+        params.dry_run,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
+
 #[utoipa::path(
     path = "/api/antraege/{antrag_id}",
     responses(
@@ -114,12 +219,22 @@ async fn get_orphan_antraege(mut conn: DatabaseConnection) -> Result<impl Respon
 )]
 #[get("/{antrag_id}", wrap = "cors_permissive()")]
 async fn get_antrag_by_id(
+    user: User,
     antrag_id: Path<Uuid>,
     mut conn: DatabaseConnection,
 ) -> Result<impl Responder> {
-    let result = conn.antrag_by_id(*antrag_id).await?;
+    let Some(antrag) = conn.antrag_by_id(*antrag_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
 
-    Ok(RestStatus::Success(result))
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    // hidden Anträge are indistinguishable from missing ones
+    if !domain::can_view_antrag(&mut *conn, &betrachter, &antrag).await? {
+        return Ok(RestStatus::NotFound);
+    }
+
+    Ok(RestStatus::Success(Some(antrag)))
 }
 
 #[utoipa::path(
@@ -151,6 +266,7 @@ async fn create_antrag(
             &params.begruendung,
             &params.antragstext,
             Utc::now(),
+            params.sichtbarkeit,
         )
         .await?;
 
@@ -181,19 +297,20 @@ async fn patch_antrag(
     antrag_id: Path<Uuid>,
     mut transaction: DatabaseTransaction<'_>,
 ) -> Result<impl Responder> {
-    let person = user.query_person(&mut *transaction).await?;
+    let betrachter = user.antrag_betrachter(&mut *transaction).await?;
+
+    let Some(antrag) = transaction.antrag_by_id(*antrag_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    domain::ensure_can_modify_antrag(&mut *transaction, &betrachter, &antrag).await?;
 
-    if !user.has_capability(Capability::ManageAntraege) {
-        let Some(antrag) = transaction.antrag_by_id(*antrag_id).await? else {
-            return Ok(RestStatus::NotFound);
-        };
-
-        if !domain::can_person_modify_antrag(&mut *transaction, &person, &antrag).await? {
-            return Ok(RestStatus::Status(
-                StatusCode::UNAUTHORIZED,
-                "you are not allowed to edit this antrag".to_string(),
-            ));
-        }
+    if let Some(sichtbarkeit) = params.sichtbarkeit {
+        domain::ensure_can_change_antrag_sichtbarkeit(&betrachter, &antrag)?;
+
+        transaction
+            .set_antrag_sichtbarkeit(*antrag_id, sichtbarkeit)
+            .await?;
     }
 
     let result = transaction
@@ -237,37 +354,60 @@ async fn delete_antrag(
     Ok(RestStatus::Success(result))
 }
 
+#[utoipa::path(
+    path = "/api/antraege/{antrag_id}/attachments",
+    responses(
+        (status = 200, description = "Success", body = Vec<Anhang>),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{antrag_id}/attachments", wrap = "cors_permissive()")]
+async fn get_antrag_attachments(
+    user: User,
+    antrag_id: Path<Uuid>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    let result = domain::anhaenge_by_antrag(&mut *conn, &betrachter, *antrag_id).await?;
+
+    Ok(RestStatus::Success(result))
+}
+
 #[utoipa::path(
     path = "/api/antraege/{antrag_id}/attachments/{attachment_id}",
+    params(AttachmentParams),
     responses(
         (status = 200, description = "Success"),
+        (status = 206, description = "Partial Content"),
         (status = 401, description = "Unauthorized"),
         (status = 404, description = "Not Found"),
+        (status = 416, description = "Range Not Satisfiable"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
 #[get("/{antrag_id}/attachments/{attachment_id}", wrap = "cors_permissive()")]
 async fn get_antrag_attachment(
+    req: HttpRequest,
+    user: User,
     path_params: Path<(Uuid, Uuid)>,
+    params: Query<AttachmentParams>,
+    storage: Data<Storage>,
     mut conn: DatabaseConnection,
-) -> Result<impl Responder> {
-    let (_antrag_id, attachment_id) = path_params.into_inner();
+) -> Result<HttpResponse> {
+    let (antrag_id, attachment_id) = path_params.into_inner();
+
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
 
-    let Some(attachment) = conn.anhang_by_id(attachment_id).await? else {
+    // attachments of hidden Anträge are indistinguishable from missing ones
+    let Some(attachment) =
+        domain::anhang_by_antrag(&mut *conn, &betrachter, antrag_id, attachment_id).await?
+    else {
         return Ok(HttpResponse::NotFound().finish());
     };
 
-    let file_path = UPLOAD_DIR.as_path().join(attachment_id.to_string());
-
-    debug!("Serving file: {:?}", file_path);
-
-    Ok(HttpResponse::Ok()
-        .append_header((header::CONTENT_TYPE, "application/octet-stream"))
-        .append_header((
-            header::CONTENT_DISPOSITION,
-            format!("attachment; filename=\"{}\"", attachment.filename),
-        ))
-        .body(fs::read(file_path).await?))
+    attachments::serve_anhang(&req, &storage, attachment, params.inline()).await
 }
 
 #[utoipa::path(
@@ -287,42 +427,35 @@ async fn get_antrag_attachment(
 async fn delete_antrag_attachment(
     user: User,
     path_params: Path<(Uuid, Uuid)>,
+    storage: Data<Storage>,
+    database: Data<DatabasePool>,
     mut transaction: DatabaseTransaction<'_>,
 ) -> Result<impl Responder> {
     let (antrag_id, attachment_id) = path_params.into_inner();
 
-    if !user.has_capability(Capability::ManageAntraege) {
-        let person = user.query_person(&mut *transaction).await?;
+    let betrachter = user.antrag_betrachter(&mut *transaction).await?;
 
-        let Some(antrag) = transaction.antrag_by_id(antrag_id).await? else {
-            return Ok(RestStatus::NotFound);
-        };
+    let Some(antrag) = transaction.antrag_by_id(antrag_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
 
-        if !domain::can_person_modify_antrag(&mut *transaction, &person, &antrag).await? {
-            return Ok(RestStatus::Status(
-                StatusCode::UNAUTHORIZED,
-                "you are not allowed to edit this antrag".to_string(),
-            ));
-        }
-    }
+    domain::ensure_can_modify_antrag(&mut *transaction, &betrachter, &antrag).await?;
 
     transaction
         .delete_anhang_from_antrag(antrag_id, attachment_id)
         .await?;
 
-    let file_path = UPLOAD_DIR.as_path();
-
-    fs::remove_file(file_path.join(attachment_id.to_string())).await?;
-
     transaction.commit().await?;
 
+    attachments::delete_if_unused(&database, &storage, attachment_id).await;
+
     Ok(RestStatus::Success(Some(())))
 }
 
 #[utoipa::path(
     path = "/api/antraege/{antrag_id}/attachments",
     responses(
-        (status = 200, description = "Success"),
+        (status = 200, description = "Success", body = Anhang),
         (status = 400, description = "Bad Request"),
         (status = 401, description = "Unauthorized"),
         (status = 404, description = "Not Found"),
@@ -337,68 +470,316 @@ async fn delete_antrag_attachment(
 async fn add_antrag_attachment(
     user: User,
     antrag_id: Path<Uuid>,
-    form: MultipartForm<UploadAntrag>,
+    form: MultipartForm<UploadAnhang>,
+    storage: Data<Storage>,
+    database: Data<DatabasePool>,
     mut transaction: DatabaseTransaction<'_>,
 ) -> Result<impl Responder> {
+    let betrachter = user.antrag_betrachter(&mut *transaction).await?;
+
     let Some(antrag) = transaction.antrag_by_id(*antrag_id).await? else {
         return Ok(RestStatus::NotFound);
     };
 
-    if !user.has_capability(Capability::ManageAntraege) {
-        let person = user.query_person(&mut *transaction).await?;
+    domain::ensure_can_modify_antrag(&mut *transaction, &betrachter, &antrag).await?;
 
-        if !domain::can_person_modify_antrag(&mut *transaction, &person, &antrag).await? {
-            return Ok(RestStatus::Status(
-                StatusCode::UNAUTHORIZED,
-                "you are not allowed to edit this antrag".to_string(),
-            ));
-        }
-    }
+    let staged = match attachments::stage_upload(&user, &form, &storage, &database).await? {
+        Ok(staged) => staged,
+        Err(reason) => return Ok(RestStatus::BadRequest(reason)),
+    };
 
-    match form.file.size {
-        0 => {
-            return Ok(RestStatus::BadRequest(
-                "The Provided file was empty".to_string(),
-            ));
-        }
-        length if length > ARGS.max_file_size => {
-            return Ok(RestStatus::BadRequest(format!(
-                "The uploaded file is too large. Maximum size is {} bytes.",
-                ARGS.max_file_size
-            )));
-        }
-        _ => {}
+    let attachment = attachments::confirm(&mut transaction, staged).await?;
+
+    transaction
+        .add_anhang_to_antrag(*antrag_id, attachment.id)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(attachment)))
+}
+
+#[utoipa::path(
+    path = "/api/antraege/{antrag_id}/attachments/{attachment_id}",
+    responses(
+        (status = 200, description = "Success", body = Anhang),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[put(
+    "/{antrag_id}/attachments/{attachment_id}",
+    wrap = "auth::capability::RequireCreateAntrag",
+    wrap = "cors_restrictive()"
+)]
+async fn put_antrag_attachment(
+    user: User,
+    path_params: Path<(Uuid, Uuid)>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (antrag_id, attachment_id) = path_params.into_inner();
+
+    let betrachter = user.antrag_betrachter(&mut *transaction).await?;
+
+    let Some(antrag) = transaction.antrag_by_id(antrag_id).await? else {
+        return Ok(RestStatus::NotFound);
     };
 
-    let temp_file_path = form.file.file.path();
-    let file_name: &str = form
-        .file
-        .file_name
-        .as_ref()
-        .map(|m| m.as_ref())
-        .unwrap_or("null");
-
-    let file_path = UPLOAD_DIR.as_path();
-
-    let attachment = transaction.create_anhang(file_name.to_string()).await;
-
-    let attachment = match attachment {
-        Ok(attachment) => attachment,
-        Err(_) => {
-            return Err(domain::Error::Message(
-                "Could not create attachment".to_string(),
-            ));
-        }
+    domain::ensure_can_modify_antrag(&mut *transaction, &betrachter, &antrag).await?;
+
+    let person = betrachter.require_person()?;
+
+    // only own staged Anhänge, like those of a finished chunked upload, can be attached
+    let Some(attachment) = transaction.confirm_anhang(attachment_id, person.id).await? else {
+        return Ok(RestStatus::NotFound);
     };
 
     transaction
-        .add_anhang_to_antrag(*antrag_id, attachment.id)
+        .add_anhang_to_antrag(antrag_id, attachment.id)
         .await?;
 
-    fs::copy(temp_file_path, file_path.join(attachment.id.to_string())).await?;
-    fs::remove_file(temp_file_path).await?;
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(attachment)))
+}
+
+#[utoipa::path(
+    path = "/api/antraege/{antrag_id}/berechtigungen",
+    responses(
+        (status = 200, description = "Success", body = Vec<AntragBerechtigung>),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{antrag_id}/berechtigungen", wrap = "cors_restrictive()")]
+async fn get_antrag_berechtigungen(
+    user: User,
+    antrag_id: Path<Uuid>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    let Some(antrag) = conn.antrag_by_id(*antrag_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    domain::ensure_can_view_antrag_details(&mut *conn, &betrachter, &antrag).await?;
+
+    let result = conn.berechtigungen_by_antrag(antrag.data.id).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/antraege/{antrag_id}/berechtigungen/{person_id}",
+    request_body = BerechtigungParams,
+    responses(
+        (status = 200, description = "Success", body = AntragBerechtigung),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[put("/{antrag_id}/berechtigungen/{person_id}", wrap = "cors_restrictive()")]
+async fn put_antrag_berechtigung(
+    user: User,
+    path_params: Path<(Uuid, Uuid)>,
+    params: ActixJson<BerechtigungParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (antrag_id, person_id) = path_params.into_inner();
+
+    let betrachter = user.antrag_betrachter(&mut *transaction).await?;
+
+    let Some(antrag) = transaction.antrag_by_id(antrag_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    domain::ensure_can_manage_antrag_berechtigung(&betrachter, &antrag, person_id, false)?;
+
+    if transaction.person_by_id(person_id).await?.is_none() {
+        return Ok(RestStatus::NotFound);
+    }
+
+    if antrag.ersteller.contains(&person_id) {
+        return Ok(RestStatus::BadRequest(
+            "antragstellende already have every right on their antrag".to_string(),
+        ));
+    }
+
+    let result = transaction
+        .set_berechtigung(antrag_id, person_id, params.rolle)
+        .await?;
 
     transaction.commit().await?;
 
-    Ok(RestStatus::Success(Some(())))
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/antraege/{antrag_id}/berechtigungen/{person_id}",
+    responses(
+        (status = 200, description = "Success", body = AntragBerechtigung),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete("/{antrag_id}/berechtigungen/{person_id}", wrap = "cors_restrictive()")]
+async fn delete_antrag_berechtigung(
+    user: User,
+    path_params: Path<(Uuid, Uuid)>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (antrag_id, person_id) = path_params.into_inner();
+
+    let betrachter = user.antrag_betrachter(&mut *transaction).await?;
+
+    let Some(antrag) = transaction.antrag_by_id(antrag_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    domain::ensure_can_manage_antrag_berechtigung(&betrachter, &antrag, person_id, true)?;
+
+    let result = transaction
+        .delete_berechtigung(antrag_id, person_id)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
+
+#[utoipa::path(
+    path = "/api/antraege/{antrag_id}/kommentare",
+    responses(
+        (status = 200, description = "Success", body = Vec<AntragKommentar>),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{antrag_id}/kommentare", wrap = "cors_restrictive()")]
+async fn get_antrag_kommentare(
+    user: User,
+    antrag_id: Path<Uuid>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    let Some(antrag) = conn.antrag_by_id(*antrag_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    domain::ensure_can_view_antrag_details(&mut *conn, &betrachter, &antrag).await?;
+
+    let result = conn.kommentare_by_antrag(antrag.data.id).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/antraege/{antrag_id}/kommentare",
+    request_body = KommentarParams,
+    responses(
+        (status = 201, description = "Created", body = AntragKommentar),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[post("/{antrag_id}/kommentare", wrap = "cors_restrictive()")]
+async fn create_antrag_kommentar(
+    user: User,
+    antrag_id: Path<Uuid>,
+    params: ActixJson<KommentarParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let betrachter = user.antrag_betrachter(&mut *transaction).await?;
+
+    let Some(antrag) = transaction.antrag_by_id(*antrag_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    let person = domain::ensure_can_comment_antrag(&mut *transaction, &betrachter, &antrag).await?;
+
+    let result = transaction
+        .create_kommentar(antrag.data.id, person.id, &params.text)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Created(Some(result)))
+}
+
+#[cfg(test)]
+mod test {
+    use actix_http::StatusCode;
+    use actix_web::dev::Service;
+    use actix_web::web::Data;
+    use actix_web::{test, App, HttpMessage};
+    use anyhow::Result;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::database::DatabasePool;
+    use crate::domain::antrag::{AntragRepo, Sichtbarkeit};
+    use crate::domain::blob_store::BlobStore;
+    use crate::storage::filesystem::FilesystemStore;
+    use crate::storage::Storage;
+    use crate::web::auth::User;
+
+    #[sqlx::test(fixtures(
+        path = "../../database/fixtures",
+        scripts(
+            "gimme_persons",
+            "gimme_antraege",
+            "gimme_attachments",
+            "gimme_attachment_mappings"
+        )
+    ))]
+    async fn hides_attachments_of_hidden_antraege(pool: PgPool) -> Result<()> {
+        let antrag_id = Uuid::parse_str("46148231-87b0-4486-8043-c55038178518").unwrap();
+        let attachment_id = Uuid::parse_str("9b5104a9-6a7d-468e-bbf2-f72a9086a3dc").unwrap();
+        let uri = format!("/antraege/{antrag_id}/attachments/{attachment_id}");
+
+        let storage =
+            FilesystemStore::new(std::env::temp_dir().join(Uuid::new_v4().to_string()).into())
+                .await?;
+        storage
+            .put(&attachment_id.to_string(), b"Tabelle".to_vec())
+            .await?;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(DatabasePool::from_pool(pool.clone())))
+                .app_data(Data::new(Storage::Filesystem(storage)))
+                .wrap_fn(|req, srv| {
+                    req.extensions_mut().insert(User::default());
+                    srv.call(req)
+                })
+                .service(super::service()),
+        )
+        .await;
+
+        let oeffentlich =
+            test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(oeffentlich.status(), StatusCode::OK);
+        assert_eq!(test::read_body(oeffentlich).await, "Tabelle");
+
+        pool.acquire()
+            .await?
+            .set_antrag_sichtbarkeit(antrag_id, Sichtbarkeit::Entwurf)
+            .await?;
+
+        let versteckt =
+            test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(versteckt.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,333 @@
+use actix_files::HttpRange;
+use actix_http::{header, StatusCode};
+use actix_multipart::form::{tempfile::TempFile, MultipartForm};
+use actix_web::http::header::{
+    ContentDisposition, DispositionParam, DispositionType, ETag, EntityTag,
+};
+use actix_web::web::Data;
+use actix_web::{delete, get, web, HttpRequest, HttpResponse, Responder, Scope};
+use async_std::{fs, path::Path};
+use chrono::Utc;
+use log::{debug, error};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::database::{DatabaseConnection, DatabasePool, DatabaseTransaction};
+use crate::domain::anhang::{self, Anhang, AnhangRepo, STAGING_TIMEOUT};
+use crate::domain::blob_store::BlobStore;
+use crate::domain::preview::PREVIEW_CONTENT_TYPE;
+use crate::domain::{self, Result};
+use crate::storage::Storage;
+use crate::web::auth::{self, User};
+use crate::web::{cors_permissive, cors_restrictive, RestStatus};
+use crate::ARGS;
+
+/// Create the attachment maintenance and preview service under /attachments
+pub(crate) fn service() -> Scope {
+    web::scope("/attachments")
+        .service(get_unused_attachments)
+        .service(delete_unused_attachments)
+        .service(get_attachment_preview)
+}
+
+#[derive(MultipartForm)]
+pub struct UploadAnhang {
+    file: TempFile,
+}
+
+#[derive(Debug, IntoParams, Deserialize, ToSchema, Validate)]
+pub struct AttachmentParams {
+    /// Set to 1 to display the attachment in the browser instead of downloading it
+    inline: Option<String>,
+}
+
+impl AttachmentParams {
+    pub(crate) fn inline(&self) -> bool {
+        matches!(self.inline.as_deref(), Some("1" | "true"))
+    }
+}
+
+/// The largest file the user may upload: the highest limit of their capabilities, or
+/// `--max-file-size` if none of them has one
+pub(crate) fn max_file_size(user: &User) -> usize {
+    ARGS.max_file_sizes
+        .iter()
+        .filter(|(capability, _)| user.has_capability(*capability))
+        .map(|(_, size)| *size)
+        .max()
+        .unwrap_or(ARGS.max_file_size)
+}
+
+/// Why a file of this size may not be uploaded by the user, if it may not
+pub(crate) fn check_file_size(user: &User, size: usize) -> Option<String> {
+    let max_file_size = max_file_size(user);
+
+    match size {
+        0 => Some("The Provided file was empty".to_string()),
+        size if size > max_file_size => Some(format!(
+            "The uploaded file is too large. Maximum size is {max_file_size} bytes."
+        )),
+        _ => None,
+    }
+}
+
+/// Checks an uploaded file and stores it as a staged Anhang. Returns why the file was rejected,
+/// if it was
+pub(crate) async fn stage_upload(
+    user: &User,
+    form: &UploadAnhang,
+    storage: &Storage,
+    database: &DatabasePool,
+) -> Result<std::result::Result<Anhang, String>> {
+    let temp_file_path = form.file.file.path();
+
+    let result = match check_file_size(user, form.file.size) {
+        Some(reason) => Ok(Err(reason)),
+        None => {
+            let file_name: &str = form
+                .file
+                .file_name
+                .as_ref()
+                .map(|m| m.as_ref())
+                .unwrap_or("null");
+
+            let person = user.query_person(&mut *database.aquire().await?).await?;
+
+            stage_file(
+                temp_file_path.into(),
+                file_name,
+                person.id,
+                storage,
+                database,
+            )
+            .await
+        }
+    };
+
+    fs::remove_file(temp_file_path).await?;
+
+    result
+}
+
+/// Checks the type of a local file and stores it as a staged Anhang of the person who uploaded
+/// it, without loading it into memory. Returns why the file was rejected, if it was
+pub(crate) async fn stage_file(
+    path: &Path,
+    file_name: &str,
+    hochgeladen_von: Uuid,
+    storage: &Storage,
+    database: &DatabasePool,
+) -> Result<std::result::Result<Anhang, String>> {
+    let datei = anhang::inspect_datei(path, file_name).await?;
+
+    if !anhang::is_content_type_allowed(&datei.content_type, &ARGS.allowed_attachment_types) {
+        return Ok(Err(format!(
+            "Files of type {} are not allowed.",
+            datei.content_type.essence_str()
+        )));
+    }
+
+    // the content is stored before the Anhang is confirmed, so a failure at any point leaves
+    // at most a staged Anhang, which is cleaned up later
+    let staged = domain::stage_anhang(
+        &mut *database.aquire().await?,
+        storage,
+        &datei,
+        path,
+        hochgeladen_von,
+    )
+    .await?;
+
+    Ok(Ok(staged))
+}
+
+/// Confirms a staged Anhang as part of the transaction attaching it
+pub(crate) async fn confirm(
+    transaction: &mut DatabaseTransaction<'_>,
+    staged: Anhang,
+) -> Result<Anhang> {
+    let confirmed = match staged.hochgeladen_von {
+        Some(hochgeladen_von) => {
+            transaction
+                .confirm_anhang(staged.id, hochgeladen_von)
+                .await?
+        }
+        None => None,
+    };
+
+    confirmed.ok_or_else(|| {
+        domain::Error::Message(format!("the staged attachment {} vanished", staged.id))
+    })
+}
+
+/// Deletes an Anhang after it was detached, if it is not attached anywhere else. Failures are
+/// only logged, the cleanup job retries them
+pub(crate) async fn delete_if_unused(database: &DatabasePool, storage: &Storage, id: Uuid) {
+    let result = match database.aquire().await {
+        Ok(mut conn) => domain::delete_anhang_if_unused(&mut *conn, storage, id).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        error!("failed to delete attachment {id}: {e:?}");
+    }
+}
+
+/// Streams the content of an Anhang, answering Range requests with the requested part
+pub(crate) async fn serve_anhang(
+    req: &HttpRequest,
+    storage: &Storage,
+    anhang: Anhang,
+    inline: bool,
+) -> Result<HttpResponse> {
+    debug!("Serving attachment: {}", anhang.id);
+
+    // Anhänge uploaded before their size was recorded are always served whole
+    let size = anhang.size.map(|size| size as u64);
+
+    let range = match (size, req.headers().get(header::RANGE)) {
+        (Some(size), Some(range)) => {
+            match HttpRange::parse(range.to_str().unwrap_or_default(), size).as_deref() {
+                Ok([HttpRange { start, length }]) => Some(*start..start + length),
+                // multiple ranges are rare, the whole content is a valid answer to them
+                Ok(_) => None,
+                Err(_) => {
+                    return Ok(HttpResponse::RangeNotSatisfiable()
+                        .insert_header((header::CONTENT_RANGE, format!("bytes */{size}")))
+                        .finish())
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let Some(content) = storage
+        .get_stream(&anhang.id.to_string(), range.clone())
+        .await?
+    else {
+        return Err(domain::Error::Message(format!(
+            "the content of attachment {} is missing",
+            anhang.id
+        )));
+    };
+
+    let disposition = if inline {
+        DispositionType::Inline
+    } else {
+        DispositionType::Attachment
+    };
+
+    let mut response = HttpResponse::Ok();
+
+    response
+        .insert_header((header::CONTENT_TYPE, anhang.content_type))
+        .insert_header(ContentDisposition {
+            disposition,
+            parameters: vec![DispositionParam::Filename(anhang.filename)],
+        })
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"));
+
+    if let Some(sha256) = anhang.sha256 {
+        response.insert_header(ETag(EntityTag::new_strong(sha256)));
+    }
+
+    if let Some(size) = size {
+        response.insert_header((header::ACCEPT_RANGES, "bytes"));
+
+        match range {
+            Some(range) => response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .insert_header((
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{size}", range.start, range.end - 1),
+                ))
+                .no_chunking(range.end - range.start),
+            None => response.no_chunking(size),
+        };
+    }
+
+    Ok(response.streaming(content))
+}
+
+#[utoipa::path(
+    path = "/api/attachments/unused",
+    responses(
+        (status = 200, description = "Success", body = Vec<Anhang>),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get(
+    "/unused",
+    wrap = "auth::capability::RequireAdmin",
+    wrap = "cors_restrictive()"
+)]
+async fn get_unused_attachments(mut conn: DatabaseConnection) -> Result<impl Responder> {
+    let result = conn.unused_anhaenge(Utc::now() - STAGING_TIMEOUT).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/attachments/unused",
+    responses(
+        (status = 200, description = "Success", body = Vec<Anhang>),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete(
+    "/unused",
+    wrap = "auth::capability::RequireAdmin",
+    wrap = "cors_restrictive()"
+)]
+async fn delete_unused_attachments(
+    storage: Data<Storage>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let result = domain::delete_unused_anhaenge(&mut *conn, &**storage, Utc::now()).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/attachments/{attachment_id}/preview",
+    responses(
+        (status = 200, description = "Success", content_type = "image/png"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{attachment_id}/preview", wrap = "cors_permissive()")]
+async fn get_attachment_preview(
+    user: User,
+    attachment_id: web::Path<Uuid>,
+    storage: Data<Storage>,
+    mut conn: DatabaseConnection,
+) -> Result<HttpResponse> {
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    // previews of hidden attachments are indistinguishable from missing ones
+    let Some(anhang) = domain::visible_anhang(&mut *conn, &betrachter, *attachment_id).await?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let Some(preview) = domain::anhang_preview(&**storage, &anhang).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let mut response = HttpResponse::Ok();
+
+    response
+        .insert_header((header::CONTENT_TYPE, PREVIEW_CONTENT_TYPE))
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"));
+
+    if let Some(sha256) = anhang.sha256 {
+        response.insert_header(ETag(EntityTag::new_strong(sha256)));
+    }
+
+    Ok(response.body(preview))
+}
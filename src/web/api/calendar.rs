@@ -1,15 +1,16 @@
 use actix_web::web::{Data, Path};
-use actix_web::{get, web, Responder, Scope};
+use actix_web::{get, post, web, Responder, Scope};
 
 use crate::domain::calendar::{CalendarEvent, CalendarRepo};
 use crate::domain::Result;
 use crate::web::calendar::CalendarData;
-use crate::web::{cors_permissive, RestStatus};
+use crate::web::{auth, cors_permissive, cors_restrictive, RestStatus};
 
 // Create the calendar service under /calendar
 pub(crate) fn service() -> Scope {
     web::scope("/calendar")
         .service(get_calendar_by_name)
+        .service(refresh_calendar)
         .service(get_calendars)
 }
 
@@ -42,3 +43,25 @@ async fn get_calendar_by_name(
         calendars.calender_by_name(name.as_str()).await?,
     ))
 }
+
+#[utoipa::path(
+    path = "/api/calendar/{calendar-name}/refresh",
+    responses(
+        (status = 200, description = "Success", body = CalendarEvent),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+    )
+)]
+#[post(
+    "/{name}/refresh",
+    wrap = "auth::capability::RequireManageCalendars",
+    wrap = "cors_restrictive()"
+)]
+async fn refresh_calendar(
+    name: Path<String>,
+    calendars: Data<CalendarData>,
+) -> Result<impl Responder> {
+    Ok(RestStatus::Success(
+        calendars.refresh_calendar(name.as_str()).await?,
+    ))
+}
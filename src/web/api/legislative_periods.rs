@@ -1,21 +1,34 @@
+use std::borrow::Cow;
+
 use actix_web::{
-    delete, get, patch, post,
-    web::{self, Path, Query},
+    delete, get, patch, post, put,
+    web::{self, Path},
     Responder, Scope,
 };
+use actix_web_validator::Query;
+use chrono::NaiveDate;
 use serde::Deserialize;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 use crate::{
     database::{DatabaseConnection, DatabaseTransaction},
     domain::{
-        legislatur_periode::{LegislaturPeriode, LegislaturPeriodeRepo},
+        legislatur_periode::{
+            LegislaturPeriode, LegislaturPeriodeFilter, LegislaturPeriodeRepo,
+            LegislaturPeriodeSort, Mitglied, MitgliedsFunktion, Mitgliedschaft,
+        },
+        pagination::SortOrder,
+        persons::PersonRepo,
         sitzung::Sitzung,
         Result,
     },
-    web::{auth, cors_permissive, cors_restrictive, RestStatus},
+    web::{
+        auth, cors_permissive, cors_restrictive,
+        pagination::{Paginated, PaginationParams},
+        RestStatus,
+    },
 };
 
 /// Create the legislative period service
@@ -31,28 +44,77 @@ pub(crate) fn service() -> Scope {
 fn register_legislative_period_id_service(parent: Scope) -> Scope {
     parent
         .service(get_sitzungen_by_legislatur_periode)
+        .service(get_mitglieder_by_legislatur_periode)
+        .service(put_mitglied)
+        .service(delete_mitglied)
         .service(get_legislatur_periode_by_id)
         .service(patch_legislatur_periode)
         .service(delete_legislatur_periode)
 }
 
 #[derive(Debug, Deserialize, ToSchema, IntoParams, Validate)]
+#[validate(schema(function = "validate_legislative_params"))]
 pub struct CreateLegislativeParams {
     name: String,
+    start: Option<NaiveDate>,
+    ende: Option<NaiveDate>,
+}
+
+fn validate_legislative_params(
+    params: &CreateLegislativeParams,
+) -> core::result::Result<(), ValidationError> {
+    match (params.start, params.ende) {
+        (Some(start), Some(ende)) if start > ende => {
+            Err(ValidationError::new("legislative_params")
+                .with_message(Cow::Borrowed("start must be before ende")))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema, IntoParams, Validate)]
+pub struct LegislaturPeriodenParams {
+    /// Only Legislaturperioden this date lies in
+    am: Option<NaiveDate>,
+    #[serde(default)]
+    sort: LegislaturPeriodeSort,
+    #[serde(default)]
+    order: SortOrder,
+}
+
+#[derive(Debug, Deserialize, ToSchema, IntoParams, Validate)]
+pub struct MitgliedParams {
+    funktion: MitgliedsFunktion,
 }
 
 #[utoipa::path(
     path = "/api/legislative-periods",
+    params(LegislaturPeriodenParams, PaginationParams),
     responses(
-        (status = 200, description = "Success", body = Vec<LegislaturPeriode>),
+        (status = 200, description = "Success", body = Vec<LegislaturPeriode>, headers(
+            ("Link" = String, description = "Links to the first, previous, next and last page"),
+            ("X-Total-Count" = i64, description = "How many items there are on all pages"),
+        )),
+        (status = 400, description = "Bad Request"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
 #[get("", wrap = "cors_permissive()")]
-async fn get_legislatur_perioden(mut conn: DatabaseConnection) -> Result<impl Responder> {
-    let result = conn.legislatur_perioden().await?;
+async fn get_legislatur_perioden(
+    params: Query<LegislaturPeriodenParams>,
+    pagination: Query<PaginationParams>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let filter = LegislaturPeriodeFilter {
+        am: params.am,
+        sort: params.sort,
+        order: params.order,
+    };
+    let pagination = pagination.pagination();
 
-    Ok(RestStatus::Success(Some(result)))
+    let result = conn.legislatur_perioden(&filter, pagination).await?;
+
+    Ok(Paginated::new(result, pagination))
 }
 
 #[utoipa::path(
@@ -103,14 +165,16 @@ async fn get_sitzungen_by_legislatur_periode(
 )]
 #[post(
     "",
-    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "auth::capability::RequireManageLegislaturPerioden",
     wrap = "cors_restrictive()"
 )]
 async fn create_legislatur_periode(
     params: Query<CreateLegislativeParams>,
     mut conn: DatabaseConnection,
 ) -> Result<impl Responder> {
-    let result = conn.create_legislatur_periode(params.name.clone()).await?;
+    let result = conn
+        .create_legislatur_periode(params.name.clone(), params.start, params.ende)
+        .await?;
 
     Ok(RestStatus::Created(Some(result)))
 }
@@ -127,7 +191,7 @@ async fn create_legislatur_periode(
 )]
 #[patch(
     "/{id}",
-    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "auth::capability::RequireManageLegislaturPerioden",
     wrap = "cors_restrictive()"
 )]
 async fn patch_legislatur_periode(
@@ -136,7 +200,7 @@ async fn patch_legislatur_periode(
     mut transaction: DatabaseTransaction<'_>,
 ) -> Result<impl Responder> {
     let result = transaction
-        .update_legislatur_periode(*id, params.name.clone())
+        .update_legislatur_periode(*id, params.name.clone(), params.start, params.ende)
         .await?;
 
     transaction.commit().await?;
@@ -155,7 +219,7 @@ async fn patch_legislatur_periode(
 )]
 #[delete(
     "/{id}",
-    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "auth::capability::RequireManageLegislaturPerioden",
     wrap = "cors_restrictive()"
 )]
 async fn delete_legislatur_periode(
@@ -168,3 +232,92 @@ async fn delete_legislatur_periode(
 
     Ok(RestStatus::Success(Some(result)))
 }
+
+#[utoipa::path(
+    path = "/api/legislative-periods/{id}/mitglieder",
+    responses(
+        (status = 200, description = "Success", body = Vec<Mitglied>),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{id}/mitglieder", wrap = "cors_permissive()")]
+async fn get_mitglieder_by_legislatur_periode(
+    mut conn: DatabaseConnection,
+    id: Path<Uuid>,
+) -> Result<impl Responder> {
+    if conn.legislatur_periode_by_id(*id).await?.is_none() {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let result = conn.mitglieder_by_legislatur_periode(*id).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/legislative-periods/{id}/mitglieder/{person_id}",
+    request_body = MitgliedParams,
+    responses(
+        (status = 200, description = "Success", body = Mitgliedschaft),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[put(
+    "/{id}/mitglieder/{person_id}",
+    wrap = "auth::capability::RequireManageLegislaturPerioden",
+    wrap = "cors_restrictive()"
+)]
+async fn put_mitglied(
+    path_params: Path<(Uuid, Uuid)>,
+    params: actix_web_validator::Json<MitgliedParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (id, person_id) = path_params.into_inner();
+
+    if transaction.legislatur_periode_by_id(id).await?.is_none()
+        || PersonRepo::person_by_id(&mut *transaction, person_id)
+            .await?
+            .is_none()
+    {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let result = transaction
+        .set_mitglied(id, person_id, params.funktion)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/legislative-periods/{id}/mitglieder/{person_id}",
+    responses(
+        (status = 200, description = "Success", body = Mitgliedschaft),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete(
+    "/{id}/mitglieder/{person_id}",
+    wrap = "auth::capability::RequireManageLegislaturPerioden",
+    wrap = "cors_restrictive()"
+)]
+async fn delete_mitglied(
+    path_params: Path<(Uuid, Uuid)>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (id, person_id) = path_params.into_inner();
+
+    let result = transaction.delete_mitglied(id, person_id).await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
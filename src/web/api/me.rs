@@ -0,0 +1,483 @@
+use actix_http::StatusCode;
+use actix_web::web::Path;
+use actix_web::{delete, get, patch, put, web, Responder, Scope};
+use actix_web_validator::Json as ActixJson;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::database::{DatabaseConnection, DatabaseTransaction};
+use crate::domain::antrag::{Antrag, AntragRepo};
+use crate::domain::persons::{Abmeldung, BenachrichtigungsEinstellungen, Person, PersonRepo};
+use crate::domain::{self, Capability, Result};
+use crate::web::api::persons::{AbmeldungParams, CreateAbmeldungParams};
+use crate::web::auth::User;
+use crate::web::{cors_restrictive, RestStatus};
+
+/// Create the service describing the current user under /me
+pub(crate) fn service() -> Scope {
+    web::scope("/me")
+        .service(get_me)
+        .service(patch_me)
+        .service(get_my_capabilities)
+        .service(get_my_abmeldungen)
+        .service(create_my_abmeldung)
+        .service(revoke_my_abmeldung)
+        .service(abmelden_for_sitzung)
+        .service(revoke_abmeldung_for_sitzung)
+}
+
+/// The logged in user together with everything stored about their Person
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Me {
+    #[schema(value_type = Object)]
+    user: User,
+    person: Option<Person>,
+    roles: Vec<String>,
+    /// Abmeldungen that have not ended yet
+    abmeldungen: Vec<Abmeldung>,
+    antraege: Vec<Antrag>,
+    benachrichtigungen: Option<BenachrichtigungsEinstellungen>,
+    capabilities: Vec<Capability>,
+}
+
+#[derive(Debug, IntoParams, Deserialize, ToSchema, Validate)]
+pub struct UpdateMeParams {
+    #[validate(length(min = 1))]
+    matrix_id: Option<String>,
+    benachrichtigungen: Option<UpdateBenachrichtigungenParams>,
+}
+
+#[derive(Debug, IntoParams, Deserialize, ToSchema)]
+pub struct UpdateBenachrichtigungenParams {
+    sitzungen: Option<bool>,
+    antraege: Option<bool>,
+    kommentare: Option<bool>,
+}
+
+#[derive(Debug, IntoParams, Deserialize, ToSchema, Validate)]
+pub struct SitzungAbmeldungParams {
+    #[validate(length(min = 1))]
+    grund: Option<String>,
+    /// Whether the grund is only visible to you and to those managing persons, defaults to true
+    privat: Option<bool>,
+}
+
+async fn me(user: User, repo: &mut (impl PersonRepo + AntragRepo)) -> Result<Me> {
+    let Some(person) = user.find_person(repo).await? else {
+        return Ok(Me {
+            capabilities: user.capabilities(),
+            user,
+            person: None,
+            roles: vec![],
+            abmeldungen: vec![],
+            antraege: vec![],
+            benachrichtigungen: None,
+        });
+    };
+
+    let today = Utc::now()
+        .with_timezone(&chrono_tz::Europe::Berlin)
+        .date_naive();
+
+    let abmeldungen = repo
+        .abmeldungen_by_person(person.id)
+        .await?
+        .into_iter()
+        .filter(|abmeldung| abmeldung.ablaufdatum >= today)
+        .collect();
+
+    Ok(Me {
+        roles: repo.roles_by_person(person.id).await?,
+        abmeldungen,
+        antraege: repo.antraege_by_ersteller(person.id).await?,
+        benachrichtigungen: Some(repo.benachrichtigungen_by_person(person.id).await?),
+        capabilities: user.capabilities(),
+        person: Some(person),
+        user,
+    })
+}
+
+#[utoipa::path(
+    path = "/api/me",
+    responses(
+        (status = 200, description = "Success", body = Me),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("", wrap = "cors_restrictive()")]
+async fn get_me(user: User, mut conn: DatabaseConnection) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you are not logged in".to_string(),
+        ));
+    }
+
+    let result = me(user, &mut *conn).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/me",
+    request_body = UpdateMeParams,
+    responses(
+        (status = 200, description = "Success", body = Me),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[patch("", wrap = "cors_restrictive()")]
+async fn patch_me(
+    user: User,
+    params: ActixJson<UpdateMeParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you are not logged in".to_string(),
+        ));
+    }
+
+    let person = user.query_person(&mut *transaction).await?;
+
+    if let Some(matrix_id) = params.matrix_id.as_deref() {
+        if transaction
+            .person_by_matrix_id(matrix_id)
+            .await?
+            .is_some_and(|other| other.id != person.id)
+        {
+            return Ok(RestStatus::BadRequest(format!(
+                "{matrix_id} already belongs to another person"
+            )));
+        }
+
+        transaction
+            .update_person(person.id, None, None, Some(matrix_id))
+            .await?;
+    }
+
+    if let Some(benachrichtigungen) = &params.benachrichtigungen {
+        let current = transaction.benachrichtigungen_by_person(person.id).await?;
+
+        transaction
+            .set_benachrichtigungen(
+                person.id,
+                BenachrichtigungsEinstellungen {
+                    sitzungen: benachrichtigungen.sitzungen.unwrap_or(current.sitzungen),
+                    antraege: benachrichtigungen.antraege.unwrap_or(current.antraege),
+                    kommentare: benachrichtigungen.kommentare.unwrap_or(current.kommentare),
+                },
+            )
+            .await?;
+    }
+
+    let result = me(user, &mut *transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/me/capabilities",
+    responses(
+        (status = 200, description = "Success", body = Vec<Capability>),
+    )
+)]
+#[get("/capabilities", wrap = "cors_restrictive()")]
+async fn get_my_capabilities(user: User) -> impl Responder {
+    RestStatus::Success(Some(user.capabilities()))
+}
+
+#[utoipa::path(
+    path = "/api/me/abmeldungen",
+    responses(
+        (status = 200, description = "Success", body = Vec<Abmeldung>),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/abmeldungen", wrap = "cors_restrictive()")]
+async fn get_my_abmeldungen(user: User, mut conn: DatabaseConnection) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you are not logged in".to_string(),
+        ));
+    }
+
+    let person = user.query_person(&mut *conn).await?;
+
+    let result = conn.abmeldungen_by_person(person.id).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/me/abmeldungen",
+    request_body = CreateAbmeldungParams,
+    responses(
+        (status = 201, description = "Created", body = Abmeldung),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[put("/abmeldungen", wrap = "cors_restrictive()")]
+async fn create_my_abmeldung(
+    user: User,
+    params: ActixJson<CreateAbmeldungParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you are not logged in".to_string(),
+        ));
+    }
+
+    let person = user.query_person(&mut *transaction).await?;
+
+    let result = transaction
+        .create_abmeldung(
+            person.id,
+            params.start,
+            params.end,
+            params.grund.as_deref(),
+            params.privat.unwrap_or(true),
+        )
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Created(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/me/abmeldungen",
+    request_body = AbmeldungParams,
+    responses(
+        (status = 200, description = "Success"),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete("/abmeldungen", wrap = "cors_restrictive()")]
+async fn revoke_my_abmeldung(
+    user: User,
+    params: ActixJson<AbmeldungParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you are not logged in".to_string(),
+        ));
+    }
+
+    let person = user.query_person(&mut *transaction).await?;
+
+    transaction
+        .revoke_abmeldung_from_person(person.id, params.start, params.end)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(())))
+}
+
+#[utoipa::path(
+    path = "/api/me/abmeldungen/sitzungen/{sitzung_id}",
+    params(("sitzung_id" = Uuid, Path, description = "sitzung_id")),
+    request_body = SitzungAbmeldungParams,
+    responses(
+        (status = 201, description = "Created", body = Abmeldung),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[put("/abmeldungen/sitzungen/{sitzung_id}", wrap = "cors_restrictive()")]
+async fn abmelden_for_sitzung(
+    user: User,
+    sitzung_id: Path<Uuid>,
+    params: ActixJson<SitzungAbmeldungParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you are not logged in".to_string(),
+        ));
+    }
+
+    let person = user.query_person(&mut *transaction).await?;
+
+    let Some(result) = domain::abmelden_for_sitzung(
+        &mut *transaction,
+        person.id,
+        *sitzung_id,
+        params.grund.as_deref(),
+        params.privat.unwrap_or(true),
+    )
+    .await?
+    else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Created(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/me/abmeldungen/sitzungen/{sitzung_id}",
+    params(("sitzung_id" = Uuid, Path, description = "sitzung_id")),
+    responses(
+        (status = 200, description = "Success"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete("/abmeldungen/sitzungen/{sitzung_id}", wrap = "cors_restrictive()")]
+async fn revoke_abmeldung_for_sitzung(
+    user: User,
+    sitzung_id: Path<Uuid>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you are not logged in".to_string(),
+        ));
+    }
+
+    let person = user.query_person(&mut *transaction).await?;
+
+    let result =
+        domain::revoke_abmeldung_for_sitzung(&mut *transaction, person.id, *sitzung_id).await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
+
+#[cfg(test)]
+mod test {
+    use actix_http::StatusCode;
+    use actix_web::dev::Service;
+    use actix_web::web::Data;
+    use actix_web::{test, App, HttpMessage};
+    use anyhow::Result;
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+
+    use crate::database::DatabasePool;
+    use crate::web::auth::User;
+
+    fn user(sub: &str) -> User {
+        User {
+            sub: sub.to_string(),
+            ..Default::default()
+        }
+    }
+
+    macro_rules! me_service {
+        ($pool:expr, $user:expr) => {{
+            let user = $user;
+            test::init_service(
+                App::new()
+                    .app_data(Data::new(DatabasePool::from_pool($pool)))
+                    .wrap_fn(move |req, srv| {
+                        req.extensions_mut().insert(user.clone());
+                        srv.call(req)
+                    })
+                    .service(super::service()),
+            )
+            .await
+        }};
+    }
+
+    #[sqlx::test(fixtures(
+        path = "../../database/fixtures",
+        scripts("gimme_persons", "gimme_antraege")
+    ))]
+    async fn get_me(pool: PgPool) -> Result<()> {
+        let anonym = me_service!(pool.clone(), User::default());
+        let abgelehnt =
+            test::call_service(&anonym, test::TestRequest::get().uri("/me").to_request()).await;
+        assert_eq!(abgelehnt.status(), StatusCode::UNAUTHORIZED);
+
+        let steve = me_service!(pool.clone(), user("steve"));
+        let me: Value =
+            test::call_and_read_body_json(&steve, test::TestRequest::get().uri("/me").to_request())
+                .await;
+        assert_eq!(me["person"]["id"], "51288f16-4442-4d7c-9606-3dce198b0601");
+        assert_eq!(me["antraege"].as_array().unwrap().len(), 1);
+        assert_eq!(me["benachrichtigungen"]["sitzungen"], true);
+
+        // users who never got a person still see who they are
+        let ohne_person = me_service!(pool, user("niemand"));
+        let niemand: Value = test::call_and_read_body_json(
+            &ohne_person,
+            test::TestRequest::get().uri("/me").to_request(),
+        )
+        .await;
+        assert_eq!(niemand["person"], Value::Null);
+        assert_eq!(niemand["antraege"], json!([]));
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures(path = "../../database/fixtures", scripts("gimme_persons")))]
+    async fn patch_me(pool: PgPool) -> Result<()> {
+        let anonym = me_service!(pool.clone(), User::default());
+        let abgelehnt = test::call_service(
+            &anonym,
+            test::TestRequest::patch()
+                .uri("/me")
+                .set_json(json!({ "matrix_id": "@anonym:matrix.org" }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(abgelehnt.status(), StatusCode::UNAUTHORIZED);
+
+        let steve = me_service!(pool, user("steve"));
+        let me: Value = test::call_and_read_body_json(
+            &steve,
+            test::TestRequest::patch()
+                .uri("/me")
+                .set_json(json!({
+                    "matrix_id": "@steve:matrix.org",
+                    "benachrichtigungen": { "kommentare": false },
+                }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(me["person"]["matrix_id"], "@steve:matrix.org");
+        assert_eq!(me["benachrichtigungen"]["kommentare"], false);
+        assert_eq!(me["benachrichtigungen"]["sitzungen"], true);
+
+        let vergeben = test::call_service(
+            &steve,
+            test::TestRequest::patch()
+                .uri("/me")
+                .set_json(json!({ "matrix_id": "@wilfried:matrix.org" }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(vergeben.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+}
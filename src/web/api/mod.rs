@@ -1,12 +1,17 @@
 use actix_web::{web, Scope};
 
 pub(crate) mod antrag;
+pub(crate) mod attachments;
 pub(crate) mod calendar;
 pub(crate) mod legislative_periods;
+pub(crate) mod me;
 pub(crate) mod persons;
 pub(crate) mod roles;
+pub(crate) mod search;
 pub(crate) mod sitzungen;
 pub(crate) mod templates;
+pub(crate) mod tokens;
+pub(crate) mod uploads;
 
 /// Create the API Service under /api
 pub(crate) fn service() -> Scope {
@@ -15,7 +20,12 @@ pub(crate) fn service() -> Scope {
         .service(persons::service())
         .service(roles::service())
         .service(antrag::service())
+        .service(attachments::service())
         .service(sitzungen::service())
         .service(legislative_periods::service())
         .service(templates::service())
+        .service(tokens::service())
+        .service(uploads::service())
+        .service(me::service())
+        .service(search::service())
 }
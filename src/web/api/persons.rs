@@ -1,5 +1,6 @@
 use std::borrow::Cow;
 
+use actix_http::StatusCode;
 use actix_web::web::Path;
 use actix_web::{delete, put, web};
 use actix_web::{get, patch, Responder, Scope};
@@ -11,9 +12,12 @@ use uuid::Uuid;
 use validator::{Validate, ValidationError};
 
 use crate::database::{DatabaseConnection, DatabaseTransaction};
-use crate::domain::persons::{Abmeldung, Person};
+use crate::domain::pagination::SortOrder;
+use crate::domain::persons::{Abmeldung, Person, PersonFilter, PersonSort};
+use crate::domain::session::{Session, SessionRepo};
 use crate::domain::Capability;
 use crate::web::auth::{self, User};
+use crate::web::pagination::{Paginated, PaginationParams};
 use crate::{
     domain::{persons::PersonRepo, Result},
     web::{cors_restrictive, RestStatus},
@@ -42,6 +46,23 @@ fn register_person_id_service(parent: Scope) -> Scope {
         .service(create_abmeldung)
         .service(get_abmeldungen_by_person)
         .service(roles_by_person)
+        .service(identities_by_person)
+        .service(link_identity)
+        .service(unlink_identity)
+        .service(delete_session)
+        .service(sessions_by_person)
+        .service(delete_sessions_by_person)
+}
+
+#[derive(Debug, Deserialize, ToSchema, IntoParams, Validate)]
+pub struct PersonsParams {
+    /// Only persons whose name contains this, ignoring case
+    #[validate(length(min = 1))]
+    name: Option<String>,
+    #[serde(default)]
+    sort: PersonSort,
+    #[serde(default)]
+    order: SortOrder,
 }
 
 #[derive(Debug, Deserialize, ToSchema, IntoParams, Validate)]
@@ -76,11 +97,29 @@ pub struct RoleParams {
     role: String,
 }
 
+#[derive(Debug, Deserialize, ToSchema, IntoParams, Validate)]
+pub struct IdentityParams {
+    /// User name of the identity, prefixed with its oauth provider like `provider-sub`
+    #[validate(length(min = 1))]
+    user_name: String,
+}
+
 #[derive(Debug, Deserialize, ToSchema, IntoParams, Validate)]
 #[validate(schema(function = "validate_abmeldung_params"))]
 pub struct AbmeldungParams {
-    start: NaiveDate,
-    end: NaiveDate,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+#[derive(Debug, Deserialize, ToSchema, IntoParams, Validate)]
+#[validate(schema(function = "validate_create_abmeldung_params"))]
+pub struct CreateAbmeldungParams {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    #[validate(length(min = 1))]
+    pub grund: Option<String>,
+    /// Whether the grund is only visible to the person and to those managing persons, defaults to true
+    pub privat: Option<bool>,
 }
 
 #[derive(Debug, Serialize, ToSchema, IntoParams, Validate)]
@@ -114,7 +153,20 @@ impl PublicPerson {
 fn validate_abmeldung_params(
     params: &AbmeldungParams,
 ) -> core::result::Result<(), ValidationError> {
-    if params.start > params.end {
+    validate_abmeldung_zeitraum(params.start, params.end)
+}
+
+fn validate_create_abmeldung_params(
+    params: &CreateAbmeldungParams,
+) -> core::result::Result<(), ValidationError> {
+    validate_abmeldung_zeitraum(params.start, params.end)
+}
+
+fn validate_abmeldung_zeitraum(
+    start: NaiveDate,
+    end: NaiveDate,
+) -> core::result::Result<(), ValidationError> {
+    if start > end {
         Err(ValidationError::new("abmeldung_params")
             .with_message(Cow::Borrowed("start must be before end")))
     } else {
@@ -124,28 +176,39 @@ fn validate_abmeldung_params(
 
 #[utoipa::path(
     path = "/api/persons",
+    params(PersonsParams, PaginationParams),
     responses(
-        (status = 200, description = "Success", body = Vec<PublicPerson>),
+        (status = 200, description = "Success", body = Vec<PublicPerson>, headers(
+            ("Link" = String, description = "Links to the first, previous, next and last page"),
+            ("X-Total-Count" = i64, description = "How many items there are on all pages"),
+        )),
+        (status = 400, description = "Bad Request"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
 #[get("", wrap = "cors_restrictive()")]
-async fn get_persons(user: User, mut conn: DatabaseConnection) -> Result<impl Responder> {
-    let persons = conn.persons().await?;
+async fn get_persons(
+    user: User,
+    params: Query<PersonsParams>,
+    pagination: Query<PaginationParams>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let filter = PersonFilter {
+        name: params.name.clone(),
+        sort: params.sort,
+        order: params.order,
+    };
+    let pagination = pagination.pagination();
 
-    let result: Vec<_> = if user.has_capability(Capability::ManagePersons) {
-        persons
-            .into_iter()
-            .map(PublicPerson::private_from_person)
-            .collect()
+    let persons = conn.persons(&filter, pagination).await?;
+
+    let result = if user.has_capability(Capability::ManagePersons) {
+        persons.map(PublicPerson::private_from_person)
     } else {
-        persons
-            .into_iter()
-            .map(PublicPerson::public_from_person)
-            .collect()
+        persons.map(PublicPerson::public_from_person)
     };
 
-    Ok(RestStatus::Success(Some(result)))
+    Ok(Paginated::new(result, pagination))
 }
 
 #[utoipa::path(
@@ -378,10 +441,11 @@ async fn roles_by_person(
 )]
 #[put(
     "/{person_id}/roles",
-    wrap = "auth::capability::RequireManagePersons",
+    wrap = "auth::capability::RequireManageRoles",
     wrap = "cors_restrictive()"
 )]
 async fn add_role_to_person(
+    user: User,
     person_id: Path<Uuid>,
     params: ActixJson<RoleParams>,
     mut transaction: DatabaseTransaction<'_>,
@@ -390,6 +454,18 @@ async fn add_role_to_person(
         return Ok(RestStatus::NotFound);
     }
 
+    // nobody may hand out more than they can do themselves, including to themselves
+    let capabilities = transaction
+        .capabilities_by_role(params.role.as_str())
+        .await?;
+
+    if let Some(cap) = capabilities.iter().find(|cap| !user.has_capability(**cap)) {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            format!("you do not have the capability {cap} of this role"),
+        ));
+    }
+
     transaction
         .assign_role_to_person(*person_id, params.role.as_str())
         .await?;
@@ -412,7 +488,7 @@ async fn add_role_to_person(
 )]
 #[delete(
     "/{person_id}/roles",
-    wrap = "auth::capability::RequireManagePersons",
+    wrap = "auth::capability::RequireManageRoles",
     wrap = "cors_restrictive()"
 )]
 async fn revoke_role_from_person(
@@ -461,7 +537,7 @@ async fn get_abmeldungen_by_person(
 
 #[utoipa::path(
     path = "/api/persons/{person_id}/abmeldungen",
-    request_body = AbmeldungParams,
+    request_body = CreateAbmeldungParams,
     responses(
         (status = 201, description = "Created", body = Abmeldung),
         (status = 400, description = "Bad Request"),
@@ -477,7 +553,7 @@ async fn get_abmeldungen_by_person(
 )]
 async fn create_abmeldung(
     person_id: Path<Uuid>,
-    params: ActixJson<AbmeldungParams>,
+    params: ActixJson<CreateAbmeldungParams>,
     mut transaction: DatabaseTransaction<'_>,
 ) -> Result<impl Responder> {
     if transaction.person_by_id(*person_id).await?.is_none() {
@@ -485,7 +561,13 @@ async fn create_abmeldung(
     }
 
     let result = transaction
-        .create_abmeldung(*person_id, params.start, params.end)
+        .create_abmeldung(
+            *person_id,
+            params.start,
+            params.end,
+            params.grund.as_deref(),
+            params.privat.unwrap_or(true),
+        )
         .await?;
 
     transaction.commit().await?;
@@ -527,3 +609,300 @@ async fn revoke_abmeldung(
 
     Ok(RestStatus::Success(Some(())))
 }
+
+#[utoipa::path(
+    path = "/api/persons/{person_id}/identities",
+    responses(
+        (status = 200, description = "Success", body = Vec<String>),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get(
+    "/{person_id}/identities",
+    wrap = "auth::capability::RequireManagePersons",
+    wrap = "cors_restrictive()"
+)]
+async fn identities_by_person(
+    person_id: Path<Uuid>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    if conn.person_by_id(*person_id).await?.is_none() {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let result = conn.identities_by_person(*person_id).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/persons/{person_id}/identities",
+    request_body = IdentityParams,
+    responses(
+        (status = 200, description = "Success"),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 409, description = "Conflict"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[put(
+    "/{person_id}/identities",
+    wrap = "auth::capability::RequireManagePersons",
+    wrap = "cors_restrictive()"
+)]
+async fn link_identity(
+    user: User,
+    person_id: Path<Uuid>,
+    params: ActixJson<IdentityParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    if transaction.person_by_id(*person_id).await?.is_none() {
+        return Ok(RestStatus::NotFound);
+    }
+
+    // whoever logs in with the identity acts as the Person, so nobody may link one to a Person
+    // who can do more than they can
+    let capabilities = transaction.capabilities_by_person(*person_id).await?;
+
+    if let Some(cap) = capabilities.iter().find(|cap| !user.has_capability(**cap)) {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            format!("you do not have the capability {cap} of this person"),
+        ));
+    }
+
+    if let Some(other) = transaction
+        .person_by_user_name(params.user_name.as_str())
+        .await?
+        .filter(|other| other.user_name == params.user_name)
+    {
+        return Ok(RestStatus::BadRequest(format!(
+            "{} is the user name of person {}, delete that person first",
+            params.user_name, other.id
+        )));
+    }
+
+    if !transaction
+        .link_identity(*person_id, params.user_name.as_str())
+        .await?
+    {
+        return Ok(RestStatus::Status(
+            StatusCode::CONFLICT,
+            format!("{} is linked to a person already", params.user_name),
+        ));
+    }
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(())))
+}
+
+#[utoipa::path(
+    path = "/api/persons/{person_id}/identities",
+    request_body = IdentityParams,
+    responses(
+        (status = 200, description = "Success"),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete(
+    "/{person_id}/identities",
+    wrap = "auth::capability::RequireManagePersons",
+    wrap = "cors_restrictive()"
+)]
+async fn unlink_identity(
+    person_id: Path<Uuid>,
+    params: ActixJson<IdentityParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let result = transaction
+        .unlink_identity(*person_id, params.user_name.as_str())
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
+
+#[utoipa::path(
+    path = "/api/persons/{person_id}/sessions",
+    responses(
+        (status = 200, description = "Success", body = Vec<Session>),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get(
+    "/{person_id}/sessions",
+    wrap = "auth::capability::RequireViewAudit",
+    wrap = "cors_restrictive()"
+)]
+async fn sessions_by_person(
+    person_id: Path<Uuid>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    if conn.person_by_id(*person_id).await?.is_none() {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let result = conn.sessions_by_person(*person_id).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/persons/{person_id}/sessions",
+    responses(
+        (status = 200, description = "Success", body = Vec<Session>),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete(
+    "/{person_id}/sessions",
+    wrap = "auth::capability::RequireManagePersons",
+    wrap = "cors_restrictive()"
+)]
+async fn delete_sessions_by_person(
+    person_id: Path<Uuid>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let result = transaction.delete_sessions_by_person(*person_id).await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/persons/{person_id}/sessions/{session_id}",
+    responses(
+        (status = 200, description = "Success", body = Session),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete(
+    "/{person_id}/sessions/{session_id}",
+    wrap = "auth::capability::RequireManagePersons",
+    wrap = "cors_restrictive()"
+)]
+async fn delete_session(
+    path_params: Path<(Uuid, Uuid)>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (person_id, session_id) = path_params.into_inner();
+
+    let Some(session) = transaction.session_by_id(session_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    if session.person_id != person_id {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let result = transaction.delete_session(session_id).await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
+
+#[cfg(test)]
+mod test {
+    use actix_http::StatusCode;
+    use actix_web::dev::Service;
+    use actix_web::web::Data;
+    use actix_web::{test, App, HttpMessage};
+    use anyhow::Result;
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::database::DatabasePool;
+    use crate::domain::persons::PersonRepo;
+    use crate::domain::Capability;
+    use crate::web::auth::User;
+
+    #[sqlx::test(fixtures(
+        path = "../../database/fixtures",
+        scripts("gimme_persons", "gimme_rollen")
+    ))]
+    async fn link_identity(pool: PgPool) -> Result<()> {
+        pool.acquire()
+            .await?
+            .grant_capability_to_role("Rat", Capability::ManageSitzungen)
+            .await?;
+
+        let user = User {
+            sub: "xXBedwarsProXx".to_string(),
+            role_capabilities: [Capability::ManagePersons].into(),
+            ..Default::default()
+        };
+
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(DatabasePool::from_pool(pool.clone())))
+                .wrap_fn(move |req, srv| {
+                    req.extensions_mut().insert(user.clone());
+                    srv.call(req)
+                })
+                .service(super::service()),
+        )
+        .await;
+
+        // steve is in the Rat and may manage Sitzungen, which the user may not
+        let steve = "/persons/51288f16-4442-4d7c-9606-3dce198b0601/identities";
+        let john = "/persons/78be7f57-8340-43e0-bba2-074da360ddf4/identities";
+        let rick = "/persons/efe83006-3d2f-49dd-8801-07ada69d9e48/identities";
+        let identity = json!({ "user_name": "github-1234" });
+
+        let mehr_rechte = test::call_service(
+            &app,
+            test::TestRequest::put()
+                .uri(steve)
+                .set_json(&identity)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(mehr_rechte.status(), StatusCode::UNAUTHORIZED);
+
+        let verknuepft = test::call_service(
+            &app,
+            test::TestRequest::put()
+                .uri(john)
+                .set_json(&identity)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(verknuepft.status(), StatusCode::OK);
+
+        let schon_verknuepft = test::call_service(
+            &app,
+            test::TestRequest::put()
+                .uri(rick)
+                .set_json(&identity)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(schon_verknuepft.status(), StatusCode::CONFLICT);
+
+        let person = pool
+            .acquire()
+            .await?
+            .person_by_user_name("github-1234")
+            .await?
+            .unwrap();
+        assert_eq!(person.name, "john cena");
+
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
-use actix_web::{delete, get, put, web, Responder, Scope};
+use actix_http::StatusCode;
+use actix_web::{delete, get, put, web, web::Path, Responder, Scope};
 use actix_web_validator::Json as ActixJson;
 use serde::Deserialize;
 use utoipa::{IntoParams, ToSchema};
@@ -8,9 +9,12 @@ use crate::{
     database::{DatabaseConnection, DatabaseTransaction},
     domain::{
         persons::{PersonRepo, Role},
-        Result,
+        Capability, Result,
+    },
+    web::{
+        auth::{self, User},
+        cors_permissive, cors_restrictive, RestStatus,
     },
-    web::{cors_permissive, cors_restrictive, auth, RestStatus},
 };
 
 // Create the roles service under /roles
@@ -19,6 +23,9 @@ pub(crate) fn service() -> Scope {
         .service(get_roles)
         .service(create_role)
         .service(delete_role)
+        .service(get_role_capabilities)
+        .service(grant_role_capability)
+        .service(revoke_role_capability)
 }
 
 #[derive(Debug, IntoParams, Deserialize, ToSchema, Validate)]
@@ -27,6 +34,11 @@ pub struct RoleParams {
     name: String,
 }
 
+#[derive(Debug, IntoParams, Deserialize, ToSchema, Validate)]
+pub struct RoleCapabilityParams {
+    capability: Capability,
+}
+
 #[utoipa::path(
     path = "/api/roles",
     responses(
@@ -53,7 +65,7 @@ async fn get_roles(mut conn: DatabaseConnection) -> Result<impl Responder> {
 )]
 #[put(
     "",
-    wrap = "auth::capability::RequireManagePersons",
+    wrap = "auth::capability::RequireManageRoles",
     wrap = "cors_restrictive()"
 )]
 async fn create_role(
@@ -80,7 +92,7 @@ async fn create_role(
 )]
 #[delete(
     "",
-    wrap = "auth::capability::RequireManagePersons",
+    wrap = "auth::capability::RequireManageRoles",
     wrap = "cors_restrictive()"
 )]
 async fn delete_role(
@@ -93,3 +105,111 @@ async fn delete_role(
 
     Ok(RestStatus::Success(result))
 }
+
+#[utoipa::path(
+    path = "/api/roles/{name}/capabilities",
+    responses(
+        (status = 200, description = "Success", body = Vec<Capability>),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{name}/capabilities", wrap = "cors_permissive()")]
+async fn get_role_capabilities(
+    name: Path<String>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    if !conn.roles().await?.contains(&name) {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let result = conn.capabilities_by_role(name.as_str()).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/roles/{name}/capabilities",
+    request_body = RoleCapabilityParams,
+    responses(
+        (status = 200, description = "Success"),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[put(
+    "/{name}/capabilities",
+    wrap = "auth::capability::RequireManageRoles",
+    wrap = "cors_restrictive()"
+)]
+async fn grant_role_capability(
+    user: User,
+    name: Path<String>,
+    params: ActixJson<RoleCapabilityParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    // otherwise anyone managing roles could grant Admin
+    if !user.has_capability(params.capability) {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            format!("you do not have the capability {}", params.capability),
+        ));
+    }
+
+    if !transaction.roles().await?.contains(&name) {
+        return Ok(RestStatus::NotFound);
+    }
+
+    transaction
+        .grant_capability_to_role(name.as_str(), params.capability)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(())))
+}
+
+#[utoipa::path(
+    path = "/api/roles/{name}/capabilities",
+    request_body = RoleCapabilityParams,
+    responses(
+        (status = 200, description = "Success"),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete(
+    "/{name}/capabilities",
+    wrap = "auth::capability::RequireManageRoles",
+    wrap = "cors_restrictive()"
+)]
+async fn revoke_role_capability(
+    user: User,
+    name: Path<String>,
+    params: ActixJson<RoleCapabilityParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    // otherwise anyone managing roles could revoke Admin
+    if !user.has_capability(params.capability) {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            format!("you do not have the capability {}", params.capability),
+        ));
+    }
+
+    if !transaction.roles().await?.contains(&name) {
+        return Ok(RestStatus::NotFound);
+    }
+
+    transaction
+        .revoke_capability_from_role(name.as_str(), params.capability)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(())))
+}
@@ -0,0 +1,45 @@
+use actix_web::{get, web, Responder, Scope};
+use actix_web_validator::Query;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use crate::database::DatabaseConnection;
+use crate::domain::search::SearchResult;
+use crate::domain::{self, Result};
+use crate::web::auth::User;
+use crate::web::{cors_permissive, RestStatus};
+
+/// Create the full-text search service under /search
+pub(crate) fn service() -> Scope {
+    web::scope("/search").service(search)
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
+pub struct SearchParams {
+    /// The search query. Supports `"exact phrases"`, `or` and `-excluded` words
+    #[validate(length(min = 1, max = 256))]
+    q: String,
+}
+
+#[utoipa::path(
+    path = "/api/search",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Success", body = SearchResult),
+        (status = 400, description = "Bad Request"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("", wrap = "cors_permissive()")]
+async fn search(
+    user: User,
+    params: Query<SearchParams>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    let result = domain::search(&mut *conn, &betrachter, &params.q).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
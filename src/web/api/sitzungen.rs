@@ -1,29 +1,55 @@
 use std::borrow::Cow;
-
-use actix_web::web::{Data, Path};
-use actix_web::{delete, get, patch, post, web, Responder, Scope};
+use std::cell::RefCell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use actix_http::header;
+use actix_multipart::form::MultipartForm;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{Bytes, Data, Path};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse, Responder, Scope};
 use actix_web_validator::{Json as ActixJson, Query};
-use chrono::{DateTime, Utc};
+use async_std::channel::{self, Sender};
+use async_std::io::{Write as AsyncWrite, WriteExt};
+use async_zip::base::write::ZipFileWriter;
+use async_zip::{Compression, ZipEntryBuilder};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
+use chrono_tz::Tz;
+use futures_util::{Stream, TryStreamExt};
+use icalendar::{Calendar, Component, Event, EventLike, EventStatus};
+use log::warn;
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 use validator::{Validate, ValidationError};
 
-use crate::database::{DatabaseConnection, DatabaseTransaction};
+use crate::database::{DatabaseConnection, DatabasePool, DatabaseTransaction};
 
+use crate::domain::anhang::{Anhang, AnhangRepo};
 use crate::domain::antrag_top_attachment_map::AntragTopMapping;
+use crate::domain::blob_store::BlobStore;
+use crate::domain::bundle::BundleDatei;
 use crate::domain::calendar::{CalendarEvent, CalendarRepo};
-use crate::domain::persons::{Abmeldung, Person};
-use crate::domain::sitzung::{Sitzung, SitzungWithTops, Top, TopWithAntraege};
+use crate::domain::pagination::{Pagination, SortOrder};
+use crate::domain::persons::{Abmeldung, Person, PersonFilter};
+use crate::domain::sitzung::{
+    SerienRegel, Sitzung, SitzungFilter, SitzungSerie, SitzungSerieWithSitzungen, SitzungSort,
+    SitzungStatus, SitzungWithTops, Top, TopWithAntraege, Verschiebung, Vertagung,
+};
 use crate::domain::templates::TemplatesRepo;
 use crate::domain::{
     self,
     antrag_top_attachment_map::AntragTopAttachmentMap,
     sitzung::{SitzungRepo, SitzungTyp, TopTyp},
-    Result,
+    Capability, Result,
 };
+use crate::storage::Storage;
+use crate::web::api::attachments::{self, AttachmentParams, UploadAnhang};
+use crate::web::auth::{self, User};
 use crate::web::calendar::CalendarData;
-use crate::web::{auth, cors_permissive, cors_restrictive, RestStatus};
+use crate::web::pagination::{Paginated, PaginationParams};
+use crate::web::{cors_permissive, cors_restrictive, RestStatus};
 use crate::TEMPLATE_ENGINE;
 
 /// Create the sitzungs service under /sitzungen
@@ -32,7 +58,13 @@ pub(crate) fn service() -> Scope {
         .service(get_sitzungen)
         .service(post_sitzungen)
         .service(get_sitzungen_between)
-        .service(get_sitzungen_after);
+        .service(get_sitzungen_after)
+        .service(get_sitzungen_ics)
+        .service(get_sitzung_serien)
+        .service(post_sitzung_serie)
+        .service(get_sitzung_serie_by_id)
+        .service(patch_sitzung_serie)
+        .service(cancel_sitzung_serie);
 
     // must come last
     register_sitzung_id_service(scope)
@@ -43,10 +75,19 @@ fn register_sitzung_id_service(parent: Scope) -> Scope {
         .service(get_sitzung_by_id)
         .service(patch_sitzung_by_id)
         .service(delete_sitzung_by_id)
+        .service(patch_sitzung_status)
+        .service(verschiebe_sitzung)
+        .service(get_verschiebungen)
         .service(get_abmeldungen_by_sitzung)
         .service(get_tops)
         .service(post_tops)
-        .service(get_sitzung_template);
+        .service(get_sitzung_template)
+        .service(get_sitzung_bundle)
+        .service(get_sitzung_attachments)
+        .service(get_sitzung_attachment)
+        .service(add_sitzung_attachment)
+        .service(put_sitzung_attachment)
+        .service(delete_sitzung_attachment);
 
     // must come last
     register_top_id_service(scope)
@@ -58,6 +99,13 @@ fn register_top_id_service(parent: Scope) -> Scope {
         .service(delete_tops)
         .service(assoc_antrag)
         .service(delete_assoc_antrag)
+        .service(vertage_top)
+        .service(get_vertagungen)
+        .service(get_top_attachments)
+        .service(get_top_attachment)
+        .service(add_top_attachment)
+        .service(put_top_attachment)
+        .service(delete_top_attachment)
 }
 
 #[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
@@ -67,7 +115,50 @@ pub struct CreateSitzungParams {
     ort: String,
     typ: SitzungTyp,
     antragsfrist: DateTime<Utc>,
-    legislative_period: Uuid,
+    /// Defaults to the Legislaturperiode covering `datetime`
+    legislative_period: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, ToSchema, Validate)]
+#[validate(schema(function = "validate_sitzung_serie_params"))]
+pub struct CreateSitzungSerieParams {
+    #[validate(length(min = 1))]
+    name: String,
+    #[validate(length(min = 1))]
+    ort: String,
+    typ: SitzungTyp,
+    /// Defaults to the Legislaturperiode covering the date of each Sitzung
+    legislative_period: Option<Uuid>,
+    start: NaiveDate,
+    ende: NaiveDate,
+    #[schema(value_type = String, example = "Wed")]
+    wochentag: Weekday,
+    uhrzeit: NaiveTime,
+    /// Defaults to Europe/Berlin
+    #[schema(value_type = Option<String>, example = "Europe/Berlin")]
+    zeitzone: Option<Tz>,
+    /// Dates on which no Sitzung should take place
+    #[serde(default)]
+    ausnahmen: Vec<NaiveDate>,
+    /// How many hours before each Sitzung its Antragsfrist ends
+    #[validate(range(min = 0))]
+    antragsfrist_vorlauf_stunden: i64,
+}
+
+#[derive(Debug, Deserialize, ToSchema, Validate)]
+pub struct UpdateSitzungSerieParams {
+    #[validate(length(min = 1))]
+    ort: Option<String>,
+    typ: Option<SitzungTyp>,
+    #[validate(range(min = 0))]
+    antragsfrist_vorlauf_stunden: Option<i64>,
+    legislatur_periode_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, ToSchema, Validate)]
+pub struct CancelSitzungSerieParams {
+    #[validate(length(min = 1))]
+    grund: Option<String>,
 }
 
 #[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
@@ -88,6 +179,22 @@ pub struct UpdateSitzungParams {
     legislatur_periode_id: Option<Uuid>,
 }
 
+#[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
+pub struct SitzungStatusParams {
+    status: SitzungStatus,
+    #[validate(length(min = 1))]
+    grund: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
+pub struct VerschiebenParams {
+    datetime: DateTime<Utc>,
+    /// Defaults to the old Antragsfrist, moved by the same amount as the Sitzung
+    antragsfrist: Option<DateTime<Utc>>,
+    #[validate(length(min = 1))]
+    grund: Option<String>,
+}
+
 #[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
 pub struct UpdateTopParams {
     #[validate(length(min = 1))]
@@ -97,6 +204,21 @@ pub struct UpdateTopParams {
     weight: Option<i64>,
 }
 
+#[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
+pub struct SitzungenParams {
+    typ: Option<SitzungTyp>,
+    status: Option<SitzungStatus>,
+    legislatur_periode_id: Option<Uuid>,
+    /// Only Sitzungen at or after this time
+    ab: Option<DateTime<Utc>>,
+    /// Only Sitzungen at or before this time
+    bis: Option<DateTime<Utc>>,
+    #[serde(default)]
+    sort: SitzungSort,
+    #[serde(default)]
+    order: SortOrder,
+}
+
 #[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
 pub struct SitzungenAfterParams {
     timestamp: DateTime<Utc>,
@@ -110,9 +232,17 @@ pub struct SitzungBetweenParams {
     end: DateTime<Utc>,
 }
 
+#[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
+pub struct BundleParams {
+    /// Name of the template rendered as Einladung, defaults to `einladung`. If there is no such
+    /// template, the bundle contains no Einladung
+    einladung: Option<String>,
+}
+
 #[derive(Debug, Serialize, IntoParams, ToSchema)]
-pub struct TemplateRenderStruct {
-    sitzung: SitzungWithTops,
+pub struct TemplateRenderStruct<'a> {
+    sitzung: &'a SitzungWithTops,
+    verschiebungen: Vec<Verschiebung>,
     persons: Vec<Person>,
     calendars: Vec<TemplateCalendar>,
 }
@@ -134,23 +264,63 @@ fn validate_sitzung_between_params(
     }
 }
 
+fn validate_sitzung_serie_params(
+    params: &CreateSitzungSerieParams,
+) -> core::result::Result<(), ValidationError> {
+    if params.start > params.ende {
+        Err(ValidationError::new("sitzung_serie_params")
+            .with_message(Cow::Borrowed("start must be before ende")))
+    } else {
+        Ok(())
+    }
+}
+
 #[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
 pub struct AssocAntragParams {
     antrag_id: Uuid,
 }
 
+#[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
+pub struct VertagenParams {
+    /// The Sitzung to move the Top to
+    sitzung_id: Uuid,
+    /// Copy the Top instead of moving it
+    #[serde(default)]
+    copy: bool,
+}
+
 #[utoipa::path(
     path = "/api/sitzungen",
+    params(SitzungenParams, PaginationParams),
     responses(
-        (status = 200, description = "Success", body = Vec<Sitzung>),
+        (status = 200, description = "Success", body = Vec<Sitzung>, headers(
+            ("Link" = String, description = "Links to the first, previous, next and last page"),
+            ("X-Total-Count" = i64, description = "How many items there are on all pages"),
+        )),
+        (status = 400, description = "Bad Request"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
 #[get("", wrap = "cors_permissive()")]
-async fn get_sitzungen(mut conn: DatabaseConnection) -> Result<impl Responder> {
-    let result = conn.sitzungen().await?;
+async fn get_sitzungen(
+    params: Query<SitzungenParams>,
+    pagination: Query<PaginationParams>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let filter = SitzungFilter {
+        typ: params.typ,
+        status: params.status,
+        legislatur_periode_id: params.legislatur_periode_id,
+        ab: params.ab,
+        bis: params.bis,
+        sort: params.sort,
+        order: params.order,
+    };
+    let pagination = pagination.pagination();
 
-    Ok(RestStatus::Success(Some(result)))
+    let result = conn.sitzungen(&filter, pagination).await?;
+
+    Ok(Paginated::new(result, pagination))
 }
 
 #[utoipa::path(
@@ -172,13 +342,26 @@ async fn post_sitzungen(
     params: ActixJson<CreateSitzungParams>,
     mut transaction: DatabaseTransaction<'_>,
 ) -> Result<impl Responder> {
+    let legislatur_periode = match params.legislative_period {
+        Some(legislatur_periode) => legislatur_periode,
+        None => match domain::legislatur_periode_at(&mut *transaction, params.datetime).await? {
+            Some(legislatur_periode) => legislatur_periode.id,
+            None => {
+                return Ok(RestStatus::BadRequest(
+                    "no legislative period covers the date of the sitzung".to_string(),
+                ))
+            }
+        },
+    };
+
     let result = transaction
         .create_sitzung(
             params.datetime,
             params.ort.as_str(),
             params.typ,
             params.antragsfrist,
-            params.legislative_period,
+            legislatur_periode,
+            None,
         )
         .await?;
 
@@ -199,11 +382,15 @@ async fn post_sitzungen(
 )]
 #[get("/after", wrap = "cors_permissive()")]
 async fn get_sitzungen_after(
+    user: User,
     params: Query<SitzungenAfterParams>,
     mut conn: DatabaseConnection,
 ) -> Result<impl Responder> {
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
     let result =
-        domain::sitzungen_after_with_tops(&mut *conn, params.timestamp, params.limit).await?;
+        domain::sitzungen_after_with_tops(&mut *conn, &betrachter, params.timestamp, params.limit)
+            .await?;
 
     Ok(RestStatus::Success(result))
 }
@@ -227,6 +414,213 @@ async fn get_sitzungen_between(
     Ok(RestStatus::Success(Some(result)))
 }
 
+#[utoipa::path(
+    path = "/api/sitzungen/ics",
+    responses(
+        (status = 200, description = "Success", body = String, content_type = "text/calendar"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/ics", wrap = "cors_permissive()")]
+async fn get_sitzungen_ics(mut conn: DatabaseConnection) -> Result<impl Responder> {
+    let sitzungen = conn
+        .sitzungen(&SitzungFilter::default(), Pagination::alle())
+        .await?
+        .items;
+
+    let mut calendar = Calendar::new();
+    calendar.name("Sitzungen");
+
+    for sitzung in &sitzungen {
+        calendar.push(sitzung_event(sitzung));
+    }
+
+    Ok(HttpResponse::Ok()
+        .append_header((header::CONTENT_TYPE, "text/calendar; charset=utf-8"))
+        .body(calendar.done().to_string()))
+}
+
+fn sitzung_event(sitzung: &Sitzung) -> Event {
+    let status = match sitzung.status {
+        SitzungStatus::Abgesagt => EventStatus::Cancelled,
+        _ => EventStatus::Confirmed,
+    };
+
+    let mut event = Event::new();
+    event
+        .uid(&sitzung.id.to_string())
+        .summary(sitzung.typ.bezeichnung())
+        .location(&sitzung.ort)
+        .starts(sitzung.datetime)
+        .status(status);
+
+    if let Some(grund) = &sitzung.status_grund {
+        event.description(grund);
+    }
+
+    event.done()
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/serien",
+    responses(
+        (status = 200, description = "Success", body = Vec<SitzungSerie>),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/serien", wrap = "cors_permissive()")]
+async fn get_sitzung_serien(mut conn: DatabaseConnection) -> Result<impl Responder> {
+    let result = conn.sitzung_serien().await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/serien",
+    request_body = CreateSitzungSerieParams,
+    responses(
+        (status = 201, description = "Created", body = SitzungSerieWithSitzungen),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[post(
+    "/serien",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn post_sitzung_serie(
+    params: ActixJson<CreateSitzungSerieParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let regel = SerienRegel {
+        start: params.start,
+        ende: params.ende,
+        wochentag: params.wochentag,
+        uhrzeit: params.uhrzeit,
+        zeitzone: params.zeitzone.unwrap_or(chrono_tz::Europe::Berlin),
+        ausnahmen: params.ausnahmen.clone(),
+    };
+
+    let result = domain::create_sitzung_serie(
+        &mut *transaction,
+        params.name.as_str(),
+        &regel,
+        TimeDelta::hours(params.antragsfrist_vorlauf_stunden),
+        params.ort.as_str(),
+        params.typ,
+        params.legislative_period,
+    )
+    .await?;
+
+    let Some(result) = result else {
+        return Ok(RestStatus::BadRequest(
+            "no legislative period covers every date of the serie".to_string(),
+        ));
+    };
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Created(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/serien/{serie_id}",
+    responses(
+        (status = 200, description = "Success", body = SitzungSerieWithSitzungen),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/serien/{serie_id}", wrap = "cors_permissive()")]
+async fn get_sitzung_serie_by_id(
+    serie_id: Path<Uuid>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let Some(serie) = conn.sitzung_serie_by_id(*serie_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    let sitzungen = conn.sitzungen_by_serie(*serie_id).await?;
+
+    Ok(RestStatus::Success(Some(SitzungSerieWithSitzungen {
+        serie,
+        sitzungen,
+    })))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/serien/{serie_id}",
+    request_body = UpdateSitzungSerieParams,
+    responses(
+        (status = 200, description = "Success", body = Vec<Sitzung>),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[patch(
+    "/serien/{serie_id}",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn patch_sitzung_serie(
+    serie_id: Path<Uuid>,
+    params: ActixJson<UpdateSitzungSerieParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let result = domain::update_sitzung_serie(
+        &mut *transaction,
+        *serie_id,
+        Utc::now(),
+        params.ort.as_deref(),
+        params.typ,
+        params.antragsfrist_vorlauf_stunden.map(TimeDelta::hours),
+        params.legislatur_periode_id,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/serien/{serie_id}/absagen",
+    request_body = CancelSitzungSerieParams,
+    responses(
+        (status = 200, description = "Success", body = Vec<Sitzung>),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[post(
+    "/serien/{serie_id}/absagen",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn cancel_sitzung_serie(
+    serie_id: Path<Uuid>,
+    params: ActixJson<CancelSitzungSerieParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let result = domain::cancel_sitzung_serie(
+        &mut *transaction,
+        *serie_id,
+        Utc::now(),
+        params.grund.as_deref(),
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
+
 #[utoipa::path(
     path = "/api/sitzungen/{sitzung_id}",
     responses(
@@ -237,10 +631,13 @@ async fn get_sitzungen_between(
 )]
 #[get("/{sitzung_id}", wrap = "cors_permissive()")]
 async fn get_sitzung_by_id(
+    user: User,
     sitzung_id: Path<Uuid>,
     mut conn: DatabaseConnection,
 ) -> Result<impl Responder> {
-    let result = domain::sitzung_with_tops(&mut *conn, *sitzung_id).await?;
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    let result = domain::sitzung_with_tops(&mut *conn, &betrachter, *sitzung_id).await?;
 
     Ok(RestStatus::Success(result))
 }
@@ -307,6 +704,93 @@ async fn delete_sitzung_by_id(
     Ok(RestStatus::Success(Some(result)))
 }
 
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/status",
+    request_body = SitzungStatusParams,
+    responses(
+        (status = 200, description = "Success", body = Sitzung),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[patch(
+    "/{sitzung_id}/status",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn patch_sitzung_status(
+    sitzung_id: Path<Uuid>,
+    params: ActixJson<SitzungStatusParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let result = transaction
+        .set_sitzung_status(*sitzung_id, params.status, params.grund.as_deref())
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/verschieben",
+    request_body = VerschiebenParams,
+    responses(
+        (status = 200, description = "Success", body = Sitzung),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[post(
+    "/{sitzung_id}/verschieben",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn verschiebe_sitzung(
+    sitzung_id: Path<Uuid>,
+    params: ActixJson<VerschiebenParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let result = domain::verschiebe_sitzung(
+        &mut *transaction,
+        *sitzung_id,
+        params.datetime,
+        params.antragsfrist,
+        params.grund.as_deref(),
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/verschiebungen",
+    responses(
+        (status = 200, description = "Success", body = Vec<Verschiebung>),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{sitzung_id}/verschiebungen", wrap = "cors_permissive()")]
+async fn get_verschiebungen(
+    sitzung_id: Path<Uuid>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    if conn.sitzung_by_id(*sitzung_id).await?.is_none() {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let result = conn.verschiebungen_by_sitzung(*sitzung_id).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
 #[utoipa::path(
     path = "/api/sitzungen/{sitzung_id}/abmeldungen",
     responses(
@@ -317,6 +801,7 @@ async fn delete_sitzung_by_id(
 )]
 #[get("/{sitzung_id}/abmeldungen", wrap = "cors_permissive()")]
 async fn get_abmeldungen_by_sitzung(
+    user: User,
     sitzung_id: Path<Uuid>,
     mut conn: DatabaseConnection,
 ) -> Result<impl Responder> {
@@ -324,7 +809,23 @@ async fn get_abmeldungen_by_sitzung(
         return Ok(RestStatus::NotFound);
     }
 
-    let result = domain::abmeldungen_by_sitzung(&mut *conn, *sitzung_id).await?;
+    let alle = user.has_capability(Capability::ManagePersons);
+    let person_id = user.find_person(&mut *conn).await?.map(|person| person.id);
+
+    let result = domain::abmeldungen_by_sitzung(&mut *conn, *sitzung_id)
+        .await?
+        .map(|abmeldungen| {
+            abmeldungen
+                .into_iter()
+                .map(|abmeldung| {
+                    if alle || Some(abmeldung.person_id) == person_id {
+                        abmeldung
+                    } else {
+                        abmeldung.ohne_privaten_grund()
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
 
     Ok(RestStatus::Success(result))
 }
@@ -339,12 +840,18 @@ async fn get_abmeldungen_by_sitzung(
     )
 )]
 #[get("/{sitzung_id}/tops", wrap = "cors_permissive()")]
-async fn get_tops(sitzung_id: Path<Uuid>, mut conn: DatabaseConnection) -> Result<impl Responder> {
+async fn get_tops(
+    user: User,
+    sitzung_id: Path<Uuid>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
     if conn.sitzung_by_id(*sitzung_id).await?.is_none() {
         return Ok(RestStatus::NotFound);
     }
 
-    let result = domain::top_with_antraege_by_sitzung(&mut *conn, *sitzung_id).await?;
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    let result = domain::top_with_antraege_by_sitzung(&mut *conn, &betrachter, *sitzung_id).await?;
 
     Ok(RestStatus::Success(Some(result)))
 }
@@ -543,29 +1050,129 @@ async fn delete_assoc_antrag(
 }
 
 #[utoipa::path(
-    path = "/api/sitzungen/{sitzung_id}/template/{name}",
+    path = "/api/sitzungen/{sitzung_id}/tops/{top_id}/vertagen",
+    request_body = VertagenParams,
     responses(
-        (status = 200, description = "Sucess", body = String),
+        (status = 200, description = "Sucess", body = TopWithAntraege),
         (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
         (status = 404, description = "Not Found"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
-#[get("/{sitzung_id}/template/{name}", wrap = "cors_permissive()")]
-async fn get_sitzung_template(
-    path_params: Path<(Uuid, String)>,
-    mut conn: DatabaseConnection,
-    calendars: Data<CalendarData>,
+#[post(
+    "/{sitzung_id}/tops/{top_id}/vertagen",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn vertage_top(
+    path_params: Path<(Uuid, Uuid)>,
+    params: ActixJson<VertagenParams>,
+    mut transaction: DatabaseTransaction<'_>,
 ) -> Result<impl Responder> {
-    let (sitzung_id, template_name) = path_params.into_inner();
+    let (sitzung_id, top_id) = path_params.into_inner();
 
-    let Some(sitzung) = domain::sitzung_with_tops(&mut *conn, sitzung_id).await? else {
+    if sitzung_id == params.sitzung_id {
+        return Ok(RestStatus::BadRequest(
+            "the top already belongs to this sitzung".to_string(),
+        ));
+    }
+
+    if transaction.sitzung_by_id(sitzung_id).await?.is_none() {
         return Ok(RestStatus::NotFound);
-    };
+    }
 
-    let persons = domain::persons::PersonRepo::persons(&mut *conn).await?;
+    let result = domain::vertage_top(
+        &mut *transaction,
+        top_id,
+        sitzung_id,
+        params.sitzung_id,
+        params.copy,
+    )
+    .await?;
 
-    let calendar_names = calendars.calendar_names();
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/tops/{top_id}/vertagungen",
+    responses(
+        (status = 200, description = "Sucess", body = Vec<Vertagung>),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{sitzung_id}/tops/{top_id}/vertagungen", wrap = "cors_permissive()")]
+async fn get_vertagungen(
+    path_params: Path<(Uuid, Uuid)>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let (sitzung_id, top_id) = path_params.into_inner();
+
+    let result = conn.vertagungen_by_top(top_id, sitzung_id).await?;
+
+    // a Top vertagt to another Sitzung still has its Vertagungen in the one it was vertagt from
+    if result.is_empty()
+        && top_of_sitzung(&mut *conn, sitzung_id, top_id)
+            .await?
+            .is_none()
+    {
+        return Ok(RestStatus::NotFound);
+    }
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/template/{name}",
+    responses(
+        (status = 200, description = "Sucess", body = String),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{sitzung_id}/template/{name}", wrap = "cors_permissive()")]
+async fn get_sitzung_template(
+    user: User,
+    path_params: Path<(Uuid, String)>,
+    mut conn: DatabaseConnection,
+    calendars: Data<CalendarData>,
+) -> Result<impl Responder> {
+    let (sitzung_id, template_name) = path_params.into_inner();
+
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    let Some(sitzung) = domain::sitzung_with_tops(&mut *conn, &betrachter, sitzung_id).await?
+    else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    let result = render_sitzung_template(&mut conn, &calendars, &sitzung, &template_name).await?;
+
+    Ok(RestStatus::Success(result))
+}
+
+/// Renders a template for the Sitzung, if the template and all calendars exist
+async fn render_sitzung_template(
+    conn: &mut DatabaseConnection,
+    calendars: &CalendarData,
+    sitzung: &SitzungWithTops,
+    template_name: &str,
+) -> Result<Option<String>> {
+    let verschiebungen = conn.verschiebungen_by_sitzung(sitzung.sitzung.id).await?;
+
+    let persons = domain::persons::PersonRepo::persons(
+        &mut **conn,
+        &PersonFilter::default(),
+        Pagination::alle(),
+    )
+    .await?
+    .items;
+
+    let calendar_names = calendars.calendar_names();
 
     let mut calendars_events = Vec::new();
     for name in calendar_names {
@@ -573,7 +1180,7 @@ async fn get_sitzung_template(
             .calender_by_name(&Cow::Borrowed(name.as_str()))
             .await?
         else {
-            return Ok(RestStatus::NotFound);
+            return Ok(None);
         };
         calendars_events.push(TemplateCalendar {
             name: name.to_string(),
@@ -581,8 +1188,8 @@ async fn get_sitzung_template(
         });
     }
 
-    let Some(template) = conn.template_by_name(&template_name).await? else {
-        return Ok(RestStatus::NotFound);
+    let Some(template) = conn.template_by_name(template_name).await? else {
+        return Ok(None);
     };
 
     let result = TEMPLATE_ENGINE
@@ -591,10 +1198,667 @@ async fn get_sitzung_template(
         .template(&template.name)
         .render(TemplateRenderStruct {
             sitzung,
+            verschiebungen,
             persons,
             calendars: calendars_events,
         })
         .to_string()?;
 
+    Ok(Some(result))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/bundle.zip",
+    params(BundleParams),
+    responses(
+        (status = 200, description = "Success", content_type = "application/zip"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{sitzung_id}/bundle.zip", wrap = "cors_permissive()")]
+async fn get_sitzung_bundle(
+    user: User,
+    sitzung_id: Path<Uuid>,
+    params: Query<BundleParams>,
+    storage: Data<Storage>,
+    calendars: Data<CalendarData>,
+    mut conn: DatabaseConnection,
+) -> Result<HttpResponse> {
+    let betrachter = user.antrag_betrachter(&mut *conn).await?;
+
+    let Some(sitzung) = domain::sitzung_with_tops(&mut *conn, &betrachter, *sitzung_id).await?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let dateiname = format!("Sitzung {}.zip", sitzung.sitzung.datetime.date_naive());
+
+    let template_name = params.einladung.as_deref().unwrap_or("einladung");
+    let einladung = render_sitzung_template(&mut conn, &calendars, &sitzung, template_name).await?;
+
+    let dateien = domain::sitzung_bundle(&mut *conn, &sitzung, einladung).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(dateiname)],
+        })
+        .streaming(bundle_stream(storage, dateien)))
+}
+
+/// Streams the bundle while it is written, so no Anhang is ever held in memory as a whole
+fn bundle_stream(
+    storage: Data<Storage>,
+    dateien: Vec<BundleDatei>,
+) -> impl Stream<Item = Result<Bytes>> {
+    // a single chunk is buffered, writing waits until it was sent
+    let (sender, receiver) = channel::bounded(1);
+
+    actix_web::rt::spawn(async move {
+        if let Err(e) = write_bundle(storage.get_ref(), dateien, &sender).await {
+            // the download is broken off, if it was not already
+            let _ = sender.send(Err(e)).await;
+        }
+    });
+
+    receiver
+}
+
+/// Writes the zip of the bundle, sending everything written so far whenever a chunk was written
+async fn write_bundle(
+    storage: &impl BlobStore,
+    dateien: Vec<BundleDatei>,
+    sender: &Sender<Result<Bytes>>,
+) -> Result<()> {
+    let puffer = SharedBuffer::default();
+    let mut writer = ZipFileWriter::new(puffer.clone());
+
+    for datei in dateien {
+        match datei {
+            BundleDatei::Text { pfad, inhalt } => {
+                let entry = ZipEntryBuilder::new(pfad.into(), Compression::Deflate)
+                    .last_modification_date(Utc::now().into());
+
+                writer.write_entry_whole(entry, inhalt.as_bytes()).await?;
+            }
+            BundleDatei::Anhang { pfad, anhang } => {
+                // the download started already, so a missing Anhang is left out instead of
+                // breaking off the zip
+                let Some(mut inhalt) = storage.get_stream(&anhang.id.to_string(), None).await?
+                else {
+                    warn!(
+                        "the content of attachment {} is missing, leaving it out of the bundle",
+                        anhang.id
+                    );
+                    continue;
+                };
+
+                // Anhänge are mostly PDFs, images and office documents, which are compressed
+                // already
+                let entry = ZipEntryBuilder::new(pfad.into(), Compression::Stored)
+                    .last_modification_date(anhang.erstellt_am.into());
+
+                let mut entry_writer = writer.write_entry_stream(entry).await?;
+
+                while let Some(chunk) = inhalt.try_next().await? {
+                    entry_writer.write_all(&chunk).await?;
+                    send_written(&puffer, sender).await?;
+                }
+
+                entry_writer.close().await?;
+            }
+        }
+
+        send_written(&puffer, sender).await?;
+    }
+
+    writer.close().await?;
+
+    send_written(&puffer, sender).await
+}
+
+async fn send_written(puffer: &SharedBuffer, sender: &Sender<Result<Bytes>>) -> Result<()> {
+    let written = puffer.take();
+
+    if written.is_empty() {
+        return Ok(());
+    }
+
+    sender
+        .send(Ok(written))
+        .await
+        .map_err(|_| domain::Error::Message("the bundle download was aborted".to_string()))
+}
+
+/// The bytes written to it are taken out in between writes, while the zip writer still owns it
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Bytes {
+        Bytes::from(std::mem::take(&mut *self.0.borrow_mut()))
+    }
+}
+
+impl AsyncWrite for SharedBuffer {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.0.borrow_mut().extend_from_slice(buf);
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The Top, if it belongs to the Sitzung
+async fn top_of_sitzung(
+    conn: &mut impl SitzungRepo,
+    sitzung_id: Uuid,
+    top_id: Uuid,
+) -> Result<Option<Top>> {
+    let tops = conn.tops_by_sitzung(sitzung_id).await?;
+
+    Ok(tops.into_iter().find(|top| top.id == top_id))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/attachments",
+    responses(
+        (status = 200, description = "Success", body = Vec<Anhang>),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{sitzung_id}/attachments", wrap = "cors_permissive()")]
+async fn get_sitzung_attachments(
+    sitzung_id: Path<Uuid>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    if conn.sitzung_by_id(*sitzung_id).await?.is_none() {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let result = conn.anhaenge_by_sitzung(*sitzung_id).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/attachments/{attachment_id}",
+    params(AttachmentParams),
+    responses(
+        (status = 200, description = "Success"),
+        (status = 206, description = "Partial Content"),
+        (status = 404, description = "Not Found"),
+        (status = 416, description = "Range Not Satisfiable"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get(
+    "/{sitzung_id}/attachments/{attachment_id}",
+    wrap = "cors_permissive()"
+)]
+async fn get_sitzung_attachment(
+    req: HttpRequest,
+    path_params: Path<(Uuid, Uuid)>,
+    params: Query<AttachmentParams>,
+    storage: Data<Storage>,
+    mut conn: DatabaseConnection,
+) -> Result<HttpResponse> {
+    let (sitzung_id, attachment_id) = path_params.into_inner();
+
+    let Some(attachment) = conn.anhang_by_sitzung(sitzung_id, attachment_id).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    attachments::serve_anhang(&req, &storage, attachment, params.inline()).await
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/attachments",
+    responses(
+        (status = 200, description = "Success", body = Anhang),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[post(
+    "/{sitzung_id}/attachments",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn add_sitzung_attachment(
+    user: User,
+    sitzung_id: Path<Uuid>,
+    form: MultipartForm<UploadAnhang>,
+    storage: Data<Storage>,
+    database: Data<DatabasePool>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    if transaction.sitzung_by_id(*sitzung_id).await?.is_none() {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let staged = match attachments::stage_upload(&user, &form, &storage, &database).await? {
+        Ok(staged) => staged,
+        Err(reason) => return Ok(RestStatus::BadRequest(reason)),
+    };
+
+    let attachment = attachments::confirm(&mut transaction, staged).await?;
+
+    transaction
+        .add_anhang_to_sitzung(*sitzung_id, attachment.id)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(attachment)))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/attachments/{attachment_id}",
+    responses(
+        (status = 200, description = "Success", body = Anhang),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[put(
+    "/{sitzung_id}/attachments/{attachment_id}",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn put_sitzung_attachment(
+    user: User,
+    path_params: Path<(Uuid, Uuid)>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (sitzung_id, attachment_id) = path_params.into_inner();
+
+    if transaction.sitzung_by_id(sitzung_id).await?.is_none() {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let person = user.query_person(&mut *transaction).await?;
+
+    // only own staged Anhänge, like those of a finished chunked upload, can be attached
+    let Some(attachment) = transaction.confirm_anhang(attachment_id, person.id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    transaction
+        .add_anhang_to_sitzung(sitzung_id, attachment.id)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(attachment)))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/attachments/{attachment_id}",
+    responses(
+        (status = 200, description = "Success"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete(
+    "/{sitzung_id}/attachments/{attachment_id}",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn delete_sitzung_attachment(
+    path_params: Path<(Uuid, Uuid)>,
+    storage: Data<Storage>,
+    database: Data<DatabasePool>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (sitzung_id, attachment_id) = path_params.into_inner();
+
+    if transaction
+        .anhang_by_sitzung(sitzung_id, attachment_id)
+        .await?
+        .is_none()
+    {
+        return Ok(RestStatus::NotFound);
+    }
+
+    transaction
+        .delete_anhang_from_sitzung(sitzung_id, attachment_id)
+        .await?;
+
+    transaction.commit().await?;
+
+    attachments::delete_if_unused(&database, &storage, attachment_id).await;
+
+    Ok(RestStatus::Success(Some(())))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/tops/{top_id}/attachments",
+    responses(
+        (status = 200, description = "Success", body = Vec<Anhang>),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{sitzung_id}/tops/{top_id}/attachments", wrap = "cors_permissive()")]
+async fn get_top_attachments(
+    path_params: Path<(Uuid, Uuid)>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    let (sitzung_id, top_id) = path_params.into_inner();
+
+    if top_of_sitzung(&mut *conn, sitzung_id, top_id)
+        .await?
+        .is_none()
+    {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let result = conn.anhaenge_by_top(top_id).await?;
+
     Ok(RestStatus::Success(Some(result)))
 }
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/tops/{top_id}/attachments/{attachment_id}",
+    params(AttachmentParams),
+    responses(
+        (status = 200, description = "Success"),
+        (status = 206, description = "Partial Content"),
+        (status = 404, description = "Not Found"),
+        (status = 416, description = "Range Not Satisfiable"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get(
+    "/{sitzung_id}/tops/{top_id}/attachments/{attachment_id}",
+    wrap = "cors_permissive()"
+)]
+async fn get_top_attachment(
+    req: HttpRequest,
+    path_params: Path<(Uuid, Uuid, Uuid)>,
+    params: Query<AttachmentParams>,
+    storage: Data<Storage>,
+    mut conn: DatabaseConnection,
+) -> Result<HttpResponse> {
+    let (sitzung_id, top_id, attachment_id) = path_params.into_inner();
+
+    let Some(attachment) = conn
+        .anhang_by_top(sitzung_id, top_id, attachment_id)
+        .await?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    attachments::serve_anhang(&req, &storage, attachment, params.inline()).await
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/tops/{top_id}/attachments",
+    responses(
+        (status = 200, description = "Success", body = Anhang),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[post(
+    "/{sitzung_id}/tops/{top_id}/attachments",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn add_top_attachment(
+    user: User,
+    path_params: Path<(Uuid, Uuid)>,
+    form: MultipartForm<UploadAnhang>,
+    storage: Data<Storage>,
+    database: Data<DatabasePool>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (sitzung_id, top_id) = path_params.into_inner();
+
+    if top_of_sitzung(&mut *transaction, sitzung_id, top_id)
+        .await?
+        .is_none()
+    {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let staged = match attachments::stage_upload(&user, &form, &storage, &database).await? {
+        Ok(staged) => staged,
+        Err(reason) => return Ok(RestStatus::BadRequest(reason)),
+    };
+
+    let attachment = attachments::confirm(&mut transaction, staged).await?;
+
+    transaction.add_anhang_to_top(top_id, attachment.id).await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(attachment)))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/tops/{top_id}/attachments/{attachment_id}",
+    responses(
+        (status = 200, description = "Success", body = Anhang),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[put(
+    "/{sitzung_id}/tops/{top_id}/attachments/{attachment_id}",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn put_top_attachment(
+    user: User,
+    path_params: Path<(Uuid, Uuid, Uuid)>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (sitzung_id, top_id, attachment_id) = path_params.into_inner();
+
+    if top_of_sitzung(&mut *transaction, sitzung_id, top_id)
+        .await?
+        .is_none()
+    {
+        return Ok(RestStatus::NotFound);
+    }
+
+    let person = user.query_person(&mut *transaction).await?;
+
+    // only own staged Anhänge, like those of a finished chunked upload, can be attached
+    let Some(attachment) = transaction.confirm_anhang(attachment_id, person.id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    transaction.add_anhang_to_top(top_id, attachment.id).await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(Some(attachment)))
+}
+
+#[utoipa::path(
+    path = "/api/sitzungen/{sitzung_id}/tops/{top_id}/attachments/{attachment_id}",
+    responses(
+        (status = 200, description = "Success"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete(
+    "/{sitzung_id}/tops/{top_id}/attachments/{attachment_id}",
+    wrap = "auth::capability::RequireManageSitzungen",
+    wrap = "cors_restrictive()"
+)]
+async fn delete_top_attachment(
+    path_params: Path<(Uuid, Uuid, Uuid)>,
+    storage: Data<Storage>,
+    database: Data<DatabasePool>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    let (sitzung_id, top_id, attachment_id) = path_params.into_inner();
+
+    if transaction
+        .anhang_by_top(sitzung_id, top_id, attachment_id)
+        .await?
+        .is_none()
+    {
+        return Ok(RestStatus::NotFound);
+    }
+
+    transaction
+        .delete_anhang_from_top(top_id, attachment_id)
+        .await?;
+
+    transaction.commit().await?;
+
+    attachments::delete_if_unused(&database, &storage, attachment_id).await;
+
+    Ok(RestStatus::Success(Some(())))
+}
+
+#[cfg(test)]
+mod test {
+    use async_std::channel;
+    use async_zip::base::read::mem::ZipFileReader;
+    use chrono::Utc;
+    use futures_util::StreamExt;
+    use uuid::Uuid;
+
+    use super::write_bundle;
+    use crate::domain::anhang::Anhang;
+    use crate::domain::blob_store::BlobStore;
+    use crate::domain::bundle::BundleDatei;
+    use crate::storage::memory::MemoryStore;
+
+    #[actix_web::test]
+    async fn writes_bundle_while_streaming_it() -> anyhow::Result<()> {
+        let storage = MemoryStore::default();
+        let anhang = Anhang {
+            id: Uuid::new_v4(),
+            filename: "scan.pdf".to_string(),
+            content_type: "application/pdf".to_string(),
+            size: Some(4),
+            sha256: None,
+            erstellt_am: Utc::now(),
+            bestaetigt: true,
+            hochgeladen_von: None,
+        };
+        storage
+            .put(&anhang.id.to_string(), b"%PDF".to_vec())
+            .await?;
+
+        let dateien = vec![
+            BundleDatei::Text {
+                pfad: "einladung.md".to_string(),
+                inhalt: "# Einladung".to_string(),
+            },
+            BundleDatei::Anhang {
+                pfad: "anhaenge/scan.pdf".to_string(),
+                anhang,
+            },
+        ];
+
+        let (sender, receiver) = channel::bounded(1);
+        let schreiben = async move { write_bundle(&storage, dateien, &sender).await };
+        let (written, chunks) = futures_util::join!(schreiben, receiver.collect::<Vec<_>>());
+        written?;
+
+        // every file is sent on its own
+        assert!(chunks.len() > 2);
+
+        let zip = chunks.into_iter().collect::<Result<Vec<_>, _>>()?.concat();
+        let reader = ZipFileReader::new(zip).await?;
+
+        let mut inhalte = vec![];
+        for (index, entry) in reader.file().entries().iter().enumerate() {
+            let mut inhalt = vec![];
+            reader
+                .reader_with_entry(index)
+                .await?
+                .read_to_end_checked(&mut inhalt)
+                .await?;
+
+            inhalte.push((entry.filename().as_str()?.to_string(), inhalt));
+        }
+
+        assert_eq!(
+            inhalte,
+            vec![
+                ("einladung.md".to_string(), b"# Einladung".to_vec()),
+                ("anhaenge/scan.pdf".to_string(), b"%PDF".to_vec()),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn leaves_out_missing_anhaenge() -> anyhow::Result<()> {
+        let storage = MemoryStore::default();
+        let fehlend = Anhang {
+            id: Uuid::new_v4(),
+            filename: "weg.pdf".to_string(),
+            content_type: "application/pdf".to_string(),
+            size: Some(4),
+            sha256: None,
+            erstellt_am: Utc::now(),
+            bestaetigt: true,
+            hochgeladen_von: None,
+        };
+
+        let dateien = vec![
+            BundleDatei::Anhang {
+                pfad: "anhaenge/weg.pdf".to_string(),
+                anhang: fehlend,
+            },
+            BundleDatei::Text {
+                pfad: "einladung.md".to_string(),
+                inhalt: "# Einladung".to_string(),
+            },
+        ];
+
+        let (sender, receiver) = channel::bounded(1);
+        let schreiben = async move { write_bundle(&storage, dateien, &sender).await };
+        let (written, chunks) = futures_util::join!(schreiben, receiver.collect::<Vec<_>>());
+        written?;
+
+        let zip = chunks.into_iter().collect::<Result<Vec<_>, _>>()?.concat();
+        let reader = ZipFileReader::new(zip).await?;
+
+        let namen = reader
+            .file()
+            .entries()
+            .iter()
+            .map(|entry| entry.filename().as_str().map(ToString::to_string))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(namen, vec!["einladung.md".to_string()]);
+
+        Ok(())
+    }
+}
@@ -9,7 +9,7 @@ use crate::database::DatabaseConnection;
 
 use crate::domain::templates::{Template, TemplatesRepo};
 use crate::domain::Result;
-use crate::web::{cors_permissive, cors_restrictive, RestStatus};
+use crate::web::{auth, cors_permissive, cors_restrictive, RestStatus};
 use crate::TEMPLATE_ENGINE;
 
 #[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
@@ -68,10 +68,15 @@ async fn get_template_by_name(
     path = "/api/templates/{template_name}",
     responses(
         (status = 200, description = "Success", body = Template),
+        (status = 401, description = "Unauthorized"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
-#[delete("/{template_name}", wrap = "cors_restrictive()")]
+#[delete(
+    "/{template_name}",
+    wrap = "auth::capability::RequireManageTemplates",
+    wrap = "cors_restrictive()"
+)]
 async fn delete_template(
     name: Path<String>,
     mut conn: DatabaseConnection,
@@ -88,10 +93,15 @@ async fn delete_template(
     request_body = UpdateTemplateParams,
     responses(
         (status = 200, description = "Success", body = Template),
+        (status = 401, description = "Unauthorized"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
-#[patch("/{template_name}", wrap = "cors_restrictive()")]
+#[patch(
+    "/{template_name}",
+    wrap = "auth::capability::RequireManageTemplates",
+    wrap = "cors_restrictive()"
+)]
 async fn patch_template(
     name: Path<String>,
     params: ActixJson<UpdateTemplateParams>,
@@ -111,10 +121,15 @@ async fn patch_template(
     request_body = CreateTemplateParams,
     responses(
         (status = 200, description = "Success", body = Template),
+        (status = 401, description = "Unauthorized"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
-#[post("", wrap = "cors_restrictive()")]
+#[post(
+    "",
+    wrap = "auth::capability::RequireManageTemplates",
+    wrap = "cors_restrictive()"
+)]
 async fn create_template(
     params: ActixJson<CreateTemplateParams>,
     mut conn: DatabaseConnection,
@@ -0,0 +1,148 @@
+use actix_http::StatusCode;
+use actix_web::web::Path;
+use actix_web::{delete, get, post, web, Responder, Scope};
+use actix_web_validator::Json as ActixJson;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::database::{DatabaseConnection, DatabaseTransaction};
+use crate::domain::api_token::{self, ApiToken, ApiTokenRepo, CreatedApiToken};
+use crate::domain::{Capability, Result};
+use crate::web::auth::User;
+use crate::web::{cors_restrictive, RestStatus};
+
+/// Create the api token service under /tokens
+pub(crate) fn service() -> Scope {
+    web::scope("/tokens")
+        .service(get_api_tokens)
+        .service(create_api_token)
+        .service(revoke_api_token)
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
+pub struct CreateApiTokenParams {
+    #[validate(length(min = 1))]
+    name: String,
+    /// Must be a subset of the capabilities of the creating user
+    capabilities: Vec<Capability>,
+    ablaufdatum: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    path = "/api/tokens",
+    responses(
+        (status = 200, description = "Success", body = Vec<ApiToken>),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("", wrap = "cors_restrictive()")]
+async fn get_api_tokens(user: User, mut conn: DatabaseConnection) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you need to be logged in to manage api tokens".to_string(),
+        ));
+    }
+
+    let person = user.query_person(&mut *conn).await?;
+
+    let result = conn.api_tokens_by_person(person.id).await?;
+
+    Ok(RestStatus::Success(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/tokens",
+    request_body = CreateApiTokenParams,
+    responses(
+        (status = 201, description = "Created", body = CreatedApiToken),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[post("", wrap = "cors_restrictive()")]
+async fn create_api_token(
+    user: User,
+    params: ActixJson<CreateApiTokenParams>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    if user.is_anonymous() || user.api_token.is_some() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "api tokens can only be created after logging in".to_string(),
+        ));
+    }
+
+    // tokens are checked against the capabilities of the Person when they are used, capabilities
+    // only granted through OAuth groups would make them useless
+    let owned = user.owned_capabilities();
+
+    if let Some(cap) = params
+        .capabilities
+        .iter()
+        .find(|cap| !owned.contains(cap) && !owned.contains(&Capability::Admin))
+    {
+        return Ok(RestStatus::BadRequest(format!(
+            "you do not have the capability {cap}"
+        )));
+    }
+
+    if params.ablaufdatum <= Utc::now() {
+        return Ok(RestStatus::BadRequest(
+            "ablaufdatum must be in the future".to_string(),
+        ));
+    }
+
+    let person = user.query_person(&mut *transaction).await?;
+
+    let result = api_token::create_api_token(
+        &mut *transaction,
+        person.id,
+        params.name.as_str(),
+        &params.capabilities,
+        params.ablaufdatum,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Created(Some(result)))
+}
+
+#[utoipa::path(
+    path = "/api/tokens/{token_id}",
+    responses(
+        (status = 200, description = "Success", body = ApiToken),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete("/{token_id}", wrap = "cors_restrictive()")]
+async fn revoke_api_token(
+    user: User,
+    token_id: Path<Uuid>,
+    mut transaction: DatabaseTransaction<'_>,
+) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you need to be logged in to manage api tokens".to_string(),
+        ));
+    }
+
+    let person = user.query_person(&mut *transaction).await?;
+
+    let result = transaction
+        .revoke_api_token(*token_id, person.id, Utc::now())
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(RestStatus::Success(result))
+}
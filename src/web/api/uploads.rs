@@ -0,0 +1,302 @@
+use actix_http::StatusCode;
+use actix_web::web::{Data, Path, Payload};
+use actix_web::{delete, get, patch, post, web, HttpRequest, Responder, Scope};
+use actix_web_validator::Json as ActixJson;
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::database::{DatabaseConnection, DatabasePool};
+use crate::domain::anhang::Anhang;
+use crate::domain::upload::{Upload, UploadRepo};
+use crate::domain::{Capability, Result};
+use crate::storage::{partial::PartialUploads, Storage};
+use crate::web::api::attachments;
+use crate::web::auth::User;
+use crate::web::{cors_restrictive, RestStatus};
+
+/// Header telling at which offset a chunk starts
+const UPLOAD_OFFSET: &str = "Upload-Offset";
+
+/// Create the chunked upload service under /uploads
+pub(crate) fn service() -> Scope {
+    web::scope("/uploads")
+        .service(create_upload)
+        .service(get_upload)
+        .service(patch_upload)
+        .service(delete_upload)
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
+pub struct CreateUploadParams {
+    #[validate(length(min = 1))]
+    filename: String,
+    /// Size of the whole file in bytes
+    size: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UploadStatus {
+    #[serde(flatten)]
+    upload: Upload,
+    /// How many bytes arrived so far. The next chunk has to start there
+    offset: u64,
+    /// The staged Anhang, once all of the file arrived. Attach it within an hour, or it is deleted again
+    anhang: Option<Anhang>,
+}
+
+/// The upload, if it belongs to the user
+async fn own_upload(
+    user: &User,
+    conn: &mut DatabaseConnection,
+    id: Uuid,
+) -> Result<Option<Upload>> {
+    let person = user.query_person(&mut **conn).await?;
+
+    Ok(conn
+        .upload_by_id(id)
+        .await?
+        .filter(|upload| upload.person_id == person.id))
+}
+
+#[utoipa::path(
+    path = "/api/uploads",
+    request_body = CreateUploadParams,
+    responses(
+        (status = 201, description = "Created", body = UploadStatus),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[post("", wrap = "cors_restrictive()")]
+async fn create_upload(
+    user: User,
+    params: ActixJson<CreateUploadParams>,
+    partial_uploads: Data<PartialUploads>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you need to be logged in to upload files in chunks".to_string(),
+        ));
+    }
+
+    if !user.has_capability(Capability::CreateAntrag)
+        && !user.has_capability(Capability::ManageSitzungen)
+    {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you are not allowed to upload attachments".to_string(),
+        ));
+    }
+
+    if let Some(reason) = attachments::check_file_size(&user, params.size) {
+        return Ok(RestStatus::BadRequest(reason));
+    }
+
+    let person = user.query_person(&mut *conn).await?;
+
+    let upload = conn
+        .create_upload(person.id, &params.filename, params.size as i64)
+        .await?;
+
+    partial_uploads.create(upload.id).await?;
+
+    Ok(RestStatus::Created(Some(UploadStatus {
+        upload,
+        offset: 0,
+        anhang: None,
+    })))
+}
+
+#[utoipa::path(
+    path = "/api/uploads/{upload_id}",
+    responses(
+        (status = 200, description = "Success", body = UploadStatus),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[get("/{upload_id}", wrap = "cors_restrictive()")]
+async fn get_upload(
+    user: User,
+    upload_id: Path<Uuid>,
+    partial_uploads: Data<PartialUploads>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you need to be logged in to upload files in chunks".to_string(),
+        ));
+    }
+
+    let Some(upload) = own_upload(&user, &mut conn, *upload_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    let Some(offset) = partial_uploads.offset(upload.id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    Ok(RestStatus::Success(Some(UploadStatus {
+        upload,
+        offset,
+        anhang: None,
+    })))
+}
+
+#[utoipa::path(
+    path = "/api/uploads/{upload_id}",
+    request_body(content = Vec<u8>, content_type = "application/offset+octet-stream"),
+    params(
+        ("Upload-Offset" = u64, Header, description = "Offset the chunk starts at"),
+    ),
+    responses(
+        (status = 200, description = "Success", body = UploadStatus),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 409, description = "Conflict"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[patch("/{upload_id}", wrap = "cors_restrictive()")]
+async fn patch_upload(
+    req: HttpRequest,
+    user: User,
+    upload_id: Path<Uuid>,
+    chunk: Payload,
+    partial_uploads: Data<PartialUploads>,
+    storage: Data<Storage>,
+    database: Data<DatabasePool>,
+) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you need to be logged in to upload files in chunks".to_string(),
+        ));
+    }
+
+    let Some(chunk_offset) = req
+        .headers()
+        .get(UPLOAD_OFFSET)
+        .and_then(|offset| offset.to_str().ok())
+        .and_then(|offset| offset.parse::<u64>().ok())
+    else {
+        return Ok(RestStatus::BadRequest(format!(
+            "the {UPLOAD_OFFSET} header is missing"
+        )));
+    };
+
+    let Some(upload) = own_upload(&user, &mut database.aquire().await?, *upload_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    // the chunks of an upload are written one after another
+    let _guard = partial_uploads.lock(upload.id).await;
+
+    let Some(offset) = partial_uploads.offset(upload.id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    if chunk_offset != offset {
+        return Ok(RestStatus::Status(
+            StatusCode::CONFLICT,
+            format!("the upload continues at offset {offset}"),
+        ));
+    }
+
+    let size = upload.size as u64;
+
+    let Some(offset) = partial_uploads
+        .write(
+            upload.id,
+            offset,
+            size,
+            chunk.map_err(std::io::Error::other),
+        )
+        .await?
+    else {
+        return Ok(RestStatus::BadRequest(format!(
+            "the chunk does not fit into the {size} bytes of the upload"
+        )));
+    };
+
+    if offset < size {
+        return Ok(RestStatus::Success(Some(UploadStatus {
+            upload,
+            offset,
+            anhang: None,
+        })));
+    }
+
+    let mut transaction = database.start_transaction().await?;
+
+    if transaction.delete_upload(upload.id).await?.is_none() {
+        return Ok(RestStatus::NotFound);
+    }
+
+    transaction.commit().await?;
+
+    let staged = attachments::stage_file(
+        partial_uploads.path(upload.id).as_path(),
+        &upload.filename,
+        upload.person_id,
+        &storage,
+        &database,
+    )
+    .await;
+
+    partial_uploads.delete(upload.id).await?;
+
+    match staged? {
+        Ok(anhang) => Ok(RestStatus::Success(Some(UploadStatus {
+            upload,
+            offset,
+            anhang: Some(anhang),
+        }))),
+        Err(reason) => Ok(RestStatus::BadRequest(reason)),
+    }
+}
+
+#[utoipa::path(
+    path = "/api/uploads/{upload_id}",
+    responses(
+        (status = 200, description = "Success"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[delete("/{upload_id}", wrap = "cors_restrictive()")]
+async fn delete_upload(
+    user: User,
+    upload_id: Path<Uuid>,
+    partial_uploads: Data<PartialUploads>,
+    mut conn: DatabaseConnection,
+) -> Result<impl Responder> {
+    if user.is_anonymous() {
+        return Ok(RestStatus::Status(
+            StatusCode::UNAUTHORIZED,
+            "you need to be logged in to upload files in chunks".to_string(),
+        ));
+    }
+
+    let Some(upload) = own_upload(&user, &mut conn, *upload_id).await? else {
+        return Ok(RestStatus::NotFound);
+    };
+
+    // waits for a chunk that is still being written
+    let _guard = partial_uploads.lock(upload.id).await;
+
+    partial_uploads.delete(upload.id).await?;
+    conn.delete_upload(upload.id).await?;
+
+    Ok(RestStatus::Success(Some(())))
+}
@@ -97,13 +97,33 @@ capability_middleware!(
 );
 
 capability_middleware!(
-    RequireViewHidden,
-    RequireViewHiddenMiddleware,
-    Capability::ViewHidden
+    RequireManageLegislaturPerioden,
+    RequireManageLegislaturPeriodenMiddleware,
+    Capability::ManageLegislaturPerioden
 );
 
 capability_middleware!(
-    RequireViewProtected,
-    RequireViewProtectedMiddleware,
-    Capability::ViewProtected
+    RequireManageRoles,
+    RequireManageRolesMiddleware,
+    Capability::ManageRoles
 );
+
+capability_middleware!(
+    RequireManageCalendars,
+    RequireManageCalendarsMiddleware,
+    Capability::ManageCalendars
+);
+
+capability_middleware!(
+    RequireManageTemplates,
+    RequireManageTemplatesMiddleware,
+    Capability::ManageTemplates
+);
+
+capability_middleware!(
+    RequireViewAudit,
+    RequireViewAuditMiddleware,
+    Capability::ViewAudit
+);
+
+capability_middleware!(RequireAdmin, RequireAdminMiddleware, Capability::Admin);
@@ -22,29 +22,30 @@ use oauth2::{
     basic::BasicClient, http::HeaderValue, reqwest::async_http_client, AuthUrl, AuthorizationCode,
     ClientId, ClientSecret, CsrfToken, RedirectUrl, RefreshToken, TokenResponse, TokenUrl,
 };
-use regex::Regex;
 use serde::Deserialize;
+use strum::IntoEnumIterator;
+use uuid::Uuid;
 
 use crate::{
-    database::DatabaseTransaction,
+    database::{DatabaseConnection, DatabasePool, DatabaseTransaction},
     domain::{
         self,
+        antrag::AntragBetrachter,
+        api_token::{self, ApiToken, ApiTokenRepo, API_TOKEN_PREFIX},
         persons::{Person, PersonRepo},
+        session::{self, Session, SessionRepo},
         Capability,
     },
     ARGS,
 };
 
 pub mod capability;
+pub mod oidc;
 
-const COOKIE_MAX_AGE: Duration = Duration::days(30);
+use oidc::OidcVerifier;
 
 const ANONYMOUS_USER_NAME: &str = "fscs-website-anonymous";
 
-static AUTH_COOKIE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::from_str("(refresh_token=[.--;]*;)|(access_token=[.--;]*;)|(user=[.--;]*;)").unwrap()
-});
-
 /// Maps Capabilites to Roles that have them
 static CAPABILITY_SET: LazyLock<HashMap<Capability, HashSet<String>>> = LazyLock::new(|| {
     let mut set: HashMap<Capability, HashSet<String>> = HashMap::new();
@@ -79,18 +80,33 @@ pub struct User {
     #[serde(default)]
     pub groups: Vec<String>,
 
+    /// Name of the OAuth provider the user logged in with. Users without one belong to the
+    /// provider given by --oauth-source-name
+    #[serde(default)]
+    pub oauth_provider: Option<String>,
+
     #[serde(flatten)]
     pub extra: HashMap<String, serde_json::Value>,
+
+    /// Capabilities granted through the roles of the corresponding Person. These are loaded
+    /// from the database on every request and never end up in the user cookie.
+    #[serde(skip)]
+    pub role_capabilities: HashSet<Capability>,
+
+    /// Set if the user authenticated using a personal API token. Only the capabilities the
+    /// token is scoped to are granted then, as long as the Person still holds them.
+    #[serde(skip)]
+    pub api_token: Option<ApiToken>,
 }
 
 impl User {
     pub async fn from_token(
         access_token: &str,
-        oauth_client: &OauthClient,
+        provider: &OauthProvider,
     ) -> Result<Self, actix_web::Error> {
-        oauth_client
+        let mut user: User = provider
             .reqwest_client
-            .get(oauth_client.user_info.clone())
+            .get(provider.user_info.clone())
             .bearer_auth(access_token)
             .send()
             .await
@@ -103,31 +119,165 @@ impl User {
             .map_err(|e| {
                 log::error!("{:?}", e);
                 actix_web::error::ErrorUnauthorized("Internal Error")
-            })
+            })?;
+
+        user.oauth_provider = Some(provider.name.clone());
+
+        Ok(user)
     }
 
-    pub async fn query_person(&self, repo: &mut impl PersonRepo) -> domain::Result<Person> {
-        let user_name = if self.sub == ANONYMOUS_USER_NAME {
+    pub async fn from_api_token(
+        secret: &str,
+        repo: &mut (impl ApiTokenRepo + PersonRepo),
+    ) -> domain::Result<Option<Self>> {
+        let Some(token) = api_token::valid_api_token(repo, secret, Utc::now()).await? else {
+            return Ok(None);
+        };
+
+        let Some(person) = repo.person_by_id(token.person_id).await? else {
+            return Ok(None);
+        };
+
+        let role_capabilities = repo
+            .capabilities_by_person(person.id)
+            .await?
+            .into_iter()
+            .collect();
+
+        let mut user = User {
+            exp: token.ablaufdatum.timestamp(),
+            name: person.name,
+            preferred_username: person.user_name.clone(),
+            sub: person.user_name,
+            groups: vec![],
+            oauth_provider: None,
+            extra: HashMap::new(),
+            role_capabilities,
+            api_token: None,
+        };
+
+        // tokens of persons that lost some of the capabilities they are scoped to are rejected
+        if !token.is_scope_held(&user.owned_capabilities()) {
+            debug!(
+                "rejected api token {}, its owner no longer holds its capabilities",
+                token.id
+            );
+            return Ok(None);
+        }
+
+        user.api_token = Some(token);
+
+        Ok(Some(user))
+    }
+
+    /// The user name of the corresponding Person, prefixed with the OAuth provider
+    pub fn user_name(&self) -> String {
+        if self.sub == ANONYMOUS_USER_NAME {
             // ewwwwww
-            self.sub.clone()
-        } else if let Some(source_name) = ARGS.oauth_source_name.as_ref() {
-            format!("{}-{}", source_name.as_str(), self.sub.as_str())
+            return self.sub.clone();
+        }
+
+        match self
+            .oauth_provider
+            .as_ref()
+            .or(ARGS.oauth_source_name.as_ref())
+        {
+            Some(provider) => format!("{}-{}", provider.as_str(), self.sub.as_str()),
+            None => self.sub.clone(),
+        }
+    }
+
+    pub async fn query_person(&self, repo: &mut impl PersonRepo) -> domain::Result<Person> {
+        self.find_person(repo)
+            .await?
+            .ok_or_else(|| match &self.api_token {
+                Some(_) => domain::Error::Message(
+                    "the person of this api token no longer exists".to_string(),
+                ),
+                None => domain::Error::Message(format!(
+                "no corresponding person found for username {}, try logging out and back in again",
+                self.user_name()
+            )),
+            })
+    }
+
+    /// The Person of this user, `None` if there is none, like for the anonymous user
+    pub async fn find_person(&self, repo: &mut impl PersonRepo) -> domain::Result<Option<Person>> {
+        match &self.api_token {
+            Some(token) => repo.person_by_id(token.person_id).await,
+            None => repo.person_by_user_name(self.user_name().as_str()).await,
+        }
+    }
+
+    /// Describes which Anträge this user gets to see
+    pub async fn antrag_betrachter(
+        &self,
+        repo: &mut impl PersonRepo,
+    ) -> domain::Result<AntragBetrachter> {
+        // users without a person can still see everything their capabilities allow
+        let person = if self.is_anonymous() {
+            None
         } else {
-            self.sub.clone()
+            self.find_person(repo).await?
         };
 
-        repo.person_by_user_name(user_name.as_str())
+        Ok(AntragBetrachter {
+            person,
+            intern: self.has_capability(Capability::ViewHidden)
+                || self.has_capability(Capability::ViewProtected),
+            alle: self.has_capability(Capability::ManageAntraege),
+        })
+    }
+
+    pub async fn load_role_capabilities(
+        &mut self,
+        repo: &mut impl PersonRepo,
+    ) -> domain::Result<()> {
+        let person = self.query_person(repo).await?;
+
+        self.role_capabilities = repo
+            .capabilities_by_person(person.id)
             .await?
-            .ok_or_else(|| {
-                domain::Error::Message(format!(
-                    "no corresponding person found for username {}, try logging out and back in again",
-                    user_name
-                ))
-            })
+            .into_iter()
+            .collect();
+
+        Ok(())
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        self.sub == ANONYMOUS_USER_NAME
+    }
+
+    /// All capabilities this user has, however they were granted
+    pub fn capabilities(&self) -> Vec<Capability> {
+        Capability::iter()
+            .filter(|cap| self.has_capability(*cap))
+            .collect()
+    }
+
+    /// Capabilities granted by the roles of the Person and the default capabilities. Unlike those
+    /// from OAuth groups, these are known when the user is not logged in, like for API tokens
+    pub fn owned_capabilities(&self) -> HashSet<Capability> {
+        self.role_capabilities
+            .iter()
+            .chain(&ARGS.default_capabilities)
+            .copied()
+            .collect()
     }
 
     pub fn has_capability(&self, cap: Capability) -> bool {
-        if ARGS.default_capabilities.contains(&cap) {
+        if let Some(token) = &self.api_token {
+            let scoped = token.capabilities.contains(&cap)
+                || token.capabilities.contains(&Capability::Admin);
+            let owned = self.owned_capabilities();
+
+            return scoped && (owned.contains(&cap) || owned.contains(&Capability::Admin));
+        }
+
+        if ARGS.default_capabilities.contains(&cap)
+            || self.role_capabilities.contains(&cap)
+            || self.role_capabilities.contains(&Capability::Admin)
+        {
             return true;
         }
 
@@ -154,7 +304,10 @@ impl Default for User {
             preferred_username: "Anonymous".to_string(),
             sub: ANONYMOUS_USER_NAME.to_string(),
             groups: vec![],
+            oauth_provider: None,
             extra: HashMap::new(),
+            role_capabilities: HashSet::new(),
+            api_token: None,
         }
     }
 }
@@ -218,9 +371,8 @@ where
 
     fn call(&self, mut req: ServiceRequest) -> Self::Future {
         let service = self.service.clone();
-        let mut updated_cookies = false;
         Box::pin(async move {
-            let mut jar = req.extract::<AuthCookieJar>().await?;
+            let jar = req.extract::<AuthCookieJar>().await?;
             let oauth_client =
                 req.app_data::<Data<OauthClient>>()
                     .ok_or(domain::Error::Message(
@@ -229,85 +381,84 @@ where
 
             // try obtaining a user
             //
-            // - if there is a user, but it is expired, get a new one
-            // - if there is a user, just use that one
+            // - if there is an active session, use the user stored in it. if that user is expired,
+            //   get a new one using the refresh token of the session
             // - if there is none, and the Authorization header is set, try get a user using that
-            // - if there is none, but we have a refresh_token, use that to get a new one
-            // - if there is none, but we have an access_token, use that to get a new one
+            //   (either a personal api token, a signed oidc token that can be verified locally or
+            //   an oauth access token)
             // - otherwise, just give up
             //
-            let maybe_user = match jar.user_info() {
-                Some(user) if user.exp - 30 < Utc::now().timestamp() => {
-                    let maybe_new_user = refresh_authentication(&mut jar, &mut req).await;
-                    updated_cookies = true;
-                    maybe_new_user.ok()
+            let session_user = match (jar.session_id(), req.app_data::<Data<DatabasePool>>()) {
+                (Some(session_id), Some(pool)) => {
+                    session_user(session_id, oauth_client, &mut *pool.aquire().await?).await?
                 }
+                _ => None,
+            };
+
+            let maybe_user = match session_user {
                 Some(user) => Some(user),
                 None if req.headers().contains_key("Authorization") => {
-                    if let Some(token) =
-                        token_from_auth_header(req.headers().get("Authorization").unwrap())
-                    {
-                        User::from_token(token, oauth_client).await.ok()
-                    } else {
-                        None
+                    match token_from_auth_header(req.headers().get("Authorization").unwrap()) {
+                        Some(token) if token.starts_with(API_TOKEN_PREFIX) => {
+                            let pool = req.app_data::<Data<DatabasePool>>().ok_or(
+                                domain::Error::Message("database is not configured".to_string()),
+                            )?;
+
+                            User::from_api_token(token, &mut *pool.aquire().await?).await?
+                        }
+                        Some(token)
+                            if is_jwt(token) && req.app_data::<Data<OidcVerifier>>().is_some() =>
+                        {
+                            let verifier = req.app_data::<Data<OidcVerifier>>().unwrap();
+
+                            verifier
+                                .verify(token)
+                                .await
+                                .inspect_err(|e| debug!("rejected oidc token: {}", e))
+                                .ok()
+                        }
+                        Some(token) => User::from_token(token, oauth_client.default_provider())
+                            .await
+                            .ok(),
+                        None => None,
                     }
                 }
-                None if jar.refresh_token().is_some() => {
-                    let maybe_new_user = refresh_authentication(&mut jar, &mut req).await;
-                    updated_cookies = true;
-                    maybe_new_user.ok()
-                }
-                None if jar.access_token().is_some() => {
-                    User::from_token(jar.access_token().unwrap(), oauth_client)
-                        .await
-                        .ok()
-                }
                 None => None,
             };
 
-            if let Some(user) = maybe_user {
-                debug!(
-                    "oauth user {} aquired (sub: {}-{})",
-                    user.preferred_username,
-                    ARGS.oauth_source_name.as_ref().unwrap(),
-                    user.sub
-                );
+            if let Some(mut user) = maybe_user {
+                if user.api_token.is_some() {
+                    debug!("api token user {} aquired", user.preferred_username);
+                } else {
+                    debug!(
+                        "oauth user {} aquired ({})",
+                        user.preferred_username,
+                        user.user_name()
+                    );
+
+                    if let Some(pool) = req.app_data::<Data<DatabasePool>>() {
+                        let mut conn = pool.aquire().await?;
+
+                        if let Err(e) = user.load_role_capabilities(&mut *conn).await {
+                            debug!("could not load role capabilities: {:?}", e);
+                        }
+                    }
+                }
+
                 req.extensions_mut().insert(user);
             }
 
             // authorized ? continue to the next middleware/ErrorHandlerResponse
-            match service.call(req).await {
-                Ok(mut res) if updated_cookies => {
-                    let cookies = [
-                        jar.refresh_token().map(|r| format!("refresh_token={r}")),
-                        jar.access_token().map(|a| format!("access_token={a}")),
-                        jar.inner
-                            .get("user")
-                            .map(Cookie::value)
-                            .map(|u| format!("user={u}")),
-                    ]
-                    .into_iter()
-                    .flatten()
-                    .filter_map(|cookie| {
-                        HeaderValue::from_str(&format!(
-                            "{}; SameSite=None; Path=/; Secure; Max-Age={};",
-                            cookie, COOKIE_MAX_AGE
-                        ))
-                        .ok()
-                    });
-
-                    for cookie in cookies {
-                        res.headers_mut().append(header::SET_COOKIE, cookie);
-                    }
-
-                    Ok(res)
-                }
-                c => c,
-            }
+            service.call(req).await
         })
     }
 }
 
+/// Signed tokens consist of three dot separated parts, everything else is treated as opaque
+fn is_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
 fn token_from_auth_header(header: &HeaderValue) -> Option<&str> {
     let header_str = header.to_str().ok()?;
 
@@ -319,124 +470,127 @@ fn token_from_auth_header(header: &HeaderValue) -> Option<&str> {
     Some(parts[1])
 }
 
+/// Loads the user of an active session, refreshing it if it has expired. Sessions that can not be
+/// refreshed anymore are deleted.
+async fn session_user(
+    session_id: Uuid,
+    oauth_client: &OauthClient,
+    repo: &mut impl SessionRepo,
+) -> domain::Result<Option<User>> {
+    let Some(session) =
+        session::active_session(repo, session_id, Utc::now(), ARGS.session_idle_timeout()).await?
+    else {
+        return Ok(None);
+    };
+
+    let user: User = serde_json::from_value(session.user_info.clone())?;
+
+    if user.exp - 30 >= Utc::now().timestamp() {
+        return Ok(Some(user));
+    }
+
+    match refresh_authentication(&session, oauth_client, repo).await {
+        Ok(refreshed) => Ok(Some(refreshed)),
+        Err(e) => {
+            info!("could not refresh session {}: {:?}", session.id, e);
+            repo.delete_session(session.id).await?;
+
+            Ok(None)
+        }
+    }
+}
+
 async fn refresh_authentication(
-    jar: &mut AuthCookieJar,
-    req: &mut ServiceRequest,
+    session: &Session,
+    oauth_client: &OauthClient,
+    repo: &mut impl SessionRepo,
 ) -> domain::Result<User> {
-    info!("Refreshing user {:?}", jar.user_info());
-    let refresh = jar.refresh_token().ok_or(domain::Error::Message(
-        "Could not access refresh token".to_string(),
-    ))?;
+    info!("Refreshing session {}", session.id);
+    let refresh = session
+        .refresh_token
+        .as_deref()
+        .ok_or(domain::Error::Message(
+            "Could not access refresh token".to_string(),
+        ))?;
 
-    let oauth_client = req
-        .app_data::<Data<OauthClient>>()
+    let provider = oauth_client
+        .provider(session.oauth_provider.as_deref())
         .ok_or(domain::Error::Message(
-            "oauth client is not configured".to_string(),
+            "oauth provider is not configured".to_string(),
         ))?;
 
-    let token = oauth_client
+    let token = provider
         .client
         .exchange_refresh_token(&RefreshToken::new(refresh.to_owned()))
         .request_async(async_http_client)
         .await
         .map_err(|e| domain::Error::Message(format!("{:?}", e)))?;
 
-    let refresh_token = token.refresh_token().map_or("/", |a| a.secret());
-    let access_token = token.access_token().secret();
-    jar.set_refresh_token(refresh_token);
-    jar.set_access_token(access_token);
-
-    let user = User::from_token(token.access_token().secret(), oauth_client)
+    let user = User::from_token(token.access_token().secret(), provider)
         .await
         .map_err(|e| domain::Error::Message(format!("{:?}", e)))?;
 
-    let userinfo = jar.set_user_info(&user);
-
-    let cookie_header = req
-        .headers_mut()
-        .get_mut(header::COOKIE)
-        .ok_or(domain::Error::Message("Could not read Cookies".to_string()))?
-        .to_str()
-        .map_err(|e| domain::Error::Message(format!("{:?}", e)))?;
-
-    let cookie_header = AUTH_COOKIE_REGEX.replace_all(cookie_header, "");
+    // keep the old refresh token if the provider does not rotate them
+    let refresh_token = token.refresh_token().map_or(refresh, |r| r.secret());
 
-    let cookie_header = format!(
-        "{}refresh_token={};access_token={};user={}",
-        cookie_header, refresh_token, access_token, userinfo
-    );
-
-    req.headers_mut().insert(
-        header::COOKIE,
-        HeaderValue::from_str(&cookie_header)
-            .map_err(|e| domain::Error::Message(format!("{:?}", e)))?,
-    );
-    req.extensions_mut().clear();
+    repo.update_session_tokens(
+        session.id,
+        &serde_json::to_value(&user)?,
+        Some(refresh_token),
+    )
+    .await?;
 
     Ok(user)
 }
 
-pub(crate) struct OauthClient {
+pub(crate) struct OauthProvider {
+    name: String,
     client: BasicClient,
     reqwest_client: reqwest::Client,
     user_info: String,
-    singning_key: Key,
 }
 
-struct AuthCookieJar {
-    inner: CookieJar,
-    key: Key,
+pub(crate) struct OauthClient {
+    providers: HashMap<String, OauthProvider>,
+    default_provider: String,
+    singning_key: Key,
 }
 
-impl AuthCookieJar {
-    fn access_token(&self) -> Option<&str> {
-        self.inner
-            .get("access_token")
-            .map(actix_web::cookie::Cookie::value)
+impl OauthClient {
+    /// Looks up a provider by name, falling back to the default one
+    fn provider(&self, name: Option<&str>) -> Option<&OauthProvider> {
+        self.providers
+            .get(name.unwrap_or(self.default_provider.as_str()))
     }
 
-    fn set_access_token(&mut self, value: &str) {
-        let mut cookie = Cookie::new("access_token", value.to_string());
-        cookie.set_path("/");
-        cookie.set_same_site(SameSite::None);
-        cookie.set_max_age(COOKIE_MAX_AGE);
-        self.inner.add(cookie);
-    }
-
-    fn refresh_token(&self) -> Option<&str> {
-        self.inner
-            .get("refresh_token")
-            .map(actix_web::cookie::Cookie::value)
+    fn default_provider(&self) -> &OauthProvider {
+        &self.providers[&self.default_provider]
     }
+}
 
-    fn set_refresh_token(&mut self, value: &str) {
-        let mut cookie = Cookie::new("refresh_token", value.to_string());
-        cookie.set_path("/");
-        cookie.set_same_site(SameSite::None);
-        cookie.set_max_age(COOKIE_MAX_AGE);
-        self.inner.add(cookie);
-    }
+struct AuthCookieJar {
+    inner: CookieJar,
+    key: Key,
+}
 
-    fn user_info(&self) -> Option<User> {
+impl AuthCookieJar {
+    fn session_id(&self) -> Option<Uuid> {
         self.inner
             .signed(&self.key)
-            .get("user")
-            .and_then(|c| serde_json::from_str::<User>(c.value()).ok())
-            .filter(|u| u.exp > Utc::now().timestamp())
+            .get("session")
+            .and_then(|c| Uuid::parse_str(c.value()).ok())
     }
 
-    fn set_user_info(&mut self, user: &User) -> &str {
-        let user_str = serde_json::to_string(&user).unwrap();
-
-        let mut cookie = Cookie::new("user", user_str.clone());
+    fn set_session_id(&mut self, session_id: Uuid) {
+        let mut cookie = Cookie::new("session", session_id.to_string());
 
         cookie.set_path("/");
-        cookie.set_max_age(COOKIE_MAX_AGE);
+        cookie.set_http_only(true);
+        cookie.set_secure(true);
         cookie.set_same_site(SameSite::None);
+        cookie.set_max_age(Duration::seconds(ARGS.session_max_age().num_seconds()));
 
         self.inner.signed_mut(&self.key).add(cookie);
-
-        self.inner.get("user").unwrap().value()
     }
 
     fn delta(&self) -> impl Iterator<Item = &Cookie<'static>> {
@@ -476,22 +630,86 @@ struct AuthRequest {
     code: String,
     state: String,
     path: Option<String>,
+    provider: Option<String>,
 }
 
-pub(crate) fn oauth_client() -> OauthClient {
-    let client_id = std::env::var("CLIENT_ID").expect("No CLIENT_ID set");
-    let client_secret = std::env::var("CLIENT_SECRET").expect("No CLIENT_SECRET set");
-    let singning_key = std::env::var("SIGNING_KEY").expect("No SIGNING_KEY set");
-
-    OauthClient {
+fn oauth_provider(
+    name: &str,
+    client_id: String,
+    client_secret: String,
+    auth_url: &str,
+    token_url: &str,
+    user_info: &str,
+) -> OauthProvider {
+    OauthProvider {
+        name: name.to_string(),
         client: BasicClient::new(
             ClientId::new(client_id),
             Some(ClientSecret::new(client_secret)),
-            AuthUrl::new(ARGS.auth_url.as_ref().unwrap().clone()).unwrap(),
-            Some(TokenUrl::new(ARGS.token_url.as_ref().unwrap().clone()).unwrap()),
+            AuthUrl::new(auth_url.to_string()).unwrap(),
+            Some(TokenUrl::new(token_url.to_string()).unwrap()),
         ),
         reqwest_client: reqwest::Client::new(),
-        user_info: ARGS.user_info.as_ref().unwrap().clone(),
+        user_info: user_info.to_string(),
+    }
+}
+
+/// Environment variable holding a credential of an additional provider, e.g. `GITHUB_CLIENT_ID`
+fn provider_env_var(name: &str, var: &str) -> String {
+    let key = format!("{}_{var}", name.to_uppercase().replace('-', "_"));
+
+    std::env::var(&key).unwrap_or_else(|_| panic!("No {key} set"))
+}
+
+pub(crate) fn oauth_client() -> OauthClient {
+    let singning_key = std::env::var("SIGNING_KEY").expect("No SIGNING_KEY set");
+
+    let mut providers = HashMap::new();
+
+    if let Some(name) = ARGS.oauth_source_name.as_ref() {
+        let client_id = std::env::var("CLIENT_ID").expect("No CLIENT_ID set");
+        let client_secret = std::env::var("CLIENT_SECRET").expect("No CLIENT_SECRET set");
+
+        providers.insert(
+            name.clone(),
+            oauth_provider(
+                name,
+                client_id,
+                client_secret,
+                ARGS.auth_url.as_ref().unwrap(),
+                ARGS.token_url.as_ref().unwrap(),
+                ARGS.user_info.as_ref().unwrap(),
+            ),
+        );
+    }
+
+    for (name, urls) in &ARGS.oauth_providers {
+        let [auth_url, token_url, user_info] = urls.split(',').collect::<Vec<_>>()[..] else {
+            panic!("oauth provider {name} needs to be formatted like name=auth-url,token-url,user-info-url");
+        };
+
+        providers.insert(
+            name.clone(),
+            oauth_provider(
+                name,
+                provider_env_var(name, "CLIENT_ID"),
+                provider_env_var(name, "CLIENT_SECRET"),
+                auth_url,
+                token_url,
+                user_info,
+            ),
+        );
+    }
+
+    let default_provider = ARGS
+        .oauth_source_name
+        .clone()
+        .or_else(|| ARGS.oauth_providers.first().map(|(name, _)| name.clone()))
+        .expect("No oauth provider configured");
+
+    OauthClient {
+        providers,
+        default_provider,
         singning_key: Key::from(singning_key.as_bytes()),
     }
 }
@@ -507,15 +725,30 @@ pub(crate) fn service() -> actix_web::Scope {
 #[derive(serde::Deserialize, Debug)]
 struct PathParam {
     path: Option<String>,
+    provider: Option<String>,
 }
 
-fn redirect_url<'a>(path: &str, request: HttpRequest) -> Cow<'a, RedirectUrl> {
+fn redirect_url<'a>(
+    path: &str,
+    provider: &OauthProvider,
+    oauth_client: &OauthClient,
+    request: HttpRequest,
+) -> Cow<'a, RedirectUrl> {
     let host = request.connection_info().host().to_string();
     let scheme = request.connection_info().scheme().to_string();
 
-    std::borrow::Cow::Owned(
-        RedirectUrl::new(format!("{scheme}://{host}/auth/callback/?path={path}")).unwrap(),
-    )
+    // the default provider keeps its old redirect url, so existing client registrations continue
+    // to work
+    let url = if provider.name == oauth_client.default_provider {
+        format!("{scheme}://{host}/auth/callback/?path={path}")
+    } else {
+        format!(
+            "{scheme}://{host}/auth/callback/?path={path}&provider={}",
+            provider.name
+        )
+    };
+
+    std::borrow::Cow::Owned(RedirectUrl::new(url).unwrap())
 }
 
 #[get("/login")]
@@ -524,15 +757,20 @@ async fn login(
     path: web::Query<PathParam>,
     request: HttpRequest,
 ) -> impl Responder {
-    let path = path.into_inner().path.unwrap_or("/".to_string());
+    let PathParam { path, provider } = path.into_inner();
+    let path = path.unwrap_or("/".to_string());
+
+    let Some(provider) = oauth_client.provider(provider.as_deref()) else {
+        return HttpResponse::BadRequest().body("unknown oauth provider");
+    };
 
-    let (ref mut auth_url, csrf_token) = &mut oauth_client
+    let (ref mut auth_url, csrf_token) = &mut provider
         .client
         .authorize_url(CsrfToken::new_random)
         .add_scope(oauth2::Scope::new("openid".to_string()))
         .add_scope(oauth2::Scope::new("offline_access".to_string()))
         .add_scope(oauth2::Scope::new("profile".to_string()))
-        .set_redirect_uri(redirect_url(&path, request))
+        .set_redirect_uri(redirect_url(&path, provider, &oauth_client, request))
         .url();
 
     HttpResponse::Found()
@@ -547,21 +785,26 @@ async fn login(
 }
 
 #[get("/logout")]
-async fn logout() -> impl Responder {
+async fn logout(
+    auth_jar: AuthCookieJar,
+    mut conn: DatabaseConnection,
+) -> domain::Result<impl Responder> {
+    if let Some(session_id) = auth_jar.session_id() {
+        conn.delete_session(session_id).await?;
+    }
+
     //rmove cookies and redirect to /
-    let mut cookie_at = Cookie::build("access_token", "").path("/").finish();
-    let mut cookie_rt = Cookie::build("refresh_token", "").path("/").finish();
-    let mut cookie_u = Cookie::build("user", "").path("/").finish();
-    cookie_at.make_removal();
-    cookie_rt.make_removal();
-    cookie_u.make_removal();
+    let mut response_builder = HttpResponse::Found();
+    response_builder.append_header((header::LOCATION, "/"));
 
-    HttpResponse::Found()
-        .cookie(cookie_u)
-        .cookie(cookie_at)
-        .cookie(cookie_rt)
-        .append_header((header::LOCATION, "/"))
-        .finish()
+    // the token cookies are not used anymore, but may still be around from older logins
+    for name in ["session", "access_token", "refresh_token", "user"] {
+        let mut cookie = Cookie::build(name, "").path("/").finish();
+        cookie.make_removal();
+        response_builder.cookie(cookie);
+    }
+
+    Ok(response_builder.finish())
 }
 
 #[get("/callback")]
@@ -585,10 +828,14 @@ async fn callback(
 
     let path = query.path.clone().unwrap_or("/".to_string());
 
-    let Ok(token) = oauth_client
+    let Some(provider) = oauth_client.provider(query.provider.as_deref()) else {
+        return Ok(HttpResponse::BadRequest().body("unknown oauth provider"));
+    };
+
+    let Ok(token) = provider
         .client
         .exchange_code(code)
-        .set_redirect_uri(redirect_url(&path, request))
+        .set_redirect_uri(redirect_url(&path, provider, &oauth_client, request))
         // Set the PKCE code verifier.
         .request_async(async_http_client)
         .await
@@ -599,57 +846,62 @@ async fn callback(
     };
 
     let access_token = token.access_token().secret();
-    let refresh_token = match token.refresh_token() {
-        Some(refresh_token) => refresh_token.secret(),
-        None => "",
-    };
-
-    auth_jar.set_access_token(access_token);
-    auth_jar.set_refresh_token(refresh_token);
+    let refresh_token = token.refresh_token().map(|r| r.secret().as_str());
 
-    let Ok(user) = User::from_token(access_token, &oauth_client).await else {
+    let Ok(user) = User::from_token(access_token, provider).await else {
         return Err(domain::Error::Message(
             "Could not access user info".to_string(),
         ));
     };
-    auth_jar.set_user_info(&user);
 
-    let mut response_builder = HttpResponse::Found();
-    response_builder.append_header((header::LOCATION, path));
+    let user_name = user.user_name();
 
-    for cookie in auth_jar.delta() {
-        response_builder.cookie(cookie.clone());
-    }
+    let person_id =
+        if let Some(person) = transaction.person_by_user_name(user_name.as_str()).await? {
+            info!(
+                "syncing person {} for oauth user {}",
+                user_name.as_str(),
+                user.preferred_username.as_str()
+            );
 
-    let user_name = format!(
-        "{}-{}",
-        ARGS.oauth_source_name.as_ref().unwrap().as_str(),
-        user.sub.as_str()
-    );
+            transaction
+                .update_person(person.id, Some(user.name.as_str()), None, None)
+                .await?;
 
-    if let Some(person) = transaction.person_by_user_name(user_name.as_str()).await? {
-        info!(
-            "syncing person {} for oauth user {}",
-            user_name.as_str(),
-            user.preferred_username.as_str()
-        );
-
-        transaction
-            .update_person(person.id, Some(user.name.as_str()), None, None)
-            .await?;
-    } else {
-        info!(
-            "creating person {} for oauth user {}",
-            user_name.as_str(),
-            user.preferred_username.as_str()
-        );
+            person.id
+        } else {
+            info!(
+                "creating person {} for oauth user {}",
+                user_name.as_str(),
+                user.preferred_username.as_str()
+            );
+
+            transaction
+                .create_person(user.name.as_str(), user_name.as_str(), None)
+                .await?
+                .id
+        };
 
-        transaction
-            .create_person(user.name.as_str(), user_name.as_str(), None)
-            .await?;
-    }
+    let session = transaction
+        .create_session(
+            person_id,
+            user.oauth_provider.as_deref(),
+            &serde_json::to_value(&user)?,
+            refresh_token,
+            Utc::now() + ARGS.session_max_age(),
+        )
+        .await?;
 
     transaction.commit().await?;
 
+    auth_jar.set_session_id(session.id);
+
+    let mut response_builder = HttpResponse::Found();
+    response_builder.append_header((header::LOCATION, path));
+
+    for cookie in auth_jar.delta() {
+        response_builder.cookie(cookie.clone());
+    }
+
     Ok(response_builder.finish())
 }
@@ -0,0 +1,367 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use async_std::sync::RwLock;
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, Validation,
+};
+use log::{debug, info};
+use serde::Deserialize;
+
+use crate::domain::{self, Error};
+
+use super::User;
+
+/// How long fetched signing keys are used before asking the provider again
+const JWKS_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Tokens signed with an unknown key trigger a refetch, but not more often than this
+const JWKS_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize, Debug)]
+struct DiscoveryDocument {
+    issuer: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct OidcClaims {
+    exp: i64,
+    sub: String,
+    name: Option<String>,
+    preferred_username: Option<String>,
+    #[serde(default)]
+    groups: Vec<String>,
+
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+impl From<OidcClaims> for User {
+    fn from(claims: OidcClaims) -> Self {
+        let preferred_username = claims
+            .preferred_username
+            .unwrap_or_else(|| claims.sub.clone());
+
+        User {
+            exp: claims.exp,
+            name: claims.name.unwrap_or_else(|| preferred_username.clone()),
+            preferred_username,
+            sub: claims.sub,
+            groups: claims.groups,
+            extra: claims.extra,
+            ..User::default()
+        }
+    }
+}
+
+struct CachedJwks {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+/// Verifies signed OIDC tokens locally, using the signing keys published by the provider
+pub(crate) struct OidcVerifier {
+    issuer: String,
+    audience: String,
+    /// The OAuth provider the users of the issuer belong to
+    oauth_provider: Option<String>,
+    jwks_uri: String,
+    reqwest_client: reqwest::Client,
+    jwks: RwLock<CachedJwks>,
+}
+
+impl OidcVerifier {
+    /// Fetches the discovery document and the current signing keys of `issuer`
+    pub async fn discover(
+        issuer: &str,
+        audience: &str,
+        oauth_provider: Option<String>,
+    ) -> domain::Result<Self> {
+        let reqwest_client = reqwest::Client::new();
+
+        let discovery_url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+
+        let discovery: DiscoveryDocument = reqwest_client
+            .get(discovery_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let keys = fetch_jwks(&reqwest_client, &discovery.jwks_uri).await?;
+
+        info!(
+            "fetched {} oidc signing keys from {}",
+            keys.keys.len(),
+            discovery.jwks_uri
+        );
+
+        Ok(OidcVerifier {
+            issuer: discovery.issuer,
+            audience: audience.to_string(),
+            oauth_provider,
+            jwks_uri: discovery.jwks_uri,
+            reqwest_client,
+            jwks: RwLock::new(CachedJwks {
+                keys,
+                fetched_at: Instant::now(),
+            }),
+        })
+    }
+
+    /// Checks signature, issuer, audience and expiry of `token` and maps its claims onto a User
+    pub async fn verify(&self, token: &str) -> domain::Result<User> {
+        let header = decode_header(token)?;
+
+        let jwk = self
+            .jwk(header.kid.as_deref())
+            .await?
+            .ok_or_else(|| Error::Message("token is signed with an unknown key".to_string()))?;
+
+        // the algorithm named by the token itself can not be trusted
+        let algorithms = key_algorithms(&jwk);
+
+        if !algorithms.contains(&header.alg) {
+            return Err(Error::Message(format!(
+                "token is signed with {:?}, which its key is not used for",
+                header.alg
+            )));
+        }
+
+        let key = DecodingKey::from_jwk(&jwk)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = algorithms;
+        validation.set_issuer(&[self.issuer.as_str()]);
+        validation.set_audience(&[self.audience.as_str()]);
+
+        let claims = decode::<OidcClaims>(token, &key, &validation)?.claims;
+
+        Ok(User {
+            oauth_provider: self.oauth_provider.clone(),
+            ..claims.into()
+        })
+    }
+
+    async fn jwk(&self, kid: Option<&str>) -> domain::Result<Option<Jwk>> {
+        {
+            let cache = self.jwks.read().await;
+            let age = cache.fetched_at.elapsed();
+
+            if age < JWKS_MAX_AGE {
+                if let Some(jwk) = find_jwk(&cache.keys, kid) {
+                    return Ok(Some(jwk.clone()));
+                }
+
+                if age < JWKS_MIN_REFRESH_INTERVAL {
+                    return Ok(None);
+                }
+            }
+        }
+
+        debug!("refreshing oidc signing keys from {}", self.jwks_uri);
+
+        let keys = fetch_jwks(&self.reqwest_client, &self.jwks_uri).await?;
+
+        let mut cache = self.jwks.write().await;
+        *cache = CachedJwks {
+            keys,
+            fetched_at: Instant::now(),
+        };
+
+        Ok(find_jwk(&cache.keys, kid).cloned())
+    }
+}
+
+async fn fetch_jwks(client: &reqwest::Client, jwks_uri: &str) -> domain::Result<JwkSet> {
+    let keys = client
+        .get(jwks_uri)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(keys)
+}
+
+/// The signature algorithms a key may be used with: the one it names, or else every one fitting
+/// its type. Symmetric keys are never accepted, as anyone could sign with a published secret
+fn key_algorithms(jwk: &Jwk) -> Vec<Algorithm> {
+    if let Some(algorithm) = &jwk.common.key_algorithm {
+        return match algorithm {
+            KeyAlgorithm::ES256 => vec![Algorithm::ES256],
+            KeyAlgorithm::ES384 => vec![Algorithm::ES384],
+            KeyAlgorithm::RS256 => vec![Algorithm::RS256],
+            KeyAlgorithm::RS384 => vec![Algorithm::RS384],
+            KeyAlgorithm::RS512 => vec![Algorithm::RS512],
+            KeyAlgorithm::PS256 => vec![Algorithm::PS256],
+            KeyAlgorithm::PS384 => vec![Algorithm::PS384],
+            KeyAlgorithm::PS512 => vec![Algorithm::PS512],
+            KeyAlgorithm::EdDSA => vec![Algorithm::EdDSA],
+            _ => vec![],
+        };
+    }
+
+    match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => vec![
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+        ],
+        AlgorithmParameters::EllipticCurve(parameters) => match parameters.curve {
+            EllipticCurve::P256 => vec![Algorithm::ES256],
+            EllipticCurve::P384 => vec![Algorithm::ES384],
+            _ => vec![],
+        },
+        AlgorithmParameters::OctetKeyPair(_) => vec![Algorithm::EdDSA],
+        AlgorithmParameters::OctetKey(_) => vec![],
+    }
+}
+
+/// Tokens without a key id are only accepted if the provider publishes a single key
+fn find_jwk<'a>(keys: &'a JwkSet, kid: Option<&str>) -> Option<&'a Jwk> {
+    match kid {
+        Some(kid) => keys.find(kid),
+        None if keys.keys.len() == 1 => keys.keys.first(),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use async_std::sync::RwLock;
+    use chrono::Utc;
+    use jsonwebtoken::jwk::{Jwk, KeyAlgorithm};
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use serde_json::json;
+
+    use super::{key_algorithms, CachedJwks, OidcVerifier};
+
+    const ISSUER: &str = "https://idp.example.org/realms/fscs";
+    const AUDIENCE: &str = "fscs-website";
+
+    fn verifier() -> OidcVerifier {
+        OidcVerifier {
+            issuer: ISSUER.to_string(),
+            audience: AUDIENCE.to_string(),
+            oauth_provider: Some("keycloak".to_string()),
+            // never reached, as the keys are fresh
+            jwks_uri: "http://127.0.0.1:9/jwks".to_string(),
+            reqwest_client: reqwest::Client::new(),
+            jwks: RwLock::new(CachedJwks {
+                keys: serde_json::from_str(include_str!("fixtures/oidc_test_jwks.json")).unwrap(),
+                fetched_at: Instant::now(),
+            }),
+        }
+    }
+
+    fn sign(kid: &str, claims: serde_json::Value) -> String {
+        sign_with(Algorithm::RS256, kid, claims)
+    }
+
+    fn sign_with(algorithm: Algorithm, kid: &str, claims: serde_json::Value) -> String {
+        let mut header = Header::new(algorithm);
+        header.kid = Some(kid.to_string());
+
+        let key = EncodingKey::from_rsa_pem(include_bytes!("fixtures/oidc_test_key.pem")).unwrap();
+
+        encode(&header, &claims, &key).unwrap()
+    }
+
+    fn claims() -> serde_json::Value {
+        json!({
+            "iss": ISSUER,
+            "aud": AUDIENCE,
+            "exp": Utc::now().timestamp() + 300,
+            "sub": "1234",
+            "preferred_username": "knut",
+            "groups": ["fsr"],
+        })
+    }
+
+    #[tokio::test]
+    async fn verify_token() {
+        let token = sign("test-key", claims());
+
+        let user = verifier().verify(&token).await.unwrap();
+
+        assert_eq!(user.sub, "1234");
+        assert_eq!(user.preferred_username, "knut");
+        assert_eq!(user.name, "knut");
+        assert_eq!(user.groups, vec!["fsr".to_string()]);
+        assert_eq!(user.user_name(), "keycloak-1234");
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_tokens() {
+        let verifier = verifier();
+
+        let mut wrong_audience = claims();
+        wrong_audience["aud"] = json!("someone-else");
+
+        let mut wrong_issuer = claims();
+        wrong_issuer["iss"] = json!("https://evil.example.org");
+
+        let mut expired = claims();
+        expired["exp"] = json!(Utc::now().timestamp() - 600);
+
+        for claims in [wrong_audience, wrong_issuer, expired] {
+            let token = sign("test-key", claims);
+
+            assert!(verifier.verify(&token).await.is_err());
+        }
+
+        let unknown_key = sign("other-key", claims());
+
+        assert!(verifier.verify(&unknown_key).await.is_err());
+
+        let mut tampered = sign("test-key", claims());
+        tampered.insert(tampered.len() - 4, 'x');
+
+        assert!(verifier.verify(&tampered).await.is_err());
+
+        // the key is published for RS256 only
+        let other_algorithm = sign_with(Algorithm::PS256, "test-key", claims());
+
+        assert!(verifier.verify(&other_algorithm).await.is_err());
+
+        // the public key must not become an HMAC secret
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("test-key".to_string());
+        let hmac = encode(&header, &claims(), &EncodingKey::from_secret(b"test-key")).unwrap();
+
+        assert!(verifier.verify(&hmac).await.is_err());
+    }
+
+    #[test]
+    fn algorithms_follow_the_key() {
+        let mut jwk: Jwk = serde_json::from_value(json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+            "y": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0",
+        }))
+        .unwrap();
+
+        assert_eq!(key_algorithms(&jwk), vec![Algorithm::ES256]);
+
+        jwk.common.key_algorithm = Some(KeyAlgorithm::RSA_OAEP);
+
+        assert!(key_algorithms(&jwk).is_empty());
+    }
+}
@@ -36,6 +36,19 @@ impl CalendarData {
     }
 }
 
+impl CalendarData {
+    /// Fetch a calendar again, instead of waiting for its cache to expire
+    pub async fn refresh_calendar(&self, name: &str) -> Result<Option<Vec<CalendarEvent>>> {
+        let Some(calendar_cache) = self.calendars.get(name) else {
+            return Ok(None);
+        };
+
+        calendar_cache.invalidate().await;
+
+        self.calender_by_name(name).await
+    }
+}
+
 impl CalendarRepo for CalendarData {
     fn calendar_names(&self) -> Vec<String> {
         self.calendars.keys().map(|s| s.to_owned()).collect()
@@ -86,7 +99,7 @@ async fn request_cal(url: String) -> Result<Vec<CalendarEvent>> {
         })
         .collect::<Vec<_>>();
 
-    events.sort_by(|a, b| a.start.cmp(&b.start));
+    events.sort_by_key(|event| event.start);
     Ok(events)
 }
 
@@ -1,8 +1,10 @@
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 
 use actix_cors::Cors;
 use actix_http::{header, StatusCode};
+use actix_multipart::form::MultipartFormConfig;
 use actix_web::body::BoxBody;
 use actix_web::dev::Payload;
 use actix_web::middleware::{Compress, Condition, Logger, NormalizePath};
@@ -21,11 +23,14 @@ pub(crate) mod api;
 pub(crate) mod auth;
 pub(crate) mod calendar;
 pub(crate) mod files;
+pub(crate) mod pagination;
 
 use crate::database::{DatabaseConnection, DatabasePool, DatabaseTransaction};
 use crate::domain::Error;
+use crate::storage::{partial::PartialUploads, Storage};
 use crate::ARGS;
 use auth::AuthMiddle;
+use pagination::TOTAL_COUNT_HEADER;
 
 pub fn cors_permissive() -> Cors {
     Cors::permissive().max_age(0).allow_any_method()
@@ -35,6 +40,10 @@ pub fn cors_restrictive() -> Cors {
     let mut cors = Cors::default()
         .allow_any_method()
         .allow_any_header()
+        .expose_headers([
+            header::LINK,
+            header::HeaderName::from_static(TOTAL_COUNT_HEADER),
+        ])
         .supports_credentials();
 
     for allowed in &ARGS.cors_allowed_origin {
@@ -52,7 +61,14 @@ pub(super) enum RestStatus<T: Serialize> {
     NotFound,
 }
 
-impl ResponseError for Error {}
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
 impl<T: Serialize> Responder for RestStatus<T> {
     type Body = BoxBody;
@@ -137,16 +153,37 @@ impl FromRequest for DatabaseConnection {
 ))]
 struct ApiDoc;
 
-pub async fn start_server(database: DatabasePool) -> Result<(), Error> {
+pub async fn start_server(
+    database: DatabasePool,
+    storage: Arc<Storage>,
+    partial_uploads: Arc<PartialUploads>,
+) -> Result<(), Error> {
     let database_data = Data::new(database);
+    let storage_data = Data::from(storage);
+    let partial_uploads_data = Data::from(partial_uploads);
     let calendar_data = Data::new(calendar::CalendarData::new());
 
-    let enable_oauth = ARGS.oauth_source_name.is_some();
+    let enable_oauth = ARGS.oauth_source_name.is_some() || !ARGS.oauth_providers.is_empty();
 
     if !enable_oauth {
         info!("OAuth is DISABLED");
     }
 
+    let oidc_data = match ARGS.oidc_issuer.as_ref() {
+        Some(issuer) => {
+            let audience = ARGS
+                .oidc_audience
+                .clone()
+                .unwrap_or_else(|| std::env::var("CLIENT_ID").expect("No CLIENT_ID set"));
+
+            Some(Data::new(
+                auth::oidc::OidcVerifier::discover(issuer, &audience, ARGS.oidc_provider.clone())
+                    .await?,
+            ))
+        }
+        None => None,
+    };
+
     let server = HttpServer::new(move || {
         let app = if enable_oauth {
             // app data cant be conditional :(
@@ -155,10 +192,18 @@ pub async fn start_server(database: DatabasePool) -> Result<(), Error> {
             App::new()
         };
 
+        let app = match oidc_data.clone() {
+            Some(oidc_data) => app.app_data(oidc_data),
+            None => app,
+        };
+
         app
             // app data
             .app_data(database_data.clone())
             .app_data(calendar_data.clone())
+            .app_data(storage_data.clone())
+            .app_data(partial_uploads_data.clone())
+            .app_data(MultipartFormConfig::default().total_limit(ARGS.largest_max_file_size()))
             // middlewares
             .wrap(Compress::default())
             .wrap(Logger::default())
@@ -0,0 +1,160 @@
+use actix_http::header;
+use actix_web::body::BoxBody;
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use crate::domain::pagination::{Page, Pagination};
+
+pub const MAX_LIMIT: i64 = 1000;
+
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
+pub struct PaginationParams {
+    /// How many items to return, everything after the offset if not given
+    #[validate(range(min = 1, max = MAX_LIMIT))]
+    limit: Option<i64>,
+    /// How many items to skip, defaults to 0
+    #[validate(range(min = 0))]
+    offset: Option<i64>,
+}
+
+impl PaginationParams {
+    /// The whole list, unless the request asks for a page of it
+    pub(crate) fn pagination(&self) -> Pagination {
+        Pagination {
+            limit: self.limit,
+            offset: self.offset.unwrap_or_default(),
+        }
+    }
+}
+
+/// A page of a list. The items are the body, the length of the whole list is sent in the
+/// `X-Total-Count` header and the neighbouring pages are linked in the `Link` header
+pub(crate) struct Paginated<T: Serialize> {
+    page: Page<T>,
+    pagination: Pagination,
+}
+
+impl<T: Serialize> Paginated<T> {
+    pub(crate) fn new(page: Page<T>, pagination: Pagination) -> Self {
+        Self { page, pagination }
+    }
+
+    /// The `Link` header value pointing to the first, previous, next and last page
+    fn links(&self, req: &HttpRequest) -> String {
+        let limit = self.pagination.limit.unwrap_or(self.page.total).max(1);
+        let offset = self.pagination.offset;
+        let last = (self.page.total - 1).max(0) / limit * limit;
+
+        // every other query parameter, like filters and sorting, is kept as it was sent
+        let andere = req
+            .query_string()
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .filter(|pair| {
+                let key = pair.split('=').next().unwrap_or_default();
+                key != "limit" && key != "offset"
+            })
+            .collect::<Vec<_>>();
+
+        let link = |ziel: i64, rel: &str| {
+            let mut query = andere.clone();
+            let limit = format!("limit={limit}");
+            let ziel = format!("offset={ziel}");
+            query.push(&limit);
+            query.push(&ziel);
+
+            format!("<{}?{}>; rel=\"{rel}\"", req.path(), query.join("&"))
+        };
+
+        let mut links = vec![link(0, "first")];
+
+        if offset > 0 {
+            links.push(link((offset - limit).max(0), "prev"));
+        }
+
+        if offset + limit < self.page.total {
+            links.push(link(offset + limit, "next"));
+        }
+
+        links.push(link(last, "last"));
+
+        links.join(", ")
+    }
+}
+
+impl<T: Serialize> Responder for Paginated<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        HttpResponse::Ok()
+            .insert_header((header::LINK, self.links(req)))
+            .insert_header((TOTAL_COUNT_HEADER, self.page.total))
+            .json(self.page.items)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::http::header;
+    use actix_web::test::TestRequest;
+    use actix_web::{body, Responder};
+
+    use super::{Paginated, PaginationParams};
+    use crate::domain::pagination::{Page, Pagination};
+
+    #[test]
+    fn only_paginates_when_asked_to() {
+        let ohne = PaginationParams {
+            limit: None,
+            offset: None,
+        };
+        assert_eq!(ohne.pagination(), Pagination::alle());
+
+        let versetzt = PaginationParams {
+            limit: None,
+            offset: Some(10),
+        };
+        assert_eq!(
+            versetzt.pagination(),
+            Pagination {
+                limit: None,
+                offset: 10
+            }
+        );
+    }
+
+    #[actix_web::test]
+    async fn links_neighbouring_pages() {
+        let req = TestRequest::get()
+            .uri("/api/sitzungen?typ=vv&limit=2&offset=2&order=desc")
+            .to_http_request();
+
+        let response = Paginated::new(
+            Page {
+                items: vec![3, 4],
+                total: 5,
+            },
+            Pagination {
+                limit: Some(2),
+                offset: 2,
+            },
+        )
+        .respond_to(&req);
+
+        assert_eq!(
+            response.headers().get(header::LINK).unwrap(),
+            "</api/sitzungen?typ=vv&order=desc&limit=2&offset=0>; rel=\"first\", \
+             </api/sitzungen?typ=vv&order=desc&limit=2&offset=0>; rel=\"prev\", \
+             </api/sitzungen?typ=vv&order=desc&limit=2&offset=4>; rel=\"next\", \
+             </api/sitzungen?typ=vv&order=desc&limit=2&offset=4>; rel=\"last\""
+        );
+        assert_eq!(response.headers().get("x-total-count").unwrap(), "5");
+
+        let body = body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "[3,4]");
+    }
+}